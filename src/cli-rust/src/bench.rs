@@ -0,0 +1,285 @@
+//! `bench`: a load generator sending greetings across several accounts and
+//! payers at once, reporting throughput, latency, failures and fees.
+//!
+//! Only meant for a local test validator: it airdrops to throwaway payers and
+//! sends as fast as the cluster takes it.
+
+use {
+    crate::{
+        create_greeting_account, get_greeting_account,
+        output::{CliBench, CliLatency},
+        println_display, simulate_compute_units, CliError, ComputeUnitLimit, Config, Error,
+        MAX_COMPUTE_UNIT_LIMIT,
+    },
+    clap::ArgMatches,
+    helloworld::instruction::init_greeting,
+    helloworld_client::address::greeting_account_address,
+    solana_clap_utils::input_parsers::value_of,
+    solana_client::{
+        client_error::{ClientError, ClientErrorKind},
+        rpc_client::RpcClient,
+        rpc_request::RpcError,
+    },
+    solana_program::{
+        hash::Hash,
+        instruction::Instruction,
+        message::Message,
+        native_token::{lamports_to_sol, sol_to_lamports},
+        pubkey::Pubkey,
+    },
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::Transaction,
+    },
+    std::{
+        collections::BTreeMap,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Mutex,
+        },
+        thread,
+        time::{Duration, Instant},
+    },
+};
+
+/// The outcome of one benchmark transaction
+struct Sample {
+    latency: Duration,
+    /// The kind of failure, `None` if it confirmed
+    failure: Option<String>,
+}
+
+pub(crate) fn process_bench(config: &Config, matches: &ArgMatches) -> Result<CliBench, Error> {
+    let url = config.rpc_client.url();
+    if !(url.starts_with("http://127.0.0.1:") || url.starts_with("http://localhost:")) {
+        return Err(format!(
+            "bench only runs against a local test validator, not {}",
+            url
+        )
+        .into());
+    }
+    if config.dry_run || !config.online() {
+        return Err("bench sends transactions, it can't --dry-run or --sign-only".into());
+    }
+    let transactions: usize = value_of(matches, "transactions").unwrap();
+    let num_accounts: usize = value_of(matches, "accounts").unwrap();
+    let num_payers: usize = value_of(matches, "payers").unwrap();
+    let concurrency: usize = value_of(matches, "concurrency").unwrap();
+    let payer_funding = sol_to_lamports(value_of(matches, "payer_funding").unwrap());
+
+    let greeting_account_ids = (0..num_accounts)
+        .map(|index| bench_greeting_account(config, index))
+        .collect::<Result<Vec<_>, _>>()?;
+    let payers = (0..num_payers).map(|_| Keypair::new()).collect::<Vec<_>>();
+    fund_payers(config, &payers, payer_funding)?;
+
+    // `Config` isn't `Sync`, so the workers only get what they need of it
+    let program_id = config.program_id;
+    let url = config.rpc_client.url();
+    let commitment = config.rpc_client.commitment();
+    let new_message =
+        |index: usize, compute_budget_instructions: &[Instruction], payer: &Pubkey| {
+            let instructions = [
+                compute_budget_instructions.to_vec(),
+                // Distinct data, or identical transactions would be deduplicated
+                vec![init_greeting(
+                    program_id,
+                    greeting_account_ids[index % num_accounts],
+                    1,
+                    format!("bench {}", index),
+                )],
+            ]
+            .concat();
+            Message::new(&instructions, Some(payer))
+        };
+    let compute_unit_limit = if config.compute_unit_limit == ComputeUnitLimit::Simulated {
+        simulate_compute_units(
+            config,
+            new_message(
+                0,
+                &config.compute_budget_instructions(MAX_COMPUTE_UNIT_LIMIT),
+                &payers[0].pubkey(),
+            ),
+        )?
+    } else {
+        MAX_COMPUTE_UNIT_LIMIT
+    };
+    let compute_budget_instructions = config.compute_budget_instructions(compute_unit_limit);
+
+    println_display(
+        config,
+        format!(
+            "Sending {} greetings to {} accounts from {} payers, {} at a time...",
+            transactions, num_accounts, num_payers, concurrency
+        ),
+    );
+    let balances_before = get_balances(config, &payers)?;
+    let next_index = AtomicUsize::new(0);
+    let blockhash = Mutex::new((config.rpc_client.get_latest_blockhash()?, Instant::now()));
+    let samples = Mutex::new(Vec::with_capacity(transactions));
+    let started = Instant::now();
+    thread::scope(|scope| {
+        for _ in 0..concurrency.min(transactions) {
+            scope.spawn(|| {
+                // Each worker has its own connection
+                let rpc_client = RpcClient::new_with_commitment(url.clone(), commitment);
+                loop {
+                    let index = next_index.fetch_add(1, Ordering::Relaxed);
+                    if index >= transactions {
+                        break;
+                    }
+                    let payer = &payers[index % num_payers];
+                    let message = new_message(index, &compute_budget_instructions, &payer.pubkey());
+                    let transaction = Transaction::new(
+                        &[payer],
+                        message,
+                        recent_blockhash(&rpc_client, &blockhash),
+                    );
+                    let sent = Instant::now();
+                    let result = rpc_client.send_and_confirm_transaction(&transaction);
+                    let sample = Sample {
+                        latency: sent.elapsed(),
+                        failure: result.err().map(|err| failure_kind(&err)),
+                    };
+                    samples.lock().unwrap().push(sample);
+                }
+            });
+        }
+    });
+    let elapsed = started.elapsed();
+    let samples = samples.into_inner().unwrap();
+
+    let balances_after = get_balances(config, &payers)?;
+    let total_fees = balances_before
+        .iter()
+        .sum::<u64>()
+        .saturating_sub(balances_after.iter().sum());
+    let mut latencies = samples
+        .iter()
+        .filter(|sample| sample.failure.is_none())
+        .map(|sample| sample.latency)
+        .collect::<Vec<_>>();
+    latencies.sort();
+    let mut failures = BTreeMap::new();
+    for failure in samples.iter().filter_map(|sample| sample.failure.as_ref()) {
+        *failures.entry(failure.clone()).or_insert(0) += 1;
+    }
+
+    Ok(CliBench {
+        transactions,
+        accounts: num_accounts,
+        payers: num_payers,
+        concurrency,
+        confirmed: latencies.len(),
+        failed: samples.len() - latencies.len(),
+        elapsed_secs: elapsed.as_secs_f64(),
+        tps: latencies.len() as f64 / elapsed.as_secs_f64(),
+        latency: CliLatency {
+            p50_ms: percentile(&latencies, 50).as_millis() as u64,
+            p90_ms: percentile(&latencies, 90).as_millis() as u64,
+            p99_ms: percentile(&latencies, 99).as_millis() as u64,
+            max_ms: latencies.last().copied().unwrap_or_default().as_millis() as u64,
+        },
+        failures,
+        total_fees: lamports_to_sol(total_fees),
+    })
+}
+
+/// The `index`th benchmark greeting account of the fee payer, created if
+/// it doesn't exist yet
+fn bench_greeting_account(config: &Config, index: usize) -> Result<Pubkey, Error> {
+    let seed = format!("bench-{}", index);
+    let greeting_account_id =
+        greeting_account_address(&config.fee_payer.pubkey(), &seed, &config.program_id)?;
+    match get_greeting_account(config, &greeting_account_id) {
+        Ok(_) => {}
+        Err(err) if matches!(err.downcast_ref(), Some(CliError::AccountNotFound(_))) => {
+            create_greeting_account(config, &seed, 0)?;
+        }
+        Err(err) => return Err(err),
+    }
+    Ok(greeting_account_id)
+}
+
+/// Airdrops `lamports` to each payer, so that they don't have to wait on one
+/// another's balance
+fn fund_payers(config: &Config, payers: &[Keypair], lamports: u64) -> Result<(), Error> {
+    println_display(
+        config,
+        format!(
+            "Airdropping {} SOL to {} payers...",
+            lamports_to_sol(lamports),
+            payers.len()
+        ),
+    );
+    let mut signatures = vec![];
+    for payer in payers {
+        signatures.push(
+            config
+                .rpc_client
+                .request_airdrop(&payer.pubkey(), lamports)?,
+        );
+    }
+    for signature in signatures {
+        config
+            .rpc_client
+            .poll_for_signature_with_commitment(&signature, config.rpc_client.commitment())?;
+    }
+    Ok(())
+}
+
+fn get_balances(config: &Config, payers: &[Keypair]) -> Result<Vec<u64>, Error> {
+    payers
+        .iter()
+        .map(|payer| Ok(config.rpc_client.get_balance(&payer.pubkey())?))
+        .collect()
+}
+
+/// A blockhash shared by the workers, refreshed every few seconds so that
+/// transactions don't expire in flight. Kept a while longer if refreshing
+/// fails, the failures will show when sending.
+fn recent_blockhash(rpc_client: &RpcClient, blockhash: &Mutex<(Hash, Instant)>) -> Hash {
+    let mut blockhash = blockhash.lock().unwrap();
+    if blockhash.1.elapsed() > Duration::from_secs(10) {
+        if let Ok(latest_blockhash) = rpc_client.get_latest_blockhash() {
+            *blockhash = (latest_blockhash, Instant::now());
+        }
+    }
+    blockhash.0
+}
+
+/// Groups failures by what went wrong
+fn failure_kind(err: &ClientError) -> String {
+    match err.get_transaction_error() {
+        Some(transaction_error) => transaction_error.to_string(),
+        None => match err.kind() {
+            ClientErrorKind::RpcError(RpcError::ForUser(_)) => "unconfirmed".to_string(),
+            ClientErrorKind::Io(_) | ClientErrorKind::Reqwest(_) => "network".to_string(),
+            _ => err.to_string(),
+        },
+    }
+}
+
+/// The nearest-rank `percent`th percentile of `sorted`, zero if it's empty
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    if sorted.is_empty() {
+        return Duration::default();
+    }
+    let rank = (sorted.len() * percent).div_ceil(100);
+    sorted[rank.max(1) - 1]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_percentile() {
+        let latencies = (1..=10).map(Duration::from_millis).collect::<Vec<_>>();
+        assert_eq!(percentile(&latencies, 50), Duration::from_millis(5));
+        assert_eq!(percentile(&latencies, 90), Duration::from_millis(9));
+        assert_eq!(percentile(&latencies, 99), Duration::from_millis(10));
+        assert_eq!(percentile(&latencies, 0), Duration::from_millis(1));
+        assert_eq!(percentile(&[], 50), Duration::default());
+    }
+}
@@ -0,0 +1,483 @@
+//! `deploy`, `upgrade` and `set-upgrade-authority`: ship the program through
+//! the upgradeable BPF loader. `verify` checks what got shipped.
+//!
+//! The program is first written to a buffer account a chunk per transaction,
+//! then deployed or upgraded from it in one go. A buffer left behind by a
+//! failed write can be passed back with `--buffer`; only the chunks that
+//! differ are written again.
+
+use {
+    crate::{
+        output::{CliProgram, CliVerifiedProgram},
+        println_display, send_instructions, CliError, Config, Error, PROGRAM_ID,
+    },
+    clap::ArgMatches,
+    solana_clap_utils::input_parsers::{pubkey_of, value_of},
+    solana_program::{
+        bpf_loader_upgradeable::{
+            self, create_buffer, deploy_with_max_program_len, set_upgrade_authority, upgrade,
+            write, UpgradeableLoaderState,
+        },
+        hash::hash,
+        instruction::Instruction,
+        message::Message,
+        pubkey::Pubkey,
+    },
+    solana_sdk::{
+        packet::PACKET_DATA_SIZE,
+        signature::{read_keypair_file, Keypair, Signature, Signer},
+        transaction::Transaction,
+    },
+};
+
+pub(crate) fn process_deploy(config: &Config, matches: &ArgMatches) -> Result<CliProgram, Error> {
+    if config.dry_run {
+        return Err("--dry-run isn't supported by deploy".into());
+    }
+    let program_keypair = read_program_keypair(matches)?;
+    let program_data = read_program(matches)?;
+    let max_len = value_of(matches, "max_len").unwrap_or(program_data.len() * 2);
+    if max_len < program_data.len() {
+        return Err(format!("--max-len must be at least {} bytes", program_data.len()).into());
+    }
+
+    let mut cli_program = deploy_program(
+        config,
+        &program_keypair,
+        &program_data,
+        max_len,
+        pubkey_of(matches, "buffer"),
+    )?;
+    if matches.is_present("final") {
+        let program_id = program_keypair.pubkey();
+        let transaction = send_instructions(
+            config,
+            &[set_upgrade_authority(
+                &program_id,
+                &config.upgrade_authority().pubkey(),
+                None,
+            )],
+            &[config.upgrade_authority()],
+            0,
+        )?;
+        cli_program = CliProgram::new(&program_id, None);
+        cli_program.signature = transaction.signature;
+    }
+    Ok(cli_program)
+}
+
+pub(crate) fn process_upgrade(config: &Config, matches: &ArgMatches) -> Result<CliProgram, Error> {
+    if config.dry_run {
+        return Err("--dry-run isn't supported by upgrade".into());
+    }
+    let program_data = read_program(matches)?;
+    upgrade_program(
+        config,
+        &config.program_id,
+        &program_data,
+        pubkey_of(matches, "buffer"),
+    )
+}
+
+/// Deploys `program_data` as a new program, or upgrades the program if a
+/// different build is deployed
+pub(crate) fn deploy_or_upgrade(
+    config: &Config,
+    program_keypair: &Keypair,
+    program_data: &[u8],
+) -> Result<CliProgram, Error> {
+    let program_id = program_keypair.pubkey();
+    if config
+        .rpc_client
+        .get_account_with_commitment(&program_id, config.rpc_client.commitment())?
+        .value
+        .is_none()
+    {
+        return deploy_program(
+            config,
+            program_keypair,
+            program_data,
+            program_data.len() * 2,
+            None,
+        );
+    }
+    match get_programdata(config, &program_id)? {
+        (
+            UpgradeableLoaderState::ProgramData {
+                upgrade_authority_address,
+                ..
+            },
+            deployed,
+        ) if strip_padding(&deployed) == strip_padding(program_data) => {
+            println_display(config, format!("Program {} is up to date", program_id));
+            Ok(CliProgram::new(
+                &program_id,
+                upgrade_authority_address.as_ref(),
+            ))
+        }
+        _ => upgrade_program(config, &program_id, program_data, None),
+    }
+}
+
+fn deploy_program(
+    config: &Config,
+    program_keypair: &Keypair,
+    program_data: &[u8],
+    max_len: usize,
+    resume_buffer: Option<Pubkey>,
+) -> Result<CliProgram, Error> {
+    let program_id = program_keypair.pubkey();
+    if config
+        .rpc_client
+        .get_account_with_commitment(&program_id, config.rpc_client.commitment())?
+        .value
+        .is_some()
+    {
+        return Err(format!("Program {} is already deployed, use `upgrade`", program_id).into());
+    }
+    let upgrade_authority = config.upgrade_authority();
+    let buffer = write_buffer(config, program_data, resume_buffer)?;
+
+    let program_lamports = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(UpgradeableLoaderState::program_len()?)?;
+    let programdata_lamports = config.rpc_client.get_minimum_balance_for_rent_exemption(
+        UpgradeableLoaderState::programdata_len(max_len)?,
+    )?;
+    println_display(config, format!("Deploying program {}", program_id));
+    let transaction = send_instructions(
+        config,
+        &deploy_with_max_program_len(
+            &config.fee_payer.pubkey(),
+            &program_id,
+            &buffer,
+            &upgrade_authority.pubkey(),
+            program_lamports,
+            max_len,
+        )?,
+        &[program_keypair, upgrade_authority],
+        program_lamports + programdata_lamports,
+    )?;
+
+    let mut cli_program = CliProgram::new(&program_id, Some(&upgrade_authority.pubkey()));
+    cli_program.signature = transaction.signature;
+    Ok(cli_program)
+}
+
+fn upgrade_program(
+    config: &Config,
+    program_id: &Pubkey,
+    program_data: &[u8],
+    resume_buffer: Option<Pubkey>,
+) -> Result<CliProgram, Error> {
+    let upgrade_authority = config.upgrade_authority();
+    match get_upgrade_authority(config, program_id)? {
+        Some(authority) if authority == upgrade_authority.pubkey() => {}
+        Some(authority) => {
+            return Err(format!(
+                "Program {} can only be upgraded by {}, pass it with --upgrade-authority",
+                program_id, authority
+            )
+            .into())
+        }
+        None => return Err(format!("Program {} is final", program_id).into()),
+    }
+
+    let buffer = write_buffer(config, program_data, resume_buffer)?;
+    println_display(config, format!("Upgrading program {}", program_id));
+    let transaction = send_instructions(
+        config,
+        &[upgrade(
+            program_id,
+            &buffer,
+            &upgrade_authority.pubkey(),
+            &config.fee_payer.pubkey(),
+        )],
+        &[upgrade_authority],
+        0,
+    )?;
+
+    let mut cli_program = CliProgram::new(program_id, Some(&upgrade_authority.pubkey()));
+    cli_program.signature = transaction.signature;
+    Ok(cli_program)
+}
+
+pub(crate) fn process_set_upgrade_authority(
+    config: &Config,
+    matches: &ArgMatches,
+) -> Result<CliProgram, Error> {
+    let new_upgrade_authority = pubkey_of(matches, "new_upgrade_authority");
+    let transaction = send_instructions(
+        config,
+        &[set_upgrade_authority(
+            &config.program_id,
+            &config.upgrade_authority().pubkey(),
+            new_upgrade_authority.as_ref(),
+        )],
+        &[config.upgrade_authority()],
+        0,
+    )?;
+
+    let mut cli_program = CliProgram::new(&config.program_id, new_upgrade_authority.as_ref());
+    cli_program.signature = transaction.signature;
+    Ok(cli_program)
+}
+
+pub(crate) fn process_verify(
+    config: &Config,
+    matches: &ArgMatches,
+) -> Result<CliVerifiedProgram, Error> {
+    let program_id = config.program_id;
+    let (upgrade_authority, last_deploy_slot, programdata) =
+        match get_programdata(config, &program_id)? {
+            (
+                UpgradeableLoaderState::ProgramData {
+                    slot,
+                    upgrade_authority_address,
+                },
+                data,
+            ) => (upgrade_authority_address, slot, data),
+            _ => return Err(format!("{} has no programdata account", program_id).into()),
+        };
+    let local_program = read_program(matches)?;
+
+    let deployed = strip_padding(&programdata);
+    let local = strip_padding(&local_program);
+    let cli_verified_program = CliVerifiedProgram {
+        program: CliProgram::new(&program_id, upgrade_authority.as_ref()),
+        last_deploy_slot,
+        deployed_trimmed_hash: hash(deployed).to_string(),
+        deployed_trimmed_len: deployed.len(),
+        local_trimmed_hash: hash(local).to_string(),
+        local_trimmed_len: local.len(),
+        local_file_hash: hash(&local_program).to_string(),
+        local_file_len: local_program.len(),
+        matches: deployed == local,
+    };
+    if !cli_verified_program.matches {
+        // Printed anyway, the hashes are what tells the builds apart
+        println!(
+            "{}",
+            config.output_format.formatted_string(&cli_verified_program)
+        );
+        return Err(CliError::ProgramMismatch(program_id).into());
+    }
+    Ok(cli_verified_program)
+}
+
+/// Drops the zeros programdata is padded with up to its max length. The same
+/// is done to the local build, in case it happens to end in zeros.
+fn strip_padding(data: &[u8]) -> &[u8] {
+    let len = data
+        .iter()
+        .rposition(|byte| *byte != 0)
+        .map_or(0, |i| i + 1);
+    &data[..len]
+}
+
+/// The program keypair at `--program`, which deploying has to sign with
+pub(crate) fn read_program_keypair(matches: &ArgMatches) -> Result<Keypair, Error> {
+    let program_keypair_path = matches.value_of("program_id").unwrap_or(PROGRAM_ID);
+    read_keypair_file(program_keypair_path)
+        .map_err(|_| "The program keypair is needed to deploy, pass its path with --program".into())
+}
+
+pub(crate) fn read_program(matches: &ArgMatches) -> Result<Vec<u8>, Error> {
+    let program_so = matches.value_of("program_so").unwrap();
+    std::fs::read(program_so).map_err(|e| format!("Unable to read {}: {}", program_so, e).into())
+}
+
+/// The upgrade authority recorded in the program's programdata account,
+/// `None` if the program is final
+fn get_upgrade_authority(config: &Config, program_id: &Pubkey) -> Result<Option<Pubkey>, Error> {
+    match get_programdata(config, program_id)? {
+        (
+            UpgradeableLoaderState::ProgramData {
+                upgrade_authority_address,
+                ..
+            },
+            _,
+        ) => Ok(upgrade_authority_address),
+        _ => Err(format!("{} has no programdata account", program_id).into()),
+    }
+}
+
+/// Fetches the programdata account of an upgradeable program, returning its
+/// header and data
+fn get_programdata(
+    config: &Config,
+    program_id: &Pubkey,
+) -> Result<(UpgradeableLoaderState, Vec<u8>), Error> {
+    let program_account = config.rpc_client.get_account(program_id)?;
+    if program_account.owner != bpf_loader_upgradeable::id() {
+        return Err(format!("{} is not an upgradeable program", program_id).into());
+    }
+    let programdata_address = match bincode::deserialize(&program_account.data)? {
+        UpgradeableLoaderState::Program {
+            programdata_address,
+        } => programdata_address,
+        _ => return Err(format!("{} is not an upgradeable program", program_id).into()),
+    };
+    let mut programdata = config.rpc_client.get_account(&programdata_address)?.data;
+    let state = bincode::deserialize(&programdata)?;
+    let data = programdata.split_off(UpgradeableLoaderState::programdata_data_offset()?);
+    Ok((state, data))
+}
+
+/// Writes `program_data` to a new buffer account, or to `resume_buffer` left
+/// over from an earlier attempt, returning the buffer's address
+fn write_buffer(
+    config: &Config,
+    program_data: &[u8],
+    resume_buffer: Option<Pubkey>,
+) -> Result<Pubkey, Error> {
+    let authority = config.upgrade_authority();
+    let buffer_len = UpgradeableLoaderState::buffer_len(program_data.len())?;
+
+    let (buffer, written) = match resume_buffer {
+        Some(buffer) => {
+            let account = config.rpc_client.get_account(&buffer)?;
+            let resumable = account.owner == bpf_loader_upgradeable::id()
+                && account.data.len() == buffer_len
+                && matches!(
+                    bincode::deserialize(&account.data)?,
+                    UpgradeableLoaderState::Buffer { authority_address }
+                        if authority_address == Some(authority.pubkey())
+                );
+            if !resumable {
+                return Err(format!(
+                    "{} is not a buffer of {} bytes with authority {}",
+                    buffer,
+                    program_data.len(),
+                    authority.pubkey()
+                )
+                .into());
+            }
+            println_display(config, format!("Resuming the write to buffer {}", buffer));
+            let written = account.data[UpgradeableLoaderState::buffer_data_offset()?..].to_vec();
+            (buffer, written)
+        }
+        None => {
+            let buffer_keypair = Keypair::new();
+            let lamports = config
+                .rpc_client
+                .get_minimum_balance_for_rent_exemption(buffer_len)?;
+            println_display(
+                config,
+                format!("Creating buffer {}", buffer_keypair.pubkey()),
+            );
+            send_instructions(
+                config,
+                &create_buffer(
+                    &config.fee_payer.pubkey(),
+                    &buffer_keypair.pubkey(),
+                    &authority.pubkey(),
+                    lamports,
+                    program_data.len(),
+                )?,
+                &[&buffer_keypair],
+                lamports,
+            )?;
+            (buffer_keypair.pubkey(), vec![0; program_data.len()])
+        }
+    };
+
+    let chunk_size = max_chunk_size(
+        &config.fee_payer.pubkey(),
+        &buffer,
+        &authority.pubkey(),
+        &config.compute_budget_instructions(0),
+    );
+    for (index, chunk) in program_data.chunks(chunk_size).enumerate() {
+        let offset = index * chunk_size;
+        // A fresh buffer is zeroed, so zero chunks don't need writing either
+        if written[offset..offset + chunk.len()] == *chunk {
+            continue;
+        }
+        send_instructions(
+            config,
+            &[write(
+                &buffer,
+                &authority.pubkey(),
+                offset as u32,
+                chunk.to_vec(),
+            )],
+            &[authority],
+            0,
+        )
+        .map_err(|err| {
+            format!(
+                "Writing failed at offset {}: {}. Resume with --buffer {}",
+                offset, err, buffer
+            )
+        })?;
+    }
+    Ok(buffer)
+}
+
+/// The largest chunk of program data a `Write` transaction can carry after
+/// `compute_budget_instructions`
+fn max_chunk_size(
+    fee_payer: &Pubkey,
+    buffer: &Pubkey,
+    authority: &Pubkey,
+    compute_budget_instructions: &[Instruction],
+) -> usize {
+    let instructions = [
+        compute_budget_instructions,
+        &[write(buffer, authority, 0, vec![])],
+    ]
+    .concat();
+    let message = Message::new(&instructions, Some(fee_payer));
+    let transaction = Transaction {
+        signatures: vec![Signature::default(); message.header.num_required_signatures as usize],
+        message,
+    };
+    let transaction_size = bincode::serialized_size(&transaction).unwrap() as usize;
+    // One more byte for the longer length prefix of a non-empty chunk
+    PACKET_DATA_SIZE - transaction_size - 1
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, solana_sdk::compute_budget::ComputeBudgetInstruction};
+
+    #[test]
+    fn test_strip_padding() {
+        assert_eq!(strip_padding(&[1, 0, 2, 0, 0]), [1, 0, 2]);
+        assert_eq!(strip_padding(&[1, 2]), [1, 2]);
+        assert_eq!(strip_padding(&[0, 0]), [] as [u8; 0]);
+    }
+
+    #[test]
+    fn test_max_chunk_size() {
+        let fee_payer = Pubkey::new_unique();
+        let buffer = Pubkey::new_unique();
+        let compute_budget_instructions = vec![
+            ComputeBudgetInstruction::set_compute_unit_limit(0),
+            ComputeBudgetInstruction::set_compute_unit_price(0),
+        ];
+        for authority in [fee_payer, Pubkey::new_unique()] {
+            for compute_budget_instructions in [&[][..], &compute_budget_instructions] {
+                let chunk_size =
+                    max_chunk_size(&fee_payer, &buffer, &authority, compute_budget_instructions);
+                let instructions = [
+                    compute_budget_instructions,
+                    &[write(&buffer, &authority, 0, vec![0; chunk_size])],
+                ]
+                .concat();
+                let message = Message::new(&instructions, Some(&fee_payer));
+                let transaction = Transaction {
+                    signatures: vec![
+                        Signature::default();
+                        message.header.num_required_signatures as usize
+                    ],
+                    message,
+                };
+                assert!(
+                    bincode::serialized_size(&transaction).unwrap() as usize <= PACKET_DATA_SIZE
+                );
+            }
+        }
+    }
+}
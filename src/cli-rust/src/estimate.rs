@@ -0,0 +1,438 @@
+//! `estimate`: what greeting costs, in rent and fees, before signing
+//! anything. A planned batch can be projected from a CSV of instruction
+//! counts.
+
+use {
+    crate::{
+        output::{CliAccountRent, CliBatchEstimate, CliBatchLine, CliEstimate, CliInstructionCost},
+        Config, Error, MAX_COMPUTE_UNIT_LIMIT,
+    },
+    clap::ArgMatches,
+    helloworld::{
+        instruction::{
+            close, init_greeting, init_greeting_with_nonce, initialize, set_flags, signed_greeting,
+        },
+        processor::{find_greeter_nonce_address, GreeterNonce, GreetingAccount},
+    },
+    helloworld_client::address::{greeting_account_address, DEFAULT_SEED},
+    solana_program::{
+        instruction::Instruction, message::Message, native_token::lamports_to_sol,
+        program_pack::Pack, pubkey::Pubkey,
+    },
+    solana_sdk::system_instruction::create_account_with_seed,
+    std::{convert::TryFrom, str::FromStr},
+};
+
+/// The transactions the CLI sends, by `--batch` name
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum InstructionType {
+    Create,
+    Greet,
+    GreetWithNonce,
+    SignedGreeting,
+    SetFlags,
+    Close,
+}
+
+impl InstructionType {
+    const ALL: [InstructionType; 6] = [
+        InstructionType::Create,
+        InstructionType::Greet,
+        InstructionType::GreetWithNonce,
+        InstructionType::SignedGreeting,
+        InstructionType::SetFlags,
+        InstructionType::Close,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            InstructionType::Create => "create",
+            InstructionType::Greet => "greet",
+            InstructionType::GreetWithNonce => "greet-with-nonce",
+            InstructionType::SignedGreeting => "signed-greeting",
+            InstructionType::SetFlags => "set-flags",
+            InstructionType::Close => "close",
+        }
+    }
+
+    /// The instructions of a transaction of this type, paid for by `fee_payer`
+    fn instructions(self, program_id: Pubkey, fee_payer: &Pubkey) -> Vec<Instruction> {
+        let seed = DEFAULT_SEED;
+        let greeting_account_id = greeting_account_address(fee_payer, seed, &program_id).unwrap();
+        match self {
+            InstructionType::Create => vec![
+                create_account_with_seed(
+                    fee_payer,
+                    &greeting_account_id,
+                    fee_payer,
+                    seed,
+                    0,
+                    GreetingAccount::LEN as u64,
+                    &program_id,
+                ),
+                initialize(
+                    program_id,
+                    greeting_account_id,
+                    *fee_payer,
+                    seed.to_string(),
+                    *fee_payer,
+                    0,
+                ),
+            ],
+            InstructionType::Greet => vec![init_greeting(
+                program_id,
+                greeting_account_id,
+                1,
+                String::new(),
+            )],
+            InstructionType::GreetWithNonce => vec![init_greeting_with_nonce(
+                program_id,
+                greeting_account_id,
+                fee_payer,
+                fee_payer,
+                1,
+                String::new(),
+                0,
+            )],
+            InstructionType::SignedGreeting => signed_greeting(
+                program_id,
+                greeting_account_id,
+                &Pubkey::new_unique(),
+                fee_payer,
+                &[0; 64],
+                1,
+                0,
+            ),
+            InstructionType::SetFlags => {
+                vec![set_flags(program_id, greeting_account_id, *fee_payer, 0)]
+            }
+            InstructionType::Close => vec![close(
+                program_id,
+                greeting_account_id,
+                *fee_payer,
+                *fee_payer,
+            )],
+        }
+    }
+
+    /// Lamports the transaction moves into rent-exempt accounts, negative if
+    /// it gets them back. A greeter's first nonce greeting creates its nonce
+    /// account, later ones move nothing.
+    fn rent(self, rents: &Rents) -> Result<i64, String> {
+        Ok(match self {
+            InstructionType::Create => signed_lamports(rents.greeting_account)?,
+            InstructionType::Close => -signed_lamports(rents.greeting_account)?,
+            InstructionType::GreetWithNonce => signed_lamports(rents.greeter_nonce)?,
+            _ => 0,
+        })
+    }
+}
+
+/// Rent-exempt minimums of the accounts the program creates
+struct Rents {
+    greeting_account: u64,
+    greeter_nonce: u64,
+}
+
+/// One line of a batch: its transactions, their fees and their rent
+type BatchLine = (InstructionType, u64, u64, i64);
+
+/// Fees and rent of each line of `batch`. Only the first nonce greeting pays
+/// for the fee payer's nonce account, unless `nonce_account_exists`.
+fn batch_lines(
+    batch: &[(InstructionType, u64)],
+    fee_of: impl Fn(InstructionType) -> u64,
+    rents: &Rents,
+    mut nonce_account_exists: bool,
+) -> Result<Vec<BatchLine>, String> {
+    let mut lines = vec![];
+    for (instruction_type, count) in batch.iter().copied() {
+        let fees = fee_of(instruction_type)
+            .checked_mul(count)
+            .ok_or_else(|| overflow(instruction_type.name()))?;
+        let rent = match instruction_type {
+            InstructionType::GreetWithNonce if nonce_account_exists || count == 0 => 0,
+            InstructionType::GreetWithNonce => {
+                nonce_account_exists = true;
+                instruction_type.rent(rents)?
+            }
+            _ => instruction_type
+                .rent(rents)?
+                .checked_mul(signed_lamports(count)?)
+                .ok_or_else(|| overflow(instruction_type.name()))?,
+        };
+        lines.push((instruction_type, count, fees, rent));
+    }
+    Ok(lines)
+}
+
+/// `lamports` as a signed amount, for rent that may be refunded
+fn signed_lamports(lamports: u64) -> Result<i64, String> {
+    i64::try_from(lamports).map_err(|_| format!("{} is too large", lamports))
+}
+
+fn overflow(what: &str) -> String {
+    format!("the cost of {} overflows", what)
+}
+
+impl FromStr for InstructionType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        InstructionType::ALL
+            .iter()
+            .copied()
+            .find(|instruction_type| instruction_type.name() == s)
+            .ok_or_else(|| {
+                let names = InstructionType::ALL.map(InstructionType::name);
+                format!(
+                    "unknown instruction {}, expected one of {}",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// Parses `instruction,count` lines, with an optional header; blank lines and
+/// `#` comments are skipped
+fn parse_batch(contents: &str) -> Result<Vec<(InstructionType, u64)>, String> {
+    let mut batch = vec![];
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty()
+            || line.starts_with('#')
+            || (index == 0 && line.starts_with("instruction"))
+        {
+            continue;
+        }
+        let parse_line = || {
+            let (instruction, count) = line
+                .split_once(',')
+                .ok_or_else(|| "expected instruction,count".to_string())?;
+            let count = count
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid count {}", count.trim()))?;
+            Ok::<_, String>((instruction.trim().parse()?, count))
+        };
+        batch.push(parse_line().map_err(|e| format!("line {}: {}", index + 1, e))?);
+    }
+    Ok(batch)
+}
+
+pub(crate) fn process_estimate(
+    config: &Config,
+    matches: &ArgMatches,
+) -> Result<CliEstimate, Error> {
+    let rents = Rents {
+        greeting_account: config
+            .rpc_client
+            .get_minimum_balance_for_rent_exemption(GreetingAccount::LEN)?,
+        greeter_nonce: config
+            .rpc_client
+            .get_minimum_balance_for_rent_exemption(GreeterNonce::LEN)?,
+    };
+    let accounts = vec![
+        CliAccountRent {
+            name: "greeting account".to_string(),
+            size: GreetingAccount::LEN,
+            rent: lamports_to_sol(rents.greeting_account),
+        },
+        CliAccountRent {
+            name: "greeter nonce".to_string(),
+            size: GreeterNonce::LEN,
+            rent: lamports_to_sol(rents.greeter_nonce),
+        },
+    ];
+
+    // Fees only depend on the signatures and compute budget, not on who signs
+    let fee_payer = config.fee_payer.pubkey();
+    let blockhash = config.rpc_client.get_latest_blockhash()?;
+    let fees = InstructionType::ALL
+        .iter()
+        .map(|instruction_type| {
+            let instructions = [
+                config.compute_budget_instructions(MAX_COMPUTE_UNIT_LIMIT),
+                instruction_type.instructions(config.program_id, &fee_payer),
+            ]
+            .concat();
+            let mut message = Message::new(&instructions, Some(&fee_payer));
+            message.recent_blockhash = blockhash;
+            let fee = config.rpc_client.get_fee_for_message(&message)?;
+            Ok((
+                *instruction_type,
+                message.header.num_required_signatures,
+                fee,
+            ))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    let instructions = fees
+        .iter()
+        .map(|(instruction_type, signatures, fee)| {
+            Ok(CliInstructionCost {
+                instruction: instruction_type.name().to_string(),
+                signatures: *signatures,
+                fee: lamports_to_sol(*fee),
+                rent: sol_of(instruction_type.rent(&rents)?),
+            })
+        })
+        .collect::<Result<_, String>>()?;
+
+    let batch = match matches.value_of("batch") {
+        Some(path) => {
+            let contents = std::fs::read_to_string(path)
+                .map_err(|e| format!("Unable to read {}: {}", path, e))?;
+            let batch = parse_batch(&contents).map_err(|e| format!("{}: {}", path, e))?;
+            let fee_of = |instruction_type| {
+                fees.iter()
+                    .find(|(t, _, _)| *t == instruction_type)
+                    .map(|(_, _, fee)| *fee)
+                    .unwrap()
+            };
+            let (nonce_account_id, _) = find_greeter_nonce_address(&config.program_id, &fee_payer);
+            let nonce_account_exists = config
+                .rpc_client
+                .get_account_with_commitment(&nonce_account_id, config.rpc_client.commitment())?
+                .value
+                .is_some();
+            let lines = batch_lines(&batch, fee_of, &rents, nonce_account_exists)
+                .map_err(|e| format!("{}: {}", path, e))?;
+            let batch_overflow = || format!("{}: {}", path, overflow("the batch"));
+            let total_fees = lines
+                .iter()
+                .try_fold(0u64, |total, (_, _, fees, _)| total.checked_add(*fees))
+                .ok_or_else(batch_overflow)?;
+            let total_rent = lines
+                .iter()
+                .try_fold(0i64, |total, (_, _, _, rent)| total.checked_add(*rent))
+                .ok_or_else(batch_overflow)?;
+            let total = signed_lamports(total_fees)
+                .ok()
+                .and_then(|total_fees| total_fees.checked_add(total_rent))
+                .ok_or_else(batch_overflow)?;
+            // Refunds come after the transactions that need the lamports
+            let required = total_fees
+                .checked_add(total_rent.max(0) as u64)
+                .ok_or_else(batch_overflow)?;
+            let fee_payer_balance = config.rpc_client.get_balance(&fee_payer)?;
+            Some(CliBatchEstimate {
+                file: path.to_string(),
+                lines: lines
+                    .iter()
+                    .map(|(instruction_type, count, fees, rent)| CliBatchLine {
+                        instruction: instruction_type.name().to_string(),
+                        count: *count,
+                        fees: lamports_to_sol(*fees),
+                        rent: sol_of(*rent),
+                    })
+                    .collect(),
+                transactions: batch.iter().map(|(_, count)| count).sum(),
+                fees: lamports_to_sol(total_fees),
+                rent: sol_of(total_rent),
+                total: sol_of(total),
+                fee_payer_balance: lamports_to_sol(fee_payer_balance),
+                sufficient: fee_payer_balance >= required,
+            })
+        }
+        None => None,
+    };
+
+    Ok(CliEstimate {
+        accounts,
+        instructions,
+        batch,
+    })
+}
+
+/// `lamports_to_sol` for amounts that may be negative
+fn sol_of(lamports: i64) -> f64 {
+    lamports.signum() as f64 * lamports_to_sol(lamports.unsigned_abs())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_batch() {
+        let batch = parse_batch("instruction,count\ncreate,2\n\n# the bulk\ngreet, 100\nclose,2\n");
+        assert_eq!(
+            batch,
+            Ok(vec![
+                (InstructionType::Create, 2),
+                (InstructionType::Greet, 100),
+                (InstructionType::Close, 2),
+            ])
+        );
+        assert_eq!(
+            parse_batch("greet,1\nwave,2"),
+            Err(
+                "line 2: unknown instruction wave, expected one of create, greet, \
+                greet-with-nonce, signed-greeting, set-flags, close"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            parse_batch("greet"),
+            Err("line 1: expected instruction,count".to_string())
+        );
+        assert_eq!(
+            parse_batch("greet,-1"),
+            Err("line 1: invalid count -1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_batch_lines() {
+        let rents = Rents {
+            greeting_account: 1000,
+            greeter_nonce: 100,
+        };
+        let fee_of = |_| 5000;
+        let batch = [
+            (InstructionType::Create, 2),
+            (InstructionType::GreetWithNonce, 3),
+            (InstructionType::GreetWithNonce, 4),
+            (InstructionType::Close, 1),
+        ];
+        assert_eq!(
+            batch_lines(&batch, fee_of, &rents, false),
+            Ok(vec![
+                (InstructionType::Create, 2, 10_000, 2000),
+                (InstructionType::GreetWithNonce, 3, 15_000, 100),
+                (InstructionType::GreetWithNonce, 4, 20_000, 0),
+                (InstructionType::Close, 1, 5000, -1000),
+            ])
+        );
+        // The fee payer already greeted with a nonce
+        assert_eq!(
+            batch_lines(&batch[1..2], fee_of, &rents, true),
+            Ok(vec![(InstructionType::GreetWithNonce, 3, 15_000, 0)])
+        );
+
+        assert_eq!(
+            batch_lines(&[(InstructionType::Greet, u64::MAX)], fee_of, &rents, false),
+            Err("the cost of greet overflows".to_string())
+        );
+        assert_eq!(
+            batch_lines(&[(InstructionType::Close, u64::MAX)], |_| 0, &rents, false),
+            Err(format!("{} is too large", u64::MAX))
+        );
+        assert_eq!(
+            batch_lines(
+                &[(InstructionType::Create, i64::MAX as u64 / 100)],
+                |_| 0,
+                &rents,
+                false
+            ),
+            Err("the cost of create overflows".to_string())
+        );
+    }
+
+    #[test]
+    fn test_sol_of() {
+        assert_eq!(sol_of(1_500_000_000), 1.5);
+        assert_eq!(sol_of(-1_500_000_000), -1.5);
+    }
+}
@@ -0,0 +1,286 @@
+//! `history`: the transactions that touched a greeting account, with their
+//! greeting instructions and program logs decoded.
+
+use {
+    crate::{
+        greeting_account_of,
+        output::{CliGreetingInstruction, CliHistory, CliHistoryEntry},
+        Config, Error,
+    },
+    borsh::BorshDeserialize,
+    clap::ArgMatches,
+    helloworld::instruction::GreetingInstruction,
+    solana_clap_utils::input_parsers::value_of,
+    solana_client::{
+        rpc_client::GetConfirmedSignaturesForAddress2Config, rpc_config::RpcTransactionConfig,
+        rpc_response::RpcConfirmedTransactionStatusWithSignature,
+    },
+    solana_program::pubkey::Pubkey,
+    solana_sdk::{message::VersionedMessage, signature::Signature},
+    solana_transaction_status::{UiLoadedAddresses, UiTransactionEncoding},
+    std::str::FromStr,
+};
+
+/// Most signatures `getSignaturesForAddress` returns per request
+const MAX_PAGE_SIZE: usize = 1000;
+
+pub(crate) fn process_history(config: &Config, matches: &ArgMatches) -> Result<CliHistory, Error> {
+    let greeting_account_id = greeting_account_of(config, matches, &config.fee_payer.pubkey())?;
+    let limit: Option<usize> = value_of(matches, "limit");
+    let until = value_of::<Signature>(matches, "until");
+
+    // Newest first, one page at a time
+    let mut statuses: Vec<RpcConfirmedTransactionStatusWithSignature> = vec![];
+    let mut before = value_of::<Signature>(matches, "before");
+    loop {
+        let page_size = match limit {
+            Some(limit) => (limit - statuses.len()).min(MAX_PAGE_SIZE),
+            None => MAX_PAGE_SIZE,
+        };
+        if page_size == 0 {
+            break;
+        }
+        let page = config.rpc_client.get_signatures_for_address_with_config(
+            &greeting_account_id,
+            GetConfirmedSignaturesForAddress2Config {
+                before,
+                until,
+                limit: Some(page_size),
+                commitment: Some(config.rpc_client.commitment()),
+            },
+        )?;
+        let last_page = page.len() < page_size;
+        statuses.extend(page);
+        match statuses.last() {
+            Some(status) if !last_page => before = Some(Signature::from_str(&status.signature)?),
+            _ => break,
+        }
+    }
+
+    // Only worth paging on if the limit cut the history short
+    let before = match limit {
+        Some(limit) if statuses.len() == limit => statuses.last().map(|s| s.signature.clone()),
+        _ => None,
+    };
+    let entries = statuses
+        .iter()
+        .rev()
+        .map(|status| history_entry(config, &greeting_account_id, status))
+        .collect::<Result<_, _>>()?;
+    Ok(CliHistory {
+        greeting_account: greeting_account_id.to_string(),
+        entries,
+        before,
+    })
+}
+
+fn history_entry(
+    config: &Config,
+    greeting_account_id: &Pubkey,
+    status: &RpcConfirmedTransactionStatusWithSignature,
+) -> Result<CliHistoryEntry, Error> {
+    let confirmed_transaction = config.rpc_client.get_transaction_with_config(
+        &Signature::from_str(&status.signature)?,
+        RpcTransactionConfig {
+            encoding: Some(UiTransactionEncoding::Base64),
+            commitment: Some(config.rpc_client.commitment()),
+            max_supported_transaction_version: Some(0),
+        },
+    )?;
+    let transaction = confirmed_transaction
+        .transaction
+        .transaction
+        .decode()
+        .ok_or_else(|| format!("Unable to decode transaction {}", status.signature))?;
+    let (logs, loaded_addresses): (Option<Vec<String>>, _) =
+        match confirmed_transaction.transaction.meta {
+            Some(meta) => (meta.log_messages.into(), meta.loaded_addresses.into()),
+            None => (None, None),
+        };
+    let mut invocation_logs = top_level_logs(&logs.unwrap_or_default(), &config.program_id);
+
+    let message = &transaction.message;
+    let account_keys = account_keys(message, loaded_addresses)?;
+    let mut instructions = vec![];
+    let mut logs = vec![];
+    let mut counter = None;
+    for instruction in message.instructions() {
+        if account_keys.get(instruction.program_id_index as usize) != Some(&config.program_id) {
+            continue;
+        }
+        // Logs are missing for instructions after a failed one
+        let instruction_logs = if invocation_logs.is_empty() {
+            vec![]
+        } else {
+            invocation_logs.remove(0)
+        };
+        let greeting_account = instruction
+            .accounts
+            .first()
+            .and_then(|index| account_keys.get(*index as usize));
+        if greeting_account != Some(greeting_account_id) {
+            continue;
+        }
+        if let Some(greeted_counter) = instruction_logs.iter().rev().find_map(|log| greeted(log)) {
+            counter = Some(greeted_counter);
+        }
+        instructions.push(CliGreetingInstruction::new(
+            GreetingInstruction::try_from_slice(&instruction.data).ok(),
+        ));
+        logs.extend(instruction_logs);
+    }
+
+    // A failed transaction changed nothing, whatever its logs say
+    if status.err.is_some() {
+        counter = None;
+    }
+    Ok(CliHistoryEntry {
+        signature: status.signature.clone(),
+        slot: confirmed_transaction.slot,
+        block_time: confirmed_transaction.block_time,
+        fee_payer: account_keys
+            .first()
+            .map(|fee_payer| fee_payer.to_string())
+            .unwrap_or_default(),
+        instructions,
+        counter,
+        error: status.err.as_ref().map(|err| err.to_string()),
+        logs,
+    })
+}
+
+/// The account keys `message` indexes: its static keys, then for a v0
+/// message the writable and readonly addresses loaded from lookup tables
+fn account_keys(
+    message: &VersionedMessage,
+    loaded_addresses: Option<UiLoadedAddresses>,
+) -> Result<Vec<Pubkey>, Error> {
+    let mut account_keys = message.static_account_keys().to_vec();
+    if let Some(loaded_addresses) = loaded_addresses {
+        for address in loaded_addresses
+            .writable
+            .iter()
+            .chain(&loaded_addresses.readonly)
+        {
+            account_keys.push(Pubkey::from_str(address)?);
+        }
+    }
+    Ok(account_keys)
+}
+
+/// Splits the `Program log:` messages of a transaction by top-level
+/// invocation of `program_id`, in order
+fn top_level_logs(logs: &[String], program_id: &Pubkey) -> Vec<Vec<String>> {
+    let invoke = format!("Program {} invoke [1]", program_id);
+    let mut invocations: Vec<Vec<String>> = vec![];
+    let mut depth = 0;
+    let mut in_program = false;
+    for log in logs {
+        if let Some(message) = log.strip_prefix("Program log: ") {
+            if in_program && depth == 1 {
+                if let Some(invocation) = invocations.last_mut() {
+                    invocation.push(message.to_string());
+                }
+            }
+        } else if log.contains(" invoke [") {
+            depth += 1;
+            if *log == invoke {
+                invocations.push(vec![]);
+                in_program = true;
+            }
+        } else if log.ends_with(" success") || log.contains(" failed: ") {
+            depth -= 1;
+            if depth == 0 {
+                in_program = false;
+            }
+        }
+    }
+    invocations
+}
+
+/// The counter after a greeting, from the program's `Greeted N time(s)!` log
+fn greeted(log: &str) -> Option<u32> {
+    log.strip_prefix("Greeted ")?
+        .strip_suffix(" time(s)!")?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        solana_sdk::message::{
+            v0::{self, MessageAddressTableLookup},
+            Message,
+        },
+    };
+
+    #[test]
+    fn test_account_keys() {
+        let fee_payer = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+        let legacy = VersionedMessage::Legacy(Message {
+            account_keys: vec![fee_payer, program_id],
+            ..Message::default()
+        });
+        assert_eq!(
+            account_keys(&legacy, None).unwrap(),
+            [fee_payer, program_id]
+        );
+
+        // Greeting accounts may come from a lookup table in v0 messages
+        let writable = Pubkey::new_unique();
+        let readonly = Pubkey::new_unique();
+        let v0 = VersionedMessage::V0(v0::Message {
+            account_keys: vec![fee_payer, program_id],
+            address_table_lookups: vec![MessageAddressTableLookup {
+                account_key: Pubkey::new_unique(),
+                writable_indexes: vec![0],
+                readonly_indexes: vec![1],
+            }],
+            ..v0::Message::default()
+        });
+        let loaded_addresses = UiLoadedAddresses {
+            writable: vec![writable.to_string()],
+            readonly: vec![readonly.to_string()],
+        };
+        assert_eq!(
+            account_keys(&v0, Some(loaded_addresses)).unwrap(),
+            [fee_payer, program_id, writable, readonly]
+        );
+    }
+
+    #[test]
+    fn test_top_level_logs() {
+        let program_id = Pubkey::new_unique();
+        let other_program_id = Pubkey::new_unique();
+        let logs = [
+            format!("Program {} invoke [1]", program_id),
+            "Program log: Hello World Rust program entrypoint".to_string(),
+            "Program log: Greeted 2 time(s)!".to_string(),
+            format!("Program {} success", program_id),
+            format!("Program {} invoke [1]", other_program_id),
+            format!("Program {} invoke [2]", program_id),
+            "Program log: Greeted 3 time(s)!".to_string(),
+            format!("Program {} success", program_id),
+            format!("Program {} success", other_program_id),
+            format!("Program {} invoke [1]", program_id),
+            "Program log: Greeted 5 time(s)!".to_string(),
+            format!("Program {} failed: custom program error: 0x1", program_id),
+        ];
+        let invocations = top_level_logs(&logs, &program_id);
+        assert_eq!(
+            invocations,
+            [
+                vec![
+                    "Hello World Rust program entrypoint".to_string(),
+                    "Greeted 2 time(s)!".to_string()
+                ],
+                vec!["Greeted 5 time(s)!".to_string()],
+            ]
+        );
+        assert_eq!(greeted(&invocations[0][1]), Some(2));
+        assert_eq!(greeted(&invocations[0][0]), None);
+    }
+}
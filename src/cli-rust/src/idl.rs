@@ -0,0 +1,554 @@
+//! `idl`: a JSON IDL and the TypeScript Borsh schema of the program,
+//! generated from the `BorshSchema` of its instruction and account types.
+//! `npm run generate:idl` refreshes the checked-in copies, and a test fails
+//! when they drift from the Rust types.
+
+use {
+    crate::{output::CliIdl, Error},
+    borsh::{
+        schema::{Declaration, Definition, Fields},
+        BorshSchema,
+    },
+    clap::ArgMatches,
+    helloworld::{
+        error::GreetingError,
+        instruction::{GreetingInstruction, GreetingPayload},
+        processor::{GreeterNonce, GreetingAccount},
+    },
+    serde::Serialize,
+    solana_program::program_pack::Pack,
+    std::{
+        collections::{BTreeMap, HashMap},
+        fmt::Write,
+        fs,
+    },
+};
+
+#[derive(Serialize)]
+pub(crate) struct Idl {
+    name: String,
+    instructions: Vec<IdlInstruction>,
+    accounts: Vec<IdlAccount>,
+    types: Vec<IdlTypeDefinition>,
+    errors: Vec<IdlError>,
+}
+
+/// A variant of `GreetingInstruction`, `discriminant` being its first byte
+#[derive(Serialize)]
+struct IdlInstruction {
+    name: String,
+    discriminant: u8,
+    args: Vec<IdlType>,
+}
+
+#[derive(Serialize)]
+struct IdlAccount {
+    name: String,
+    /// In bytes
+    size: usize,
+}
+
+#[derive(Serialize)]
+struct IdlTypeDefinition {
+    name: String,
+    #[serde(flatten)]
+    kind: IdlTypeKind,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+enum IdlTypeKind {
+    Struct { fields: Vec<IdlField> },
+    Enum { variants: Vec<IdlVariant> },
+}
+
+#[derive(Serialize)]
+struct IdlField {
+    name: String,
+    #[serde(rename = "type")]
+    ty: IdlType,
+}
+
+#[derive(Serialize)]
+struct IdlVariant {
+    name: String,
+    fields: Vec<IdlType>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+enum IdlType {
+    Bool,
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+    String,
+    PublicKey,
+    Option(Box<IdlType>),
+    Vec(Box<IdlType>),
+    Array(Box<IdlType>, u32),
+    Defined(String),
+}
+
+#[derive(Serialize)]
+struct IdlError {
+    code: u32,
+    name: String,
+    msg: String,
+}
+
+/// Collects the structs and enums the Borsh schemas of the program's types
+/// refer to
+#[derive(Default)]
+struct IdlBuilder {
+    definitions: HashMap<Declaration, Definition>,
+    types: BTreeMap<String, IdlTypeKind>,
+}
+
+impl IdlBuilder {
+    fn add<T: BorshSchema>(&mut self) -> Result<IdlType, String> {
+        let container = T::schema_container();
+        self.definitions.extend(container.definitions);
+        resolve(&self.definitions, &mut self.types, &container.declaration)
+    }
+}
+
+/// The IDL type of `declaration`, adding the structs and enums it uses to
+/// `types`
+fn resolve(
+    definitions: &HashMap<Declaration, Definition>,
+    types: &mut BTreeMap<String, IdlTypeKind>,
+    declaration: &str,
+) -> Result<IdlType, String> {
+    let definition = match definitions.get(declaration) {
+        Some(definition) => definition,
+        None => return primitive(declaration),
+    };
+    let mut resolve = |declaration: &str| resolve(definitions, types, declaration);
+    let kind = match definition {
+        _ if declaration == "Pubkey" => return Ok(IdlType::PublicKey),
+        Definition::Array { length, elements } => {
+            return Ok(IdlType::Array(Box::new(resolve(elements)?), *length))
+        }
+        Definition::Sequence { elements } => return Ok(IdlType::Vec(Box::new(resolve(elements)?))),
+        Definition::Enum { variants } if declaration.starts_with("Option<") => {
+            return Ok(IdlType::Option(Box::new(resolve(&variants[1].1)?)))
+        }
+        Definition::Struct {
+            fields: Fields::NamedFields(named_fields),
+        } => {
+            let mut fields = vec![];
+            for (name, field_declaration) in named_fields {
+                let ty = resolve(field_declaration)?;
+                fields.push(IdlField {
+                    name: name.clone(),
+                    ty,
+                });
+            }
+            IdlTypeKind::Struct { fields }
+        }
+        Definition::Struct {
+            fields: Fields::Empty,
+        } => IdlTypeKind::Struct { fields: vec![] },
+        Definition::Enum {
+            variants: variant_declarations,
+        } => {
+            let mut variants = vec![];
+            for (name, variant_declaration) in variant_declarations {
+                let fields = match variant_declaration.as_str() {
+                    "nil" => vec![],
+                    _ => vec![resolve(variant_declaration)?],
+                };
+                variants.push(IdlVariant {
+                    name: name.clone(),
+                    fields,
+                });
+            }
+            IdlTypeKind::Enum { variants }
+        }
+        _ => return Err(format!("{} has no IDL type", declaration)),
+    };
+    types.insert(declaration.to_string(), kind);
+    Ok(IdlType::Defined(declaration.to_string()))
+}
+
+fn primitive(declaration: &str) -> Result<IdlType, String> {
+    Ok(match declaration {
+        "bool" => IdlType::Bool,
+        "u8" => IdlType::U8,
+        "u16" => IdlType::U16,
+        "u32" => IdlType::U32,
+        "u64" => IdlType::U64,
+        "u128" => IdlType::U128,
+        "i8" => IdlType::I8,
+        "i16" => IdlType::I16,
+        "i32" => IdlType::I32,
+        "i64" => IdlType::I64,
+        "i128" => IdlType::I128,
+        "string" => IdlType::String,
+        _ => return Err(format!("{} has no IDL type", declaration)),
+    })
+}
+
+/// The IDL of the program: its instructions, accounts, the types they use,
+/// the signed `GreetingPayload` and the `GreetingError` codes
+pub(crate) fn idl() -> Result<Idl, String> {
+    let mut builder = IdlBuilder::default();
+    builder.add::<GreetingInstruction>()?;
+    builder.add::<GreetingAccount>()?;
+    builder.add::<GreeterNonce>()?;
+    builder.add::<GreetingPayload>()?;
+
+    let instructions = match &builder.types["GreetingInstruction"] {
+        IdlTypeKind::Enum { variants } => variants
+            .iter()
+            .enumerate()
+            .map(|(discriminant, variant)| IdlInstruction {
+                name: variant.name.clone(),
+                discriminant: discriminant as u8,
+                args: variant.fields.clone(),
+            })
+            .collect(),
+        IdlTypeKind::Struct { .. } => return Err("GreetingInstruction is not an enum".into()),
+    };
+    let accounts = vec![
+        IdlAccount {
+            name: "GreetingAccount".to_string(),
+            size: GreetingAccount::LEN,
+        },
+        IdlAccount {
+            name: "GreeterNonce".to_string(),
+            size: GreeterNonce::LEN,
+        },
+    ];
+    let types = builder
+        .types
+        .into_iter()
+        .map(|(name, kind)| IdlTypeDefinition { name, kind })
+        .collect();
+    let errors = (0..)
+        .map_while(GreetingError::from_code)
+        .map(|error| IdlError {
+            code: error as u32,
+            name: format!("{:?}", error),
+            msg: error.to_string(),
+        })
+        .collect();
+    Ok(Idl {
+        name: "helloworld".to_string(),
+        instructions,
+        accounts,
+        types,
+        errors,
+    })
+}
+
+pub(crate) fn idl_json(idl: &Idl) -> String {
+    serde_json::to_string_pretty(idl).unwrap() + "\n"
+}
+
+/// The TypeScript type and `borsh` 0.6 schema field type of `ty`
+fn typescript_field(ty: &IdlType) -> Result<(String, String), String> {
+    Ok(match ty {
+        IdlType::U8 => ("number".to_string(), "'u8'".to_string()),
+        IdlType::U16 => ("number".to_string(), "'u16'".to_string()),
+        IdlType::U32 => ("number".to_string(), "'u32'".to_string()),
+        IdlType::U64 => ("BN".to_string(), "'u64'".to_string()),
+        IdlType::U128 => ("BN".to_string(), "'u128'".to_string()),
+        IdlType::String => ("string".to_string(), "'string'".to_string()),
+        IdlType::PublicKey => ("Uint8Array".to_string(), "[32]".to_string()),
+        IdlType::Array(elements, length) if **elements == IdlType::U8 => {
+            ("Uint8Array".to_string(), format!("[{}]", length))
+        }
+        IdlType::Option(ty) => {
+            let (ty, schema) = typescript_field(ty)?;
+            (
+                format!("{} | undefined", ty),
+                format!("{{kind: 'option', type: {}}}", schema),
+            )
+        }
+        IdlType::Vec(elements) => {
+            let (ty, schema) = typescript_field(elements)?;
+            (format!("{}[]", ty), format!("[{}]", schema))
+        }
+        IdlType::Defined(name) => (name.clone(), name.clone()),
+        _ => return Err(format!("borsh 0.6 has no schema for {:?}", ty)),
+    })
+}
+
+/// `GreetingAccount` as `GREETING_ACCOUNT`
+fn screaming_snake_case(name: &str) -> String {
+    let mut screaming = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            screaming.push('_');
+        }
+        screaming.push(c.to_ascii_uppercase());
+    }
+    screaming
+}
+
+/// The classes and `borsh` schema entries of the TypeScript schema, written
+/// the way Prettier formats them
+#[derive(Default)]
+struct TypescriptSchema {
+    classes: String,
+    schema: String,
+    uses_bn: bool,
+}
+
+impl TypescriptSchema {
+    fn field(&mut self, ty: &IdlType) -> Result<(String, String), String> {
+        let (ty, schema) = typescript_field(ty)?;
+        self.uses_bn |= ty.contains("BN");
+        Ok((ty, schema))
+    }
+
+    fn add_struct(&mut self, name: &str, fields: &[IdlField]) -> Result<(), String> {
+        let fields = fields
+            .iter()
+            .map(|field| {
+                let (ty, schema) = self.field(&field.ty)?;
+                Ok((field.name.as_str(), ty, schema))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        if fields.is_empty() {
+            writeln!(self.classes, "\nexport class {} {{}}", name).unwrap();
+        } else {
+            writeln!(self.classes, "\nexport class {} {{", name).unwrap();
+            for (field, ty, _) in &fields {
+                writeln!(self.classes, "  {}: {};", field, ty).unwrap();
+            }
+            writeln!(self.classes, "  constructor(fields: {{").unwrap();
+            for (field, ty, _) in &fields {
+                writeln!(self.classes, "    {}: {};", field, ty).unwrap();
+            }
+            writeln!(self.classes, "  }}) {{").unwrap();
+            for (field, _, _) in &fields {
+                writeln!(self.classes, "    this.{0} = fields.{0};", field).unwrap();
+            }
+            writeln!(self.classes, "  }}\n}}").unwrap();
+        }
+
+        // Prettier puts an array of a single field on one line
+        let field_schemas = fields
+            .iter()
+            .map(|(field, _, schema)| format!("['{}', {}]", field, schema))
+            .collect::<Vec<_>>();
+        let field_schemas = match field_schemas.as_slice() {
+            [] => "[]".to_string(),
+            [field_schema] => format!("[{}]", field_schema),
+            _ => format!("[\n        {},\n      ]", field_schemas.join(",\n        ")),
+        };
+        writeln!(
+            self.schema,
+            "  [\n    {},\n    {{\n      kind: 'struct',\n      fields: {},\n    }},\n  ],",
+            name, field_schemas
+        )
+        .unwrap();
+        Ok(())
+    }
+
+    /// A class with the variant's name in `enum` and its value in the property
+    /// of that name. Unit variants take an empty class.
+    fn add_enum(&mut self, name: &str, variants: &[IdlVariant]) -> Result<(), String> {
+        let mut values = vec![];
+        for variant in variants {
+            let (ty, schema) = match variant.fields.as_slice() {
+                [] => {
+                    let unit_name = format!("{}{}", name, variant.name);
+                    self.add_struct(&unit_name, &[])?;
+                    (unit_name.clone(), unit_name)
+                }
+                [ty] => self.field(ty)?,
+                _ => {
+                    return Err(format!(
+                        "{}::{} has more than one field",
+                        name, variant.name
+                    ))
+                }
+            };
+            values.push((variant.name.as_str(), ty, schema));
+        }
+
+        writeln!(self.classes, "\nexport class {} {{", name).unwrap();
+        writeln!(self.classes, "  enum: string;").unwrap();
+        for (variant, ty, _) in &values {
+            writeln!(self.classes, "  {}?: {};", variant, ty).unwrap();
+        }
+        let parameter = values
+            .iter()
+            .map(|(variant, ty, _)| format!("      | {{{}: {}}}", variant, ty))
+            .collect::<Vec<_>>()
+            .join("\n");
+        writeln!(
+            self.classes,
+            "  constructor(\n    fields:\n{},\n  ) {{\n    \
+            this.enum = Object.keys(fields)[0];\n    Object.assign(this, fields);\n  }}\n}}",
+            parameter
+        )
+        .unwrap();
+
+        let values = values
+            .iter()
+            .map(|(variant, _, schema)| format!("        ['{}', {}],\n", variant, schema))
+            .collect::<String>();
+        writeln!(
+            self.schema,
+            "  [\n    {},\n    {{\n      kind: 'enum',\n      field: 'enum',\n      \
+            values: [\n{}      ],\n    }},\n  ],",
+            name, values
+        )
+        .unwrap();
+        Ok(())
+    }
+}
+
+/// The size of each account, a class per struct and enum of `idl`, and the
+/// `borsh` schema of them all
+pub(crate) fn typescript_schema(idl: &Idl) -> Result<String, String> {
+    let mut typescript_schema = TypescriptSchema::default();
+    for definition in &idl.types {
+        match &definition.kind {
+            IdlTypeKind::Struct { fields } => {
+                typescript_schema.add_struct(&definition.name, fields)?
+            }
+            IdlTypeKind::Enum { variants } => {
+                typescript_schema.add_enum(&definition.name, variants)?
+            }
+        }
+    }
+
+    let mut typescript = String::from(
+        "/**\n * Borsh schema of the hello world program's instructions and accounts.\n *\n \
+        * Generated from the Rust types by `npm run generate:idl`, don't edit.\n */\n",
+    );
+    if typescript_schema.uses_bn {
+        typescript.push_str("\nimport BN from 'bn.js';\n");
+    }
+    for account in &idl.accounts {
+        write!(
+            typescript,
+            "\n/**\n * Size of a `{}`, in bytes\n */\nexport const {}_SIZE = {};\n",
+            account.name,
+            screaming_snake_case(&account.name),
+            account.size
+        )
+        .unwrap();
+    }
+    typescript.push_str(&typescript_schema.classes);
+    write!(
+        typescript,
+        "\nexport const SCHEMA = new Map<any, any>([\n{}]);\n",
+        typescript_schema.schema
+    )
+    .unwrap();
+    Ok(typescript)
+}
+
+/// Writes the IDL to `--json` and the TypeScript schema to `--ts`, or prints
+/// the IDL given neither. Needs no config, so `main` runs it before loading one.
+pub fn process_idl(matches: &ArgMatches) -> Result<CliIdl, Error> {
+    let idl = idl()?;
+    let json_path = matches.value_of("json");
+    let ts_path = matches.value_of("ts");
+    if let Some(json_path) = json_path {
+        fs::write(json_path, idl_json(&idl))
+            .map_err(|err| format!("can't write {}: {}", json_path, err))?;
+    }
+    if let Some(ts_path) = ts_path {
+        fs::write(ts_path, typescript_schema(&idl)?)
+            .map_err(|err| format!("can't write {}: {}", ts_path, err))?;
+    }
+    Ok(CliIdl {
+        idl: idl_json(&idl),
+        json_path: json_path.map(ToString::to_string),
+        ts_path: ts_path.map(ToString::to_string),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_idl() {
+        let idl = idl().unwrap();
+        let close = &idl.instructions[5];
+        assert_eq!((close.name.as_str(), close.discriminant), ("Close", 5));
+        assert!(close.args.is_empty());
+        let migrate = idl.instructions.last().unwrap();
+        assert_eq!(
+            (migrate.name.as_str(), migrate.discriminant),
+            ("Migrate", 6)
+        );
+        assert_eq!(idl.accounts[0].size, 41);
+
+        let init_greeting_args = idl
+            .types
+            .iter()
+            .find(|definition| definition.name == "InitGreetingArgs")
+            .unwrap();
+        match &init_greeting_args.kind {
+            IdlTypeKind::Struct { fields } => {
+                assert_eq!(fields[2].ty, IdlType::Option(Box::new(IdlType::U64)))
+            }
+            IdlTypeKind::Enum { .. } => panic!("InitGreetingArgs is a struct"),
+        }
+        assert_eq!(idl.errors[8].name, "NonceReused");
+        assert_eq!(screaming_snake_case("GreeterNonce"), "GREETER_NONCE");
+    }
+
+    #[test]
+    fn test_generated_files_are_current() {
+        let idl = idl().unwrap();
+        assert!(
+            idl_json(&idl) == include_str!("../../../idl/helloworld.json"),
+            "idl/helloworld.json is out of date, run `npm run generate:idl`"
+        );
+        assert!(
+            typescript_schema(&idl).unwrap() == include_str!("../../client/schema.ts"),
+            "src/client/schema.ts is out of date, run `npm run generate:idl`"
+        );
+    }
+
+    #[test]
+    fn test_generated_imports_are_dependencies() {
+        let package: serde_json::Value =
+            serde_json::from_str(include_str!("../../../package.json")).unwrap();
+        let schema = typescript_schema(&idl().unwrap()).unwrap();
+        let modules: Vec<&str> = schema
+            .lines()
+            .filter_map(|line| {
+                line.strip_prefix("import ")?
+                    .split(" from '")
+                    .nth(1)?
+                    .strip_suffix("';")
+            })
+            .collect();
+        assert!(modules.contains(&"bn.js"));
+        for module in modules {
+            assert!(
+                package["dependencies"].get(module).is_some(),
+                "src/client/schema.ts imports {}, add it to the dependencies in package.json",
+                module
+            );
+            // None of them ship their own typings
+            let types = format!("@types/{}", module);
+            assert!(
+                package["devDependencies"].get(&types).is_some(),
+                "src/client/schema.ts imports {}, add {} to the devDependencies in package.json",
+                module,
+                types
+            );
+        }
+    }
+}
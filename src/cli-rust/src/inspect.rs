@@ -0,0 +1,436 @@
+//! `inspect`: decodes raw instruction data and account dumps field by field,
+//! pointing at the exact byte where decoding stops making sense.
+
+use {
+    crate::{
+        output::{CliDecodedField, CliInspection},
+        CliError, Config, Error,
+    },
+    borsh::{
+        schema::{Declaration, Definition, Fields, VariantName},
+        BorshDeserialize, BorshSchema,
+    },
+    clap::ArgMatches,
+    helloworld::{instruction::GreetingInstruction, processor::GreetingAccount},
+    solana_client::rpc_response::RpcKeyedAccount,
+    solana_program::pubkey::Pubkey,
+    solana_sdk::account::Account,
+    std::{collections::HashMap, fmt, path::Path, str::FromStr},
+};
+
+/// Where and why decoding failed
+#[derive(Debug, PartialEq)]
+struct DecodeError {
+    offset: usize,
+    message: String,
+}
+
+/// Walks `data` one Borsh field at a time, as laid out by the `BorshSchema`
+/// of the type it holds, recording each field's position
+struct Decoder<'a> {
+    data: &'a [u8],
+    offset: usize,
+    fields: Vec<CliDecodedField>,
+    definitions: HashMap<Declaration, Definition>,
+}
+
+impl<'a> Decoder<'a> {
+    fn new<T: BorshSchema>(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            offset: 0,
+            fields: vec![],
+            definitions: T::schema_container().definitions,
+        }
+    }
+
+    fn field<T: BorshDeserialize + fmt::Debug>(&mut self, name: &str) -> Result<T, DecodeError> {
+        let mut rest = &self.data[self.offset..];
+        let value = T::deserialize(&mut rest).map_err(|err| DecodeError {
+            offset: self.offset,
+            message: format!("invalid {}: {}", name, err),
+        })?;
+        let length = self.data.len() - self.offset - rest.len();
+        self.fields.push(CliDecodedField {
+            offset: self.offset,
+            length,
+            name: name.to_string(),
+            value: format!("{:?}", value),
+        });
+        self.offset += length;
+        Ok(value)
+    }
+
+    /// Reads the variant tag of the enum `declaration`, returning the name
+    /// and declaration of the variant
+    fn variant(
+        &mut self,
+        declaration: &str,
+        name: &str,
+    ) -> Result<(VariantName, Declaration), DecodeError> {
+        let offset = self.offset;
+        let tag: u8 = self.field(name)?;
+        match self.definitions.get(declaration) {
+            Some(Definition::Enum { variants }) => variants.get(tag as usize).cloned(),
+            _ => None,
+        }
+        .ok_or_else(|| DecodeError {
+            offset,
+            message: format!("unknown variant tag {}", tag),
+        })
+    }
+
+    /// Reads a value of type `declaration`, one field per primitive
+    fn value(&mut self, declaration: &str, name: &str) -> Result<(), DecodeError> {
+        let definition = match self.definitions.get(declaration) {
+            _ if declaration == "Pubkey" => return self.field::<Pubkey>(name).map(drop),
+            Some(definition) => definition,
+            None => return self.primitive(declaration, name),
+        };
+        let elements = match definition {
+            Definition::Enum { .. } => {
+                let (_, variant) = self.variant(declaration, &format!("{} variant", name))?;
+                return match variant.as_str() {
+                    "nil" => Ok(()),
+                    _ => self.value(&variant, name),
+                };
+            }
+            Definition::Struct {
+                fields: Fields::NamedFields(fields),
+            } => fields.clone(),
+            Definition::Struct {
+                fields: Fields::UnnamedFields(elements),
+            }
+            | Definition::Tuple { elements } => elements
+                .iter()
+                .enumerate()
+                .map(|(index, element)| (format!("{}.{}", name, index), element.clone()))
+                .collect(),
+            Definition::Struct {
+                fields: Fields::Empty,
+            } => vec![],
+            Definition::Array { length, elements } => (0..*length)
+                .map(|index| (format!("{}[{}]", name, index), elements.clone()))
+                .collect(),
+            Definition::Sequence { elements } => {
+                let elements = elements.clone();
+                let length: u32 = self.field(&format!("{} length", name))?;
+                (0..length)
+                    .map(|index| (format!("{}[{}]", name, index), elements.clone()))
+                    .collect()
+            }
+        };
+        for (name, declaration) in elements {
+            self.value(&declaration, &name)?;
+        }
+        Ok(())
+    }
+
+    fn primitive(&mut self, declaration: &str, name: &str) -> Result<(), DecodeError> {
+        match declaration {
+            "bool" => self.field::<bool>(name).map(drop),
+            "u8" => self.field::<u8>(name).map(drop),
+            "u16" => self.field::<u16>(name).map(drop),
+            "u32" => self.field::<u32>(name).map(drop),
+            "u64" => self.field::<u64>(name).map(drop),
+            "u128" => self.field::<u128>(name).map(drop),
+            "i8" => self.field::<i8>(name).map(drop),
+            "i16" => self.field::<i16>(name).map(drop),
+            "i32" => self.field::<i32>(name).map(drop),
+            "i64" => self.field::<i64>(name).map(drop),
+            "i128" => self.field::<i128>(name).map(drop),
+            "string" => self.field::<String>(name).map(drop),
+            _ => Err(DecodeError {
+                offset: self.offset,
+                message: format!("{} has no known layout", declaration),
+            }),
+        }
+    }
+
+    fn finish(&self) -> Result<(), DecodeError> {
+        match self.data.len() - self.offset {
+            0 => Ok(()),
+            trailing => Err(DecodeError {
+                offset: self.offset,
+                message: format!("{} trailing byte(s)", trailing),
+            }),
+        }
+    }
+}
+
+/// Decodes a `GreetingInstruction`, returning its variant name
+fn decode_instruction(decoder: &mut Decoder) -> Result<VariantName, DecodeError> {
+    let (variant, declaration) =
+        decoder.variant(&GreetingInstruction::declaration(), "variant tag")?;
+    if declaration != "nil" {
+        decoder.value(&declaration, &variant)?;
+    }
+    decoder.finish()?;
+    Ok(variant)
+}
+
+/// Decodes a `GreetingAccount`, or the `(counter, counter_times_2)` layout
+/// of accounts created before `authority` and `flags` existed
+fn decode_account(decoder: &mut Decoder) -> Result<(), DecodeError> {
+    if decoder.data.len() == GreetingAccount::LEGACY_LEN {
+        decoder.field::<u32>("counter")?;
+        decoder.field::<u32>("counter_times_2")?;
+    } else {
+        decoder.value(&GreetingAccount::declaration(), "account")?;
+    }
+    decoder.finish()
+}
+
+pub(crate) fn process_inspect(
+    config: &Config,
+    matches: &ArgMatches,
+) -> Result<CliInspection, Error> {
+    let value = matches.value_of("value").unwrap();
+    match matches.value_of("kind").unwrap() {
+        "instruction" => inspect_instruction(config, value, matches.value_of("encoding")),
+        _ => inspect_account(config, value),
+    }
+}
+
+fn inspect_instruction(
+    config: &Config,
+    data: &str,
+    encoding: Option<&str>,
+) -> Result<CliInspection, Error> {
+    let (data, encoding) = match encoding {
+        Some("base64") => (base64::decode(data)?, "base64"),
+        Some(_) => (bs58::decode(data).into_vec()?, "base58"),
+        // Explorers show instruction data in base58, so try that first
+        None => match bs58::decode(data).into_vec() {
+            Ok(data) => (data, "base58"),
+            Err(_) => (
+                base64::decode(data).map_err(|_| "data is neither base58 nor base64")?,
+                "base64",
+            ),
+        },
+    };
+
+    let mut decoder = Decoder::new::<GreetingInstruction>(&data);
+    let result = decode_instruction(&mut decoder);
+    if result.is_ok() {
+        // The walk above follows the schema, so this can only fail if the
+        // schema and the Borsh layout drifted apart
+        GreetingInstruction::try_from_slice(&data)?;
+    }
+    report(
+        config,
+        CliInspection {
+            kind: "instruction".to_string(),
+            source: encoding.to_string(),
+            length: data.len(),
+            variant: result.as_ref().ok().cloned(),
+            owner: None,
+            fields: decoder.fields,
+            error: None,
+            error_offset: None,
+        },
+        result.err(),
+    )
+}
+
+fn inspect_account(config: &Config, address_or_file: &str) -> Result<CliInspection, Error> {
+    let (account, source) = if Path::new(address_or_file).is_file() {
+        (read_account_dump(address_or_file)?, "file")
+    } else {
+        let address = Pubkey::from_str(address_or_file)
+            .map_err(|_| format!("{} is neither a file nor an address", address_or_file))?;
+        (config.rpc_client.get_account(&address)?, "rpc")
+    };
+
+    let mut decoder = Decoder::new::<GreetingAccount>(&account.data);
+    let result = decode_account(&mut decoder);
+    if result.is_ok() {
+        GreetingAccount::unpack_versioned(&account.data)?;
+    }
+    report(
+        config,
+        CliInspection {
+            kind: "account".to_string(),
+            source: source.to_string(),
+            length: account.data.len(),
+            variant: (account.data.len() == GreetingAccount::LEGACY_LEN)
+                .then(|| "legacy".to_string()),
+            owner: (account.owner != Pubkey::default()).then(|| account.owner.to_string()),
+            fields: decoder.fields,
+            error: None,
+            error_offset: None,
+        },
+        result.err(),
+    )
+}
+
+/// Reads a `solana account --output json` dump, or the raw data written by
+/// `solana account --output-file`
+fn read_account_dump(path: &str) -> Result<Account, Error> {
+    let contents = std::fs::read(path)?;
+    match serde_json::from_slice::<RpcKeyedAccount>(&contents) {
+        Ok(keyed_account) => Ok(keyed_account
+            .account
+            .decode()
+            .ok_or_else(|| format!("Unable to decode the account data in {}", path))?),
+        Err(_) => Ok(Account {
+            data: contents,
+            ..Account::default()
+        }),
+    }
+}
+
+/// The decoded fields, failing with `CliError::DecodeFailed` after printing
+/// them if decoding stopped early
+fn report(
+    config: &Config,
+    mut inspection: CliInspection,
+    error: Option<DecodeError>,
+) -> Result<CliInspection, Error> {
+    inspection.error = error.as_ref().map(|error| error.message.clone());
+    inspection.error_offset = error.as_ref().map(|error| error.offset);
+    match error {
+        Some(DecodeError { offset, message }) => {
+            println!("{}", config.output_format.formatted_string(&inspection));
+            Err(CliError::DecodeFailed(offset, message).into())
+        }
+        None => Ok(inspection),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        borsh::BorshSerialize,
+        helloworld::instruction::{InitGreetingArgs, InitializeArgs, SignedGreetingArgs},
+    };
+
+    #[test]
+    fn test_decode_instruction() {
+        let init_greeting_args = InitGreetingArgs {
+            num_greetings: 2,
+            greeting_string: "hello".to_string(),
+            nonce: Some(7),
+        };
+        let instructions = [
+            (
+                GreetingInstruction::InitGreeting(init_greeting_args.clone()),
+                "InitGreeting",
+            ),
+            (
+                GreetingInstruction::InitGreeting2(init_greeting_args),
+                "InitGreeting2",
+            ),
+            (
+                GreetingInstruction::SignedGreeting(SignedGreetingArgs {
+                    num_greetings: 3,
+                    nonce: 1,
+                }),
+                "SignedGreeting",
+            ),
+            (
+                GreetingInstruction::Initialize(InitializeArgs {
+                    authority: Pubkey::new_unique(),
+                    flags: 1,
+                    seed: "hello".to_string(),
+                }),
+                "Initialize",
+            ),
+            (GreetingInstruction::SetFlags(1), "SetFlags"),
+            (GreetingInstruction::Close, "Close"),
+            (
+                GreetingInstruction::Migrate(InitializeArgs {
+                    authority: Pubkey::new_unique(),
+                    flags: 0,
+                    seed: String::new(),
+                }),
+                "Migrate",
+            ),
+        ];
+        for (instruction, variant) in &instructions {
+            let data = instruction.try_to_vec().unwrap();
+            let mut decoder = Decoder::new::<GreetingInstruction>(&data);
+            assert_eq!(decode_instruction(&mut decoder), Ok(variant.to_string()));
+            assert_eq!(decoder.offset, data.len());
+        }
+
+        // The fields come from the schema, `Option`s reading their tag first
+        let data = instructions[0].0.try_to_vec().unwrap();
+        let mut decoder = Decoder::new::<GreetingInstruction>(&data);
+        decode_instruction(&mut decoder).unwrap();
+        let fields = decoder
+            .fields
+            .iter()
+            .map(|field| (field.name.as_str(), field.value.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            [
+                ("variant tag", "0"),
+                ("num_greetings", "2"),
+                ("greeting_string", "\"hello\""),
+                ("nonce variant", "1"),
+                ("nonce", "7"),
+            ]
+        );
+
+        let data = GreetingInstruction::SetFlags(1).try_to_vec().unwrap();
+        let data = [&data[..], &[0, 0]].concat();
+        let mut decoder = Decoder::new::<GreetingInstruction>(&data);
+        assert_eq!(
+            decode_instruction(&mut decoder),
+            Err(DecodeError {
+                offset: 2,
+                message: "2 trailing byte(s)".to_string()
+            })
+        );
+
+        let mut decoder = Decoder::new::<GreetingInstruction>(&[9, 0]);
+        assert_eq!(
+            decode_instruction(&mut decoder),
+            Err(DecodeError {
+                offset: 0,
+                message: "unknown variant tag 9".to_string()
+            })
+        );
+
+        // `num_greetings` decodes, the string length prefix is cut short
+        let mut decoder = Decoder::new::<GreetingInstruction>(&[0, 2, 0, 0, 0, 5, 0]);
+        let error = decode_instruction(&mut decoder).unwrap_err();
+        assert_eq!(error.offset, 5);
+        assert!(error.message.starts_with("invalid greeting_string"));
+        assert_eq!(decoder.fields.len(), 2);
+    }
+
+    #[test]
+    fn test_decode_account() {
+        let account = GreetingAccount {
+            counter: 3,
+            counter_times_2: 6,
+            authority: Pubkey::new_unique(),
+            flags: 0,
+        };
+        let data = account.try_to_vec().unwrap();
+        let mut decoder = Decoder::new::<GreetingAccount>(&data);
+        assert_eq!(decode_account(&mut decoder), Ok(()));
+        assert_eq!(decoder.fields[2].offset, GreetingAccount::AUTHORITY_OFFSET);
+        assert_eq!(decoder.fields[2].value, account.authority.to_string());
+
+        let mut decoder = Decoder::new::<GreetingAccount>(&data[..20]);
+        let error = decode_account(&mut decoder).unwrap_err();
+        assert_eq!(error.offset, GreetingAccount::AUTHORITY_OFFSET);
+    }
+
+    #[test]
+    fn test_decode_legacy_account() {
+        let data = (3u32, 6u32).try_to_vec().unwrap();
+        let mut decoder = Decoder::new::<GreetingAccount>(&data);
+        assert_eq!(decode_account(&mut decoder), Ok(()));
+        assert_eq!(decoder.fields.len(), 2);
+        assert_eq!(decoder.fields[0].name, "counter");
+        assert_eq!(decoder.fields[1].name, "counter_times_2");
+        assert_eq!(decoder.fields[1].offset, 4);
+        assert_eq!(decoder.fields[1].value, "6");
+    }
+}
@@ -0,0 +1,2078 @@
+mod bench;
+mod deploy;
+mod estimate;
+mod history;
+mod idl;
+mod inspect;
+mod list;
+mod localnet;
+pub mod output;
+mod project_config;
+pub mod rpc;
+mod simulation;
+mod watch;
+
+use {
+    crate::output::{
+        CliCommandError, CliConfig, CliGreetingAccount, CliSignedGreeting, CliTransaction,
+        CommandOutput,
+    },
+    crate::{
+        bench::process_bench,
+        deploy::{process_deploy, process_set_upgrade_authority, process_upgrade, process_verify},
+        estimate::process_estimate,
+        history::process_history,
+        inspect::process_inspect,
+        list::{is_export_path, is_memcmp, process_list},
+        localnet::{process_localnet, LOCALNET_URL},
+        project_config::{Profile, ProjectConfig, PROJECT_CONFIG},
+        rpc::{get_blockhash, Pubsub, Rpc, WebsocketPubsub},
+        simulation::simulate,
+        watch::process_watch,
+    },
+    borsh::BorshSerialize,
+    clap::{
+        crate_description, crate_name, crate_version, App, AppSettings, Arg, ArgMatches, SubCommand,
+    },
+    helloworld::{
+        error::GreetingError,
+        instruction::{
+            close, init_greeting, init_greeting_with_nonce, initialize, migrate, signed_greeting,
+            GreetingPayload,
+        },
+        processor::GreetingAccount,
+        validation::GreetingPolicy,
+    },
+    helloworld_client::address::{greeting_account_address, DEFAULT_SEED},
+    solana_clap_utils::{
+        fee_payer::fee_payer_arg,
+        input_parsers::{pubkey_of, value_of},
+        input_validators::{
+            is_parsable, is_url, is_url_or_moniker, is_valid_pubkey, is_valid_signer,
+        },
+        keypair::signer_from_path,
+        nonce::{NonceArgs, NONCE_ARG, NONCE_AUTHORITY_ARG},
+        offline::{OfflineArgs, DUMP_TRANSACTION_MESSAGE, SIGN_ONLY_ARG},
+    },
+    solana_cli_config::ConfigInput,
+    solana_cli_output::{return_signers_data, OutputFormat, ReturnSignersConfig},
+    solana_client::{
+        blockhash_query::BlockhashQuery,
+        client_error::{ClientError, ClientErrorKind},
+        rpc_client::RpcClient,
+        rpc_config::RpcSimulateTransactionConfig,
+        rpc_request::{RpcError, RpcResponseErrorData},
+    },
+    solana_program::{
+        instruction::{Instruction, InstructionError},
+        message::Message,
+        native_token::lamports_to_sol,
+        program_pack::Pack,
+        pubkey::Pubkey,
+        rent::Rent,
+        system_instruction::SystemError,
+        system_program,
+    },
+    solana_sdk::{
+        commitment_config::CommitmentConfig,
+        compute_budget::ComputeBudgetInstruction,
+        signature::{read_keypair_file, Signature, Signer},
+        signer::unique_signers,
+        system_instruction::create_account_with_seed,
+        transaction::{Transaction, TransactionError},
+    },
+    std::{thread::sleep, time::Duration},
+    thiserror::Error,
+};
+
+/// Settings shared by every command, from the command line and config files
+pub struct Config {
+    rpc_client: Box<dyn Rpc>,
+    websocket_url: String,
+    pubsub_client: Box<dyn Pubsub>,
+    fee_payer: Box<dyn Signer>,
+    /// Where `fee_payer` was loaded from, recorded by `localnet up`
+    fee_payer_path: String,
+    /// `--authority`, if it differs from the fee payer
+    authority: Option<Box<dyn Signer>>,
+    /// `--upgrade-authority`, if it differs from the fee payer
+    upgrade_authority: Option<Box<dyn Signer>>,
+    config_file: Option<String>,
+    /// `PROJECT_CONFIG`, if it exists
+    project_config: Option<String>,
+    /// Where `localnet up` records its setup, `PROJECT_CONFIG` unless a test
+    /// moves it
+    project_config_path: String,
+    /// `--profile`, or `--cluster` if the project config has a profile for it
+    profile: Option<String>,
+    program_id: Pubkey,
+    greeting_account_seed: String,
+    output_format: OutputFormat,
+    dry_run: bool,
+    /// `--retries`
+    retries: usize,
+    /// `--compute-unit-price`, in micro-lamports
+    compute_unit_price: Option<u64>,
+    compute_unit_limit: ComputeUnitLimit,
+    /// Only sign and print the signatures, for offline signing
+    sign_only: bool,
+    dump_transaction_message: bool,
+    blockhash_query: BlockhashQuery,
+    /// Durable nonce account to use instead of a recent blockhash
+    nonce_account: Option<Pubkey>,
+    /// `--nonce-authority`, if it differs from the fee payer
+    nonce_authority: Option<Box<dyn Signer>>,
+}
+
+/// How `send_instructions` limits the compute units of a transaction
+#[derive(Clone, Copy, PartialEq)]
+enum ComputeUnitLimit {
+    /// No `SetComputeUnitLimit` instruction, the cluster's default applies
+    Default,
+    /// `--compute-unit-limit`
+    Static(u32),
+    /// `--auto-compute-limit`: the units a simulation consumed, plus
+    /// `AUTO_COMPUTE_LIMIT_MARGIN` percent
+    Simulated,
+}
+
+impl Config {
+    /// Builds the config of `sub_command` from its `matches`, the project
+    /// config and the `solana` CLI config
+    pub fn from_matches(sub_command: &str, matches: &ArgMatches) -> Result<Config, Error> {
+        let mut wallet_manager = None;
+        let config_file = matches.value_of("config_file").map(String::from);
+        let cli_config = if let Some(config_file) = &config_file {
+            solana_cli_config::Config::load(config_file).unwrap_or_default()
+        } else {
+            solana_cli_config::Config::default()
+        };
+        // The project config takes precedence over the `solana` CLI config,
+        // flags over both
+        let loaded_project_config = ProjectConfig::load(PROJECT_CONFIG)?;
+        let project_config_file = loaded_project_config
+            .as_ref()
+            .map(|_| PROJECT_CONFIG.to_string());
+        let project_config = loaded_project_config.unwrap_or_default();
+        let cluster = matches.value_of("cluster");
+        let (profile_name, profile) = match (matches.value_of("profile"), cluster) {
+            (Some(name), _) => match project_config.profile(name) {
+                Some(profile) => (Some(name), profile),
+                None => {
+                    return Err(CliError::Usage(format!(
+                        "no profile {} in {}",
+                        name, PROJECT_CONFIG
+                    ))
+                    .into())
+                }
+            },
+            (None, Some(cluster)) => match project_config.profile(cluster) {
+                Some(profile) => (Some(cluster), profile),
+                None => {
+                    let json_rpc_url = match cluster {
+                        "localnet" => LOCALNET_URL,
+                        moniker => moniker,
+                    };
+                    let profile = Profile {
+                        json_rpc_url: Some(json_rpc_url.to_string()),
+                        ..Profile::default()
+                    };
+                    (None, profile.or(&project_config.defaults))
+                }
+            },
+            (None, None) => (None, project_config.defaults.clone()),
+        };
+        let default_json_rpc_url = match profile.json_rpc_url {
+            Some(json_rpc_url) => json_rpc_url,
+            None if sub_command == "localnet" => LOCALNET_URL.to_string(),
+            None => cli_config.json_rpc_url.clone(),
+        };
+        let (_, json_rpc_url) = ConfigInput::compute_json_rpc_url_setting(
+            matches.value_of("json_rpc_url").unwrap_or(""),
+            &default_json_rpc_url,
+        );
+        // Derived from the RPC URL unless only the `solana` CLI config set both
+        let default_websocket_url = if default_json_rpc_url == cli_config.json_rpc_url {
+            cli_config.websocket_url.clone()
+        } else {
+            String::new()
+        };
+        let (_, websocket_url) = ConfigInput::compute_websocket_url_setting(
+            matches.value_of("websocket_url").unwrap_or(""),
+            &default_websocket_url,
+            matches.value_of("json_rpc_url").unwrap_or(""),
+            &default_json_rpc_url,
+        );
+        let (_, commitment) = ConfigInput::compute_commitment_config(
+            matches.value_of("commitment").unwrap_or(""),
+            &profile.commitment.unwrap_or(cli_config.commitment),
+        );
+
+        let fee_payer_path = matches
+            .value_of("fee_payer")
+            .map(String::from)
+            .or(profile.fee_payer)
+            .unwrap_or(cli_config.keypair_path);
+        let fee_payer_path = fee_payer_path.as_str();
+        let fee_payer =
+            signer_from_path(matches, fee_payer_path, "fee_payer", &mut wallet_manager)?;
+        let mut optional_signer = |name| {
+            matches
+                .value_of(name)
+                .map(|path| signer_from_path(matches, path, name, &mut wallet_manager))
+                .transpose()
+        };
+        let authority = optional_signer("authority")?;
+        let upgrade_authority = optional_signer("upgrade_authority")?;
+        let nonce_authority = optional_signer(NONCE_AUTHORITY_ARG.name)?;
+
+        let program_id = match pubkey_of(matches, "program_id") {
+            Some(program_id) => program_id,
+            None => match profile.program_id {
+                Some(program_id) => program_id
+                    .parse()
+                    .map_err(|e| format!("invalid program_id in {}: {}", PROJECT_CONFIG, e))?,
+                None => read_keypair_file(PROGRAM_ID)
+                    .map(|keypair| keypair.pubkey())
+                    .map_err(|e| format!("can't read {}, pass --program: {}", PROGRAM_ID, e))?,
+            },
+        };
+        let greeting_account_seed = if matches.occurrences_of("greeting_account_seed") > 0 {
+            None
+        } else {
+            profile.greeting_account_seed
+        }
+        .unwrap_or_else(|| {
+            matches
+                .value_of("greeting_account_seed")
+                .unwrap()
+                .to_string()
+        });
+        let output_format = match matches.value_of("output_format") {
+            Some("json") => OutputFormat::Json,
+            Some("json-compact") => OutputFormat::JsonCompact,
+            _ => OutputFormat::Display,
+        };
+        let dry_run = matches.is_present("dry_run");
+        let retries = value_of(matches, "retries").unwrap();
+        let compute_unit_price = value_of(matches, "compute_unit_price");
+        let compute_unit_limit = match value_of(matches, "compute_unit_limit") {
+            Some(units) => ComputeUnitLimit::Static(units),
+            None if matches.is_present("auto_compute_limit") => ComputeUnitLimit::Simulated,
+            None => ComputeUnitLimit::Default,
+        };
+        let sign_only = matches.is_present(SIGN_ONLY_ARG.name);
+        let dump_transaction_message = matches.is_present(DUMP_TRANSACTION_MESSAGE.name);
+        let blockhash_query = BlockhashQuery::new_from_matches(matches);
+        let nonce_account = pubkey_of(matches, NONCE_ARG.name);
+
+        Ok(Config {
+            rpc_client: Box::new(RpcClient::new_with_commitment(json_rpc_url, commitment)),
+            pubsub_client: Box::new(WebsocketPubsub {
+                url: websocket_url.clone(),
+            }),
+            websocket_url,
+            fee_payer,
+            fee_payer_path: fee_payer_path.to_string(),
+            authority,
+            upgrade_authority,
+            config_file,
+            project_config: project_config_file,
+            project_config_path: PROJECT_CONFIG.to_string(),
+            profile: profile_name.map(String::from),
+            program_id,
+            greeting_account_seed,
+            output_format,
+            dry_run,
+            retries,
+            compute_unit_price,
+            compute_unit_limit,
+            sign_only,
+            dump_transaction_message,
+            blockhash_query,
+            nonce_account,
+            nonce_authority,
+        })
+    }
+
+    /// The config, talking to the cluster through `rpc_client` instead of the
+    /// configured RPC URL
+    pub fn with_rpc_client(self, rpc_client: Box<dyn Rpc>) -> Config {
+        Config { rpc_client, ..self }
+    }
+
+    /// The config, subscribing through `pubsub_client` instead of the
+    /// configured websocket URL
+    pub fn with_pubsub_client(self, pubsub_client: Box<dyn Pubsub>) -> Config {
+        Config {
+            pubsub_client,
+            ..self
+        }
+    }
+
+    /// The config, with `localnet up` recording its setup at
+    /// `project_config_path` instead of `PROJECT_CONFIG`
+    pub fn with_project_config_path(self, project_config_path: String) -> Config {
+        Config {
+            project_config_path,
+            ..self
+        }
+    }
+
+    /// Prints `err` to stderr in the output format, returning the exit code
+    /// it maps to
+    pub fn report_error(&self, err: &Error) -> i32 {
+        let exit_code = exit_code_of(err);
+        let error = CliCommandError {
+            error: err.to_string(),
+            exit_code,
+        };
+        eprintln!("{}", self.output_format.formatted_string(&error));
+        exit_code
+    }
+
+    /// Returns the signer acting as greeting account authority
+    fn authority(&self) -> &dyn Signer {
+        self.authority.as_deref().unwrap_or(self.fee_payer.as_ref())
+    }
+
+    /// Returns the signer acting as the program's upgrade authority
+    fn upgrade_authority(&self) -> &dyn Signer {
+        self.upgrade_authority
+            .as_deref()
+            .unwrap_or(self.fee_payer.as_ref())
+    }
+
+    /// Returns the signer authorizing use of the durable nonce account
+    fn nonce_authority(&self) -> &dyn Signer {
+        self.nonce_authority
+            .as_deref()
+            .unwrap_or(self.fee_payer.as_ref())
+    }
+
+    /// Whether commands may query the cluster, which an offline signer can't
+    fn online(&self) -> bool {
+        !self.sign_only
+    }
+
+    /// The `ComputeBudget` instructions `send_instructions` prepends, with
+    /// `simulated_units` as the limit under `--auto-compute-limit`
+    fn compute_budget_instructions(&self, simulated_units: u32) -> Vec<Instruction> {
+        let mut instructions = vec![];
+        let compute_unit_limit = match self.compute_unit_limit {
+            ComputeUnitLimit::Default => None,
+            ComputeUnitLimit::Static(units) => Some(units),
+            ComputeUnitLimit::Simulated => Some(simulated_units),
+        };
+        if let Some(units) = compute_unit_limit {
+            instructions.push(ComputeBudgetInstruction::set_compute_unit_limit(units));
+        }
+        if let Some(micro_lamports) = self.compute_unit_price {
+            instructions.push(ComputeBudgetInstruction::set_compute_unit_price(
+                micro_lamports,
+            ));
+        }
+        instructions
+    }
+}
+
+pub type Error = Box<dyn std::error::Error>;
+pub type CommandResult = Result<CommandOutput, Error>;
+
+const PROGRAM_ID: &str = "../../dist/program/helloworld-keypair.json";
+const PROGRAM_SO: &str = "../../dist/program/helloworld.so";
+const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(500);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(8);
+/// The most compute units a transaction may request
+const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+/// Percent added to the simulated units under `--auto-compute-limit`
+const AUTO_COMPUTE_LIMIT_MARGIN: u64 = 10;
+/// `--cluster` values, after the repo's `cluster-*.env` files
+const CLUSTERS: &[&str] = &["devnet", "testnet", "mainnet-beta", "localnet"];
+
+pub use idl::process_idl;
+
+/// Process exit codes, kept stable so scripts can branch on them
+pub mod exit_code {
+    /// Any error without a more specific code
+    pub const FAILURE: i32 = 1;
+    /// Invalid command line arguments
+    pub const USAGE: i32 = 2;
+    pub const ACCOUNT_NOT_FOUND: i32 = 3;
+    pub const ACCOUNT_EXISTS: i32 = 4;
+    pub const INSUFFICIENT_FUNDS: i32 = 5;
+    /// The cluster or the program rejected the transaction
+    pub const TRANSACTION_FAILED: i32 = 6;
+    /// An external tool, such as `solana-test-validator` for `localnet`, failed
+    pub const EXTERNAL_COMMAND_FAILED: i32 = 7;
+    /// `inspect` found data that isn't a greeting instruction or account
+    pub const DECODE_FAILED: i32 = 8;
+    /// `verify` found a deployed program that differs from the local build
+    pub const PROGRAM_MISMATCH: i32 = 9;
+    /// The program failed an instruction with a `GreetingError`
+    pub const PROGRAM_ERROR: i32 = 10;
+    /// The cluster stayed unreachable, or the transaction unconfirmed, after
+    /// all `--retries`
+    pub const NETWORK_TIMEOUT: i32 = 11;
+}
+
+const EXIT_CODES_HELP: &str = "EXIT CODES:
+    0    Success
+    1    Any other error
+    2    Invalid arguments
+    3    Greeting account not found
+    4    Greeting account already exists
+    5    Insufficient funds
+    6    Transaction rejected by the cluster or the program
+    7    External command failed
+    8    Data doesn't decode
+    9    Deployed program differs from the local build
+    10   Program error
+    11   Network timeout";
+
+/// Errors with a dedicated exit code
+#[derive(Error, Debug)]
+enum CliError {
+    #[error("Greeting account {0} not found, run `create` first")]
+    AccountNotFound(Pubkey),
+    #[error("Greeting account {0} already exists")]
+    AccountExists(Pubkey),
+    #[error("Fee payer, {0}, has insufficient balance: {1} SOL required, {2} SOL available")]
+    InsufficientFunds(Pubkey, f64, f64),
+    #[error("Transaction simulation failed: {0}")]
+    SimulationFailed(String),
+    #[error("`{0}` failed: {1}")]
+    ExternalCommandFailed(String, String),
+    #[error("Decoding failed at offset {0}: {1}")]
+    DecodeFailed(usize, String),
+    #[error("Program {0} differs from the local build")]
+    ProgramMismatch(Pubkey),
+    #[error("Instruction {0} failed: {1}")]
+    ProgramError(u8, GreetingError),
+    #[error("Gave up after {0} attempt(s): {1}")]
+    NetworkTimeout(usize, String),
+    #[error("{0}")]
+    Usage(String),
+}
+
+impl CliError {
+    fn exit_code(&self) -> i32 {
+        match self {
+            CliError::AccountNotFound(_) => exit_code::ACCOUNT_NOT_FOUND,
+            CliError::AccountExists(_) => exit_code::ACCOUNT_EXISTS,
+            CliError::InsufficientFunds(..) => exit_code::INSUFFICIENT_FUNDS,
+            CliError::SimulationFailed(_) => exit_code::TRANSACTION_FAILED,
+            CliError::ExternalCommandFailed(..) => exit_code::EXTERNAL_COMMAND_FAILED,
+            CliError::DecodeFailed(..) => exit_code::DECODE_FAILED,
+            CliError::ProgramMismatch(_) => exit_code::PROGRAM_MISMATCH,
+            CliError::ProgramError(..) => exit_code::PROGRAM_ERROR,
+            CliError::NetworkTimeout(..) => exit_code::NETWORK_TIMEOUT,
+            CliError::Usage(_) => exit_code::USAGE,
+        }
+    }
+}
+
+/// The command line of `cli-rust`
+pub fn app<'a, 'b>() -> App<'a, 'b> {
+    App::new(crate_name!())
+        .about(crate_description!())
+        .version(crate_version!())
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .setting(AppSettings::VersionlessSubcommands)
+        .after_help(EXIT_CODES_HELP)
+        .arg({
+            let arg = Arg::with_name("config_file")
+                .short("C")
+                .long("config")
+                .value_name("PATH")
+                .takes_value(true)
+                .global(true)
+                .help("Configuration file to use");
+            if let Some(ref config_file) = *solana_cli_config::CONFIG_FILE {
+                arg.default_value(config_file)
+            } else {
+                arg
+            }
+        })
+        .arg(
+            Arg::with_name("json_rpc_url")
+                .short("u")
+                .long("url")
+                .value_name("URL_OR_MONIKER")
+                .takes_value(true)
+                .global(true)
+                .validator(is_url_or_moniker)
+                .help(
+                    "URL for Solana's JSON RPC or moniker (or their first letter): \
+                    [mainnet-beta, testnet, devnet, localhost] \
+                    Default from the configuration file.",
+                ),
+        )
+        .arg(
+            Arg::with_name("profile")
+                .long("profile")
+                .value_name("NAME")
+                .takes_value(true)
+                .global(true)
+                .conflicts_with("cluster")
+                .help("Use the settings of a profile of helloworld.toml"),
+        )
+        .arg(
+            Arg::with_name("cluster")
+                .long("cluster")
+                .value_name("CLUSTER")
+                .takes_value(true)
+                .global(true)
+                .possible_values(CLUSTERS)
+                .help(
+                    "Use the profile of helloworld.toml named after the cluster, if there \
+                    is one, else the cluster's public RPC URL",
+                ),
+        )
+        .arg(
+            Arg::with_name("websocket_url")
+                .long("ws")
+                .value_name("URL")
+                .takes_value(true)
+                .global(true)
+                .validator(is_url)
+                .help("WebSocket URL for the solana cluster [default: computed from --url]"),
+        )
+        .arg(fee_payer_arg().global(true))
+        .arg(
+            Arg::with_name("authority")
+                .long("authority")
+                .value_name("KEYPAIR")
+                .takes_value(true)
+                .global(true)
+                .validator(is_valid_signer)
+                .help(
+                    "Authority of the greeting account, signs `close` and is recorded by \
+                    `create`. This may be a keypair file or the ASK keyword. \
+                    Defaults to the fee payer.",
+                ),
+        )
+        .arg(
+            Arg::with_name("commitment")
+                .long("commitment")
+                .value_name("COMMITMENT_LEVEL")
+                .takes_value(true)
+                .global(true)
+                .possible_values(&["processed", "confirmed", "finalized"])
+                .hide_possible_values(true)
+                .help(
+                    "Return information at the selected commitment level \
+                    [possible values: processed, confirmed, finalized] \
+                    Default from the configuration file, else confirmed.",
+                ),
+        )
+        .arg(
+            Arg::with_name("program_id")
+                .long("program")
+                .validator(is_valid_pubkey)
+                .value_name("PROGRAM_ID")
+                .takes_value(true)
+                .global(true)
+                .required(false)
+                .help(
+                    "program ID, or the path of the program keypair \
+                    [default: ../../dist/program/helloworld-keypair.json]",
+                ),
+        )
+        .arg(
+            Arg::with_name("greeting_account_seed")
+                .long("seed")
+                .value_name("SEED")
+                .takes_value(true)
+                .global(true)
+                .required(false)
+                .default_value(DEFAULT_SEED)
+                .help("greeting account seed"),
+        )
+        .arg(
+            Arg::with_name("output_format")
+                .long("output")
+                .value_name("FORMAT")
+                .global(true)
+                .takes_value(true)
+                .possible_values(&["json", "json-compact", "display"])
+                .help("Return information in specified output format [default: display]"),
+        )
+        .arg(
+            Arg::with_name("dry_run")
+                .long("dry-run")
+                .takes_value(false)
+                .global(true)
+                .help("Simulate transaction instead of executing"),
+        )
+        .arg(
+            Arg::with_name("compute_unit_price")
+                .long("compute-unit-price")
+                .validator(is_parsable::<u64>)
+                .value_name("MICRO_LAMPORTS")
+                .takes_value(true)
+                .global(true)
+                .help("Priority fee per compute unit, to get transactions in on a busy cluster"),
+        )
+        .arg(
+            Arg::with_name("compute_unit_limit")
+                .long("compute-unit-limit")
+                .validator(is_parsable::<u32>)
+                .value_name("UNITS")
+                .takes_value(true)
+                .global(true)
+                .help("Most compute units a transaction may consume"),
+        )
+        .arg(
+            Arg::with_name("auto_compute_limit")
+                .long("auto-compute-limit")
+                .takes_value(false)
+                .global(true)
+                .conflicts_with("compute_unit_limit")
+                .help(
+                    "Simulate each transaction first and limit its compute units to what it \
+                    consumed, plus 10%",
+                ),
+        )
+        .arg(
+            Arg::with_name("retries")
+                .long("retries")
+                .validator(is_parsable::<usize>)
+                .value_name("NUMBER")
+                .takes_value(true)
+                .global(true)
+                .default_value("3")
+                .help(
+                    "Times to resend a transaction the cluster didn't confirm, backing off \
+                    exponentially and signing it anew once its blockhash expires",
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("create")
+                .about("Create the greeting account derived from the fee payer and --seed")
+                .arg(top_level_only_arg())
+                .arg(greeting_policy_arg())
+                .offline_args()
+                .nonce_args(false),
+        )
+        .subcommand(
+            SubCommand::with_name("greet")
+                .about("Send greetings to an existing greeting account")
+                .arg(greetings_arg())
+                .arg(
+                    Arg::with_name("greeting_string")
+                        .long("greeting-string")
+                        .alias("greeting_string")
+                        .value_name("GREETING_STRING")
+                        .takes_value(true)
+                        .required(false)
+                        .default_value("hello")
+                        .help("Extra greeting string to pass"),
+                )
+                .arg(greeting_nonce_arg().required(false).help(
+                    "reject the greeting unless this nonce is higher than every nonce \
+                            the fee payer used before",
+                ))
+                .arg(greeting_account_arg())
+                .offline_args()
+                .nonce_args(false),
+        )
+        .subcommand(
+            SubCommand::with_name("show")
+                .about("Show the state of a greeting account")
+                .arg(greeting_account_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("migrate")
+                .about(
+                    "Grow a greeting account created before accounts had an authority and set \
+                    its authority and flags",
+                )
+                .arg(top_level_only_arg())
+                .arg(greeting_policy_arg())
+                .offline_args()
+                .nonce_args(false),
+        )
+        .subcommand(
+            SubCommand::with_name("close")
+                .about("Close a greeting account and reclaim its rent")
+                .arg(greeting_account_arg())
+                .arg(
+                    Arg::with_name("recipient")
+                        .long("recipient")
+                        .validator(is_valid_pubkey)
+                        .value_name("RECIPIENT")
+                        .takes_value(true)
+                        .required(false)
+                        .help("account receiving the lamports [default: the fee payer]"),
+                )
+                .offline_args()
+                .nonce_args(false),
+        )
+        .subcommand(
+            SubCommand::with_name("watch")
+                .about("Stream the changes of a greeting account as they happen")
+                .arg(
+                    Arg::with_name("greeting_account")
+                        .value_name("GREETING_ACCOUNT")
+                        .validator(is_valid_pubkey)
+                        .takes_value(true)
+                        .index(1)
+                        .help("greeting account [default: derived from the fee payer and --seed]"),
+                )
+                .arg(
+                    Arg::with_name("count")
+                        .long("count")
+                        .validator(is_parsable::<u64>)
+                        .value_name("COUNT")
+                        .takes_value(true)
+                        .help("Exit after this many changes [default: watch until interrupted]"),
+                )
+                .after_help("With --output json-compact every update is printed as one JSON line."),
+        )
+        .subcommand(
+            SubCommand::with_name("history")
+                .about("Show the transactions of a greeting account, oldest first")
+                .arg(
+                    Arg::with_name("greeting_account")
+                        .value_name("GREETING_ACCOUNT")
+                        .validator(is_valid_pubkey)
+                        .takes_value(true)
+                        .index(1)
+                        .help("greeting account [default: derived from the fee payer and --seed]"),
+                )
+                .arg(
+                    Arg::with_name("limit")
+                        .long("limit")
+                        .validator(is_parsable::<usize>)
+                        .value_name("N")
+                        .takes_value(true)
+                        .help("Show the latest N transactions [default: all]"),
+                )
+                .arg(
+                    Arg::with_name("before")
+                        .long("before")
+                        .validator(is_parsable::<Signature>)
+                        .value_name("SIGNATURE")
+                        .takes_value(true)
+                        .help("Start with the transactions before this one"),
+                )
+                .arg(
+                    Arg::with_name("until")
+                        .long("until")
+                        .validator(is_parsable::<Signature>)
+                        .value_name("SIGNATURE")
+                        .takes_value(true)
+                        .help("Stop at this transaction, excluding it"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("list")
+                .about("List the greeting accounts of the program, by counter")
+                .arg(
+                    Arg::with_name("with_authority")
+                        .long("with-authority")
+                        .validator(is_valid_pubkey)
+                        .value_name("AUTHORITY")
+                        .takes_value(true)
+                        .help("Only list the accounts of this authority"),
+                )
+                .arg(
+                    Arg::with_name("memcmp")
+                        .long("memcmp")
+                        .validator(is_memcmp)
+                        .value_name("OFFSET:BYTES")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Only list accounts holding these base58 bytes at OFFSET"),
+                )
+                .arg(
+                    Arg::with_name("order")
+                        .long("order")
+                        .value_name("ORDER")
+                        .takes_value(true)
+                        .possible_values(&["asc", "desc"])
+                        .default_value("desc")
+                        .help("Order of the counters"),
+                )
+                .arg(
+                    Arg::with_name("offset")
+                        .long("offset")
+                        .validator(is_parsable::<usize>)
+                        .value_name("N")
+                        .takes_value(true)
+                        .help("Skip the first N accounts"),
+                )
+                .arg(
+                    Arg::with_name("limit")
+                        .long("limit")
+                        .validator(is_parsable::<usize>)
+                        .value_name("N")
+                        .takes_value(true)
+                        .help("List at most N accounts"),
+                )
+                .arg(
+                    Arg::with_name("export")
+                        .long("export")
+                        .validator(is_export_path)
+                        .value_name("FILE")
+                        .takes_value(true)
+                        .help("Also write the listed accounts to a .csv or .json file"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("inspect")
+                .about("Decode raw greeting instruction data or greeting account data")
+                .arg(
+                    Arg::with_name("kind")
+                        .value_name("KIND")
+                        .takes_value(true)
+                        .index(1)
+                        .required(true)
+                        .possible_values(&["instruction", "account"])
+                        .help("What the data is"),
+                )
+                .arg(
+                    Arg::with_name("value")
+                        .value_name("DATA_OR_ACCOUNT")
+                        .takes_value(true)
+                        .index(2)
+                        .required(true)
+                        .help(
+                            "base58 or base64 instruction data; \
+                             account address or dump file for `account`",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("encoding")
+                        .long("encoding")
+                        .value_name("ENCODING")
+                        .takes_value(true)
+                        .possible_values(&["base58", "base64"])
+                        .help("Encoding of the instruction data [default: base58, else base64]"),
+                ),
+        )
+        .subcommand(SubCommand::with_name("config").about("Show the effective configuration"))
+        .subcommand(
+            SubCommand::with_name("idl")
+                .about(
+                    "Generate the program's JSON IDL and TypeScript Borsh schema from its \
+                    Rust types, printing the IDL unless given a file",
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .value_name("FILE.json")
+                        .takes_value(true)
+                        .help("write the IDL to this file"),
+                )
+                .arg(
+                    Arg::with_name("ts")
+                        .long("ts")
+                        .value_name("FILE.ts")
+                        .takes_value(true)
+                        .help("write the TypeScript schema to this file"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("deploy")
+                .about("Deploy the program through the upgradeable BPF loader")
+                .arg(program_so_arg())
+                .arg(buffer_arg())
+                .arg(upgrade_authority_arg())
+                .arg(
+                    Arg::with_name("max_len")
+                        .long("max-len")
+                        .validator(is_parsable::<usize>)
+                        .value_name("BYTES")
+                        .takes_value(true)
+                        .help("Room left for upgrades [default: twice the program size]"),
+                )
+                .arg(
+                    Arg::with_name("final")
+                        .long("final")
+                        .takes_value(false)
+                        .help("Make the program immutable, it can't be upgraded"),
+                )
+                .after_help("The program keypair is read from --program."),
+        )
+        .subcommand(
+            SubCommand::with_name("upgrade")
+                .about("Upgrade the deployed program")
+                .arg(program_so_arg())
+                .arg(buffer_arg())
+                .arg(upgrade_authority_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("set-upgrade-authority")
+                .about("Transfer the program's upgrade authority, or make it final")
+                .arg(
+                    Arg::with_name("new_upgrade_authority")
+                        .value_name("NEW_UPGRADE_AUTHORITY")
+                        .validator(is_valid_pubkey)
+                        .takes_value(true)
+                        .index(1)
+                        .required_unless("final")
+                        .help("the new upgrade authority"),
+                )
+                .arg(
+                    Arg::with_name("final")
+                        .long("final")
+                        .takes_value(false)
+                        .conflicts_with("new_upgrade_authority")
+                        .help("Make the program immutable, it can't be upgraded"),
+                )
+                .arg(upgrade_authority_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("verify")
+                .about("Check that the deployed program matches a local build")
+                .arg(program_so_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("localnet")
+                .about(
+                    "Start a local validator if none is running, fund the fee payer, deploy \
+                    the program and create the greeting account",
+                )
+                .arg(
+                    Arg::with_name("action")
+                        .value_name("ACTION")
+                        .possible_values(&["up"])
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("program_so")
+                        .long("program-file")
+                        .value_name("PROGRAM_FILEPATH")
+                        .takes_value(true)
+                        .default_value(PROGRAM_SO)
+                        .help("compiled program"),
+                )
+                .arg(
+                    Arg::with_name("ledger")
+                        .long("ledger")
+                        .value_name("DIR")
+                        .takes_value(true)
+                        .default_value("test-ledger")
+                        .help("ledger directory of a validator started by this command"),
+                )
+                .arg(
+                    Arg::with_name("airdrop")
+                        .long("airdrop")
+                        .validator(is_parsable::<f64>)
+                        .value_name("SOL")
+                        .takes_value(true)
+                        .default_value("10")
+                        .help("airdrop to the fee payer while its balance is below this"),
+                )
+                .arg(upgrade_authority_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("bench")
+                .about(
+                    "Send greetings across several accounts and payers at once and report \
+                    throughput, latency, failures and fees. Local test validator only.",
+                )
+                .arg(
+                    Arg::with_name("transactions")
+                        .long("transactions")
+                        .validator(is_parsable::<usize>)
+                        .value_name("NUMBER")
+                        .takes_value(true)
+                        .default_value("100")
+                        .help("greeting transactions to send"),
+                )
+                .arg(
+                    Arg::with_name("accounts")
+                        .long("accounts")
+                        .validator(is_positive)
+                        .value_name("NUMBER")
+                        .takes_value(true)
+                        .default_value("4")
+                        .help(
+                            "greeting accounts to spread them across, derived from the fee payer",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("payers")
+                        .long("payers")
+                        .validator(is_positive)
+                        .value_name("NUMBER")
+                        .takes_value(true)
+                        .default_value("4")
+                        .help("throwaway fee payers to spread them across"),
+                )
+                .arg(
+                    Arg::with_name("payer_funding")
+                        .long("payer-funding")
+                        .validator(is_parsable::<f64>)
+                        .value_name("SOL")
+                        .takes_value(true)
+                        .default_value("1")
+                        .help("airdrop to each payer before sending"),
+                )
+                .arg(
+                    Arg::with_name("concurrency")
+                        .long("concurrency")
+                        .validator(is_positive)
+                        .value_name("NUMBER")
+                        .takes_value(true)
+                        .default_value("8")
+                        .help("transactions in flight at once"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("estimate")
+                .about(
+                    "Report the rent-exempt minimum of each account and the fee of each kind \
+                    of transaction, without signing anything",
+                )
+                .arg(
+                    Arg::with_name("batch")
+                        .long("batch")
+                        .value_name("FILE.csv")
+                        .takes_value(true)
+                        .help(
+                            "also project the cost of a batch, from `instruction,count` lines; \
+                            instructions are create, greet, greet-with-nonce, signed-greeting, \
+                            set-flags and close",
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("sign-greeting")
+                .about("Sign a greeting off-chain for someone else to relay")
+                .arg(greetings_arg())
+                .arg(greeting_nonce_arg())
+                .arg(greeting_account_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("relay-greeting")
+                .about("Submit a greeting signed with `sign-greeting`, paying its fee")
+                .arg(
+                    Arg::with_name("greeter")
+                        .long("greeter")
+                        .validator(is_valid_pubkey)
+                        .value_name("GREETER_PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("public key that signed the greeting"),
+                )
+                .arg(
+                    Arg::with_name("signature")
+                        .long("signature")
+                        .validator(is_parsable::<Signature>)
+                        .value_name("SIGNATURE")
+                        .takes_value(true)
+                        .required(true)
+                        .help("signature printed by `sign-greeting`"),
+                )
+                .arg(greetings_arg())
+                .arg(greeting_nonce_arg())
+                .arg(greeting_account_arg())
+                .offline_args()
+                .nonce_args(false),
+        )
+}
+
+/// Runs `sub_command` with its `matches`, printing and returning its output
+pub fn process_command(config: &Config, sub_command: &str, matches: &ArgMatches) -> CommandResult {
+    let output: CommandOutput = match sub_command {
+        "create" => process_create(config, matches)?.into(),
+        "greet" => process_greet(config, matches)?.into(),
+        "show" => process_show(config, matches)?.into(),
+        "migrate" => process_migrate(config, matches)?.into(),
+        "close" => process_close(config, matches)?.into(),
+        "watch" => process_watch(config, matches)?.into(),
+        "history" => process_history(config, matches)?.into(),
+        "list" => process_list(config, matches)?.into(),
+        "inspect" => process_inspect(config, matches)?.into(),
+        "localnet" => process_localnet(config, matches)?.into(),
+        "bench" => process_bench(config, matches)?.into(),
+        "estimate" => process_estimate(config, matches)?.into(),
+        "config" => process_config(config).into(),
+        "idl" => process_idl(matches)?.into(),
+        "deploy" => process_deploy(config, matches)?.into(),
+        "upgrade" => process_upgrade(config, matches)?.into(),
+        "set-upgrade-authority" => process_set_upgrade_authority(config, matches)?.into(),
+        "verify" => process_verify(config, matches)?.into(),
+        "sign-greeting" => process_sign_greeting(config, matches)?.into(),
+        "relay-greeting" => process_relay_greeting(config, matches)?.into(),
+        _ => unreachable!(),
+    };
+    output.print(&config.output_format);
+    Ok(output)
+}
+
+fn process_create(config: &Config, matches: &ArgMatches) -> Result<CliTransaction, Error> {
+    let flags = greeting_account_flags(matches);
+    create_greeting_account(config, &config.greeting_account_seed, flags)
+}
+
+/// Creates and initializes the fee payer's greeting account for `greeting_account_seed`
+fn create_greeting_account(
+    config: &Config,
+    greeting_account_seed: &str,
+    flags: u8,
+) -> Result<CliTransaction, Error> {
+    let greeting_account_id = greeting_account_address(
+        &config.fee_payer.pubkey(),
+        greeting_account_seed,
+        &config.program_id,
+    )?;
+    let lamports = if config.online() {
+        let existing_account = config
+            .rpc_client
+            .get_account_with_commitment(&greeting_account_id, config.rpc_client.commitment())?
+            .value;
+        if existing_account.is_some() {
+            return Err(CliError::AccountExists(greeting_account_id).into());
+        }
+        config
+            .rpc_client
+            .get_minimum_balance_for_rent_exemption(GreetingAccount::LEN)?
+    } else {
+        Rent::default().minimum_balance(GreetingAccount::LEN)
+    };
+    println_display(
+        config,
+        format!("Creating greeting account {}...", greeting_account_id),
+    );
+    send_instructions(
+        config,
+        &[
+            create_account_with_seed(
+                &config.fee_payer.pubkey(),
+                &greeting_account_id,
+                &config.fee_payer.pubkey(),
+                greeting_account_seed,
+                lamports,
+                GreetingAccount::LEN as u64,
+                &config.program_id,
+            ),
+            initialize(
+                config.program_id,
+                greeting_account_id,
+                config.fee_payer.pubkey(),
+                greeting_account_seed.to_string(),
+                config.authority().pubkey(),
+                flags,
+            ),
+        ],
+        &[],
+        lamports,
+    )
+}
+
+fn process_greet(config: &Config, matches: &ArgMatches) -> Result<CliTransaction, Error> {
+    // Parse inputs from arguments
+    let num_greetings: u32 = value_of(matches, "greetings").unwrap();
+    let greeting_string = String::from(matches.value_of("greeting_string").unwrap());
+    let greeting_account_id = greeting_account_of(config, matches, &config.fee_payer.pubkey())?;
+    // Check the string against the account's policy before paying for a
+    // transaction the program would reject; offline, only the defaults apply
+    let greeting_policy = if config.online() {
+        get_greeting_account(config, &greeting_account_id)?.greeting_policy()?
+    } else {
+        GreetingPolicy::DEFAULT
+    };
+    greeting_policy
+        .validate(&greeting_string)
+        .map_err(|e| CliError::Usage(format!("Invalid greeting string: {}", e)))?;
+
+    let instruction = match value_of::<u64>(matches, "greeting_nonce") {
+        Some(nonce) => init_greeting_with_nonce(
+            config.program_id,
+            greeting_account_id,
+            &config.fee_payer.pubkey(),
+            &config.fee_payer.pubkey(),
+            num_greetings,
+            greeting_string,
+            nonce,
+        ),
+        None => init_greeting(
+            config.program_id,
+            greeting_account_id,
+            num_greetings,
+            greeting_string,
+        ),
+    };
+    println_display(config, format!("Adding {} greetings...", &num_greetings));
+    let transaction = send_instructions(config, &[instruction], &[], 0)?;
+
+    report_greetings(config, transaction, &greeting_account_id)
+}
+
+fn process_show(config: &Config, matches: &ArgMatches) -> Result<CliGreetingAccount, Error> {
+    let greeting_account_id = greeting_account_of(config, matches, &config.fee_payer.pubkey())?;
+    let greeting_account = get_greeting_account(config, &greeting_account_id)?;
+    Ok(CliGreetingAccount::new(
+        &greeting_account_id,
+        greeting_account,
+    ))
+}
+
+fn process_migrate(config: &Config, matches: &ArgMatches) -> Result<CliTransaction, Error> {
+    let fee_payer = config.fee_payer.pubkey();
+    let greeting_account_id = greeting_account_address(
+        &fee_payer,
+        &config.greeting_account_seed,
+        &config.program_id,
+    )?;
+    let lamports = if config.online() {
+        get_greeting_account(config, &greeting_account_id)?;
+        config
+            .rpc_client
+            .get_minimum_balance_for_rent_exemption(GreetingAccount::LEN)?
+            .saturating_sub(config.rpc_client.get_balance(&greeting_account_id)?)
+    } else {
+        Rent::default().minimum_balance(GreetingAccount::LEN)
+    };
+
+    println_display(
+        config,
+        format!("Migrating greeting account {}...", greeting_account_id),
+    );
+    send_instructions(
+        config,
+        &[migrate(
+            config.program_id,
+            greeting_account_id,
+            fee_payer,
+            fee_payer,
+            config.greeting_account_seed.clone(),
+            config.authority().pubkey(),
+            greeting_account_flags(matches),
+        )],
+        &[],
+        lamports,
+    )
+}
+
+fn process_close(config: &Config, matches: &ArgMatches) -> Result<CliTransaction, Error> {
+    let greeting_account_id = greeting_account_of(config, matches, &config.fee_payer.pubkey())?;
+    let recipient = pubkey_of(matches, "recipient").unwrap_or_else(|| config.fee_payer.pubkey());
+    if config.online() {
+        get_greeting_account(config, &greeting_account_id)?;
+    }
+
+    println_display(
+        config,
+        format!(
+            "Closing greeting account {}, sending its lamports to {}...",
+            greeting_account_id, recipient
+        ),
+    );
+    send_instructions(
+        config,
+        &[close(
+            config.program_id,
+            greeting_account_id,
+            config.authority().pubkey(),
+            recipient,
+        )],
+        &[config.authority()],
+        0,
+    )
+}
+
+fn process_config(config: &Config) -> CliConfig {
+    CliConfig {
+        config_file: config.config_file.clone(),
+        project_config: config.project_config.clone(),
+        profile: config.profile.clone(),
+        json_rpc_url: config.rpc_client.url(),
+        websocket_url: config.websocket_url.clone(),
+        commitment: config.rpc_client.commitment().commitment.to_string(),
+        fee_payer: config.fee_payer.pubkey().to_string(),
+        authority: config.authority().pubkey().to_string(),
+        program_id: config.program_id.to_string(),
+        greeting_account_seed: config.greeting_account_seed.clone(),
+    }
+}
+
+fn process_sign_greeting(
+    config: &Config,
+    matches: &ArgMatches,
+) -> Result<CliSignedGreeting, Error> {
+    let greeter = config.fee_payer.pubkey();
+    let greeting_account_id = greeting_account_of(config, matches, &greeter)?;
+    let payload = GreetingPayload {
+        program_id: config.program_id,
+        greeting_account: greeting_account_id,
+        num_greetings: value_of(matches, "greetings").unwrap(),
+        nonce: value_of(matches, "greeting_nonce").unwrap(),
+    };
+    let signature = config.fee_payer.try_sign_message(&payload.try_to_vec()?)?;
+
+    Ok(CliSignedGreeting {
+        greeter: greeter.to_string(),
+        greeting_account: payload.greeting_account.to_string(),
+        greetings: payload.num_greetings,
+        nonce: payload.nonce,
+        signature: signature.to_string(),
+    })
+}
+
+fn process_relay_greeting(config: &Config, matches: &ArgMatches) -> Result<CliTransaction, Error> {
+    let greeter = pubkey_of(matches, "greeter").unwrap();
+    let signature: Signature = value_of(matches, "signature").unwrap();
+    let num_greetings: u32 = value_of(matches, "greetings").unwrap();
+    let nonce: u64 = value_of(matches, "greeting_nonce").unwrap();
+    let greeting_account_id = greeting_account_of(config, matches, &greeter)?;
+
+    println_display(
+        config,
+        format!(
+            "Relaying {} greetings from {} to {}...",
+            num_greetings, greeter, greeting_account_id
+        ),
+    );
+    let transaction = send_instructions(
+        config,
+        &signed_greeting(
+            config.program_id,
+            greeting_account_id,
+            &greeter,
+            &config.fee_payer.pubkey(),
+            signature.as_ref(),
+            num_greetings,
+            nonce,
+        ),
+        &[],
+        0,
+    )?;
+
+    report_greetings(config, transaction, &greeting_account_id)
+}
+
+// HELPERS
+
+fn program_so_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("program_so")
+        .value_name("PROGRAM_FILEPATH")
+        .takes_value(true)
+        .index(1)
+        .default_value(PROGRAM_SO)
+        .help("compiled program")
+}
+
+fn buffer_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("buffer")
+        .long("buffer")
+        .validator(is_valid_pubkey)
+        .value_name("BUFFER")
+        .takes_value(true)
+        .help("Resume writing to the buffer of a failed attempt")
+}
+
+fn upgrade_authority_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("upgrade_authority")
+        .long("upgrade-authority")
+        .validator(is_valid_signer)
+        .value_name("KEYPAIR")
+        .takes_value(true)
+        .help("Upgrade authority of the program [default: the fee payer]")
+}
+
+fn greetings_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("greetings")
+        .long("greetings")
+        .validator(is_parsable::<u32>)
+        .value_name("GREETINGS")
+        .takes_value(true)
+        .required(false)
+        .default_value("1")
+        .help("number of greetings to increment")
+}
+
+fn top_level_only_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("top_level_only")
+        .long("top-level-only")
+        .takes_value(false)
+        .help("Reject greetings sent through CPI")
+}
+
+fn greeting_policy_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("greeting_policy")
+        .long("greeting-policy")
+        .possible_values(&["any", "ascii", "printable"])
+        .value_name("POLICY")
+        .takes_value(true)
+        .default_value("any")
+        .help(
+            "Characters greeting strings may contain: anything but control characters, \
+            ASCII only, or printable only without invisible format characters",
+        )
+}
+
+/// The greeting account flags selected by `--top-level-only` and `--greeting-policy`
+fn greeting_account_flags(matches: &ArgMatches) -> u8 {
+    let flags = match matches.value_of("greeting_policy") {
+        Some("ascii") => GreetingAccount::ASCII_ONLY,
+        Some("printable") => GreetingAccount::PRINTABLE_ONLY,
+        _ => 0,
+    };
+    if matches.is_present("top_level_only") {
+        flags | GreetingAccount::TOP_LEVEL_ONLY
+    } else {
+        flags
+    }
+}
+
+fn greeting_nonce_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("greeting_nonce")
+        .long("greeting-nonce")
+        .validator(is_parsable::<u64>)
+        .value_name("NONCE")
+        .takes_value(true)
+        .required(true)
+        .help(
+            "nonce included in the signed greeting, which must be higher than every nonce \
+            the greeter used before",
+        )
+}
+
+fn greeting_account_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("greeting_account")
+        .long("greeting-account")
+        .validator(is_valid_pubkey)
+        .value_name("GREETING_ACCOUNT")
+        .takes_value(true)
+        .required(false)
+        .help("greeting account [default: derived from the greeter and --seed]")
+}
+
+fn is_positive(value: String) -> Result<(), String> {
+    match value.parse::<usize>() {
+        Ok(number) if number > 0 => Ok(()),
+        _ => Err(format!("{} isn't a positive number", value)),
+    }
+}
+
+/// Returns `--greeting-account`, or else the account derived from `base` and `--seed`
+fn greeting_account_of(
+    config: &Config,
+    matches: &ArgMatches,
+    base: &Pubkey,
+) -> Result<Pubkey, Error> {
+    match pubkey_of(matches, "greeting_account") {
+        Some(greeting_account_id) => Ok(greeting_account_id),
+        None => Ok(greeting_account_address(
+            base,
+            &config.greeting_account_seed,
+            &config.program_id,
+        )?),
+    }
+}
+
+/// Fetches and decodes a greeting account, failing with `CliError::AccountNotFound`
+/// if it doesn't exist
+fn get_greeting_account(
+    config: &Config,
+    greeting_account_id: &Pubkey,
+) -> Result<GreetingAccount, Error> {
+    let account = config
+        .rpc_client
+        .get_account_with_commitment(greeting_account_id, config.rpc_client.commitment())?
+        .value
+        .ok_or(CliError::AccountNotFound(*greeting_account_id))?;
+    if account.owner != config.program_id {
+        return Err(format!(
+            "{} is not a greeting account: owned by {}, not {}",
+            greeting_account_id, account.owner, config.program_id
+        )
+        .into());
+    }
+    Ok(GreetingAccount::unpack_versioned(&account.data)?)
+}
+
+/// A greeting transaction, with the greeting account's new state unless it was
+/// only simulated or signed
+fn report_greetings(
+    config: &Config,
+    mut transaction: CliTransaction,
+    greeting_account_id: &Pubkey,
+) -> Result<CliTransaction, Error> {
+    if !config.dry_run && config.online() {
+        let greeting_account = get_greeting_account(config, greeting_account_id)?;
+        transaction.greeting_account = Some(CliGreetingAccount::new(
+            greeting_account_id,
+            greeting_account,
+        ));
+    }
+    Ok(transaction)
+}
+
+fn println_display(config: &Config, message: String) {
+    match config.output_format {
+        OutputFormat::Display | OutputFormat::DisplayVerbose => {
+            println!("{}", message);
+        }
+        _ => {}
+    }
+}
+
+/// Signs `instructions` with the fee payer and `signers` and sends them, after
+/// checking the fee payer can cover the fee plus `lamports`.
+///
+/// With `--sign-only` the transaction is only partially signed and its
+/// signatures are returned, to be passed back with `--signer`.
+fn send_instructions(
+    config: &Config,
+    instructions: &[Instruction],
+    signers: &[&dyn Signer],
+    lamports: u64,
+) -> Result<CliTransaction, Error> {
+    let fee_payer = config.fee_payer.pubkey();
+    let new_message = |simulated_units| {
+        let instructions = [
+            config.compute_budget_instructions(simulated_units),
+            instructions.to_vec(),
+        ]
+        .concat();
+        match config.nonce_account {
+            Some(nonce_account) => Message::new_with_nonce(
+                instructions,
+                Some(&fee_payer),
+                &nonce_account,
+                &config.nonce_authority().pubkey(),
+            ),
+            None => Message::new(&instructions, Some(&fee_payer)),
+        }
+    };
+    let simulated_units = if config.compute_unit_limit == ComputeUnitLimit::Simulated {
+        simulate_compute_units(config, new_message(MAX_COMPUTE_UNIT_LIMIT))?
+    } else {
+        MAX_COMPUTE_UNIT_LIMIT
+    };
+    let mut message = new_message(simulated_units);
+    message.recent_blockhash = get_blockhash(config.rpc_client.as_ref(), &config.blockhash_query)?;
+
+    let mut all_signers = vec![config.fee_payer.as_ref()];
+    if config.nonce_account.is_some() {
+        all_signers.push(config.nonce_authority());
+    }
+    all_signers.extend_from_slice(signers);
+    let all_signers = unique_signers(all_signers);
+
+    let mut transaction = Transaction::new_unsigned(message);
+    if config.sign_only {
+        transaction.try_partial_sign(&all_signers, transaction.message.recent_blockhash)?;
+        let sign_only = return_signers_data(
+            &transaction,
+            &ReturnSignersConfig {
+                dump_transaction_message: config.dump_transaction_message,
+            },
+        );
+        return Ok(CliTransaction {
+            sign_only: Some(sign_only),
+            ..CliTransaction::default()
+        });
+    }
+
+    let fee = if config.nonce_account.is_some() {
+        // A durable nonce isn't in the blockhash queue the fee is looked up in
+        let mut message = transaction.message.clone();
+        message.recent_blockhash = config.rpc_client.get_latest_blockhash()?;
+        config.rpc_client.get_fee_for_message(&message)?
+    } else {
+        config
+            .rpc_client
+            .get_fee_for_message(&transaction.message)?
+    };
+    check_fee_payer_balance(config, lamports + fee)?;
+    transaction.try_sign(&all_signers, transaction.message.recent_blockhash)?;
+    send_transaction(config, transaction, &all_signers, lamports, fee)
+}
+
+/// Simulates `message` to find the compute unit limit for
+/// `--auto-compute-limit`
+fn simulate_compute_units(config: &Config, message: Message) -> Result<u32, Error> {
+    if config.sign_only {
+        return Err("--auto-compute-limit needs to simulate, which --sign-only can't".into());
+    }
+    let result = config
+        .rpc_client
+        .simulate_transaction_with_config(
+            &Transaction::new_unsigned(message),
+            RpcSimulateTransactionConfig {
+                sig_verify: false,
+                replace_recent_blockhash: true,
+                commitment: Some(config.rpc_client.commitment()),
+                ..RpcSimulateTransactionConfig::default()
+            },
+        )?
+        .value;
+    if let Some(err) = result.err {
+        return Err(CliError::SimulationFailed(err.to_string()).into());
+    }
+    let units_consumed = result
+        .units_consumed
+        .ok_or("The cluster didn't report the compute units the simulation consumed")?;
+    let compute_unit_limit = auto_compute_unit_limit(units_consumed);
+    println_display(
+        config,
+        format!(
+            "Limiting compute units to {} ({} simulated)",
+            compute_unit_limit, units_consumed
+        ),
+    );
+    Ok(compute_unit_limit)
+}
+
+/// The compute unit limit for a transaction that consumed `units_consumed`
+/// in simulation: that plus the margin, capped at the most allowed
+fn auto_compute_unit_limit(units_consumed: u64) -> u32 {
+    let units = units_consumed.saturating_mul(100 + AUTO_COMPUTE_LIMIT_MARGIN) / 100;
+    units.min(MAX_COMPUTE_UNIT_LIMIT as u64) as u32
+}
+
+/// Sends `transaction`, or only simulates it with `--dry-run`. A failed
+/// simulation is printed before its error is returned.
+fn send_transaction(
+    config: &Config,
+    transaction: Transaction,
+    signers: &[&dyn Signer],
+    lamports: u64,
+    fee: u64,
+) -> Result<CliTransaction, Error> {
+    if config.dry_run {
+        let (simulation, transaction_error) = simulate(config, &transaction, fee)?;
+        let cli_transaction = CliTransaction {
+            simulation: Some(simulation),
+            ..CliTransaction::default()
+        };
+        if let Some(transaction_error) = transaction_error {
+            println!(
+                "{}",
+                config.output_format.formatted_string(&cli_transaction)
+            );
+            return Err(
+                match greeting_error(config, &transaction, &transaction_error) {
+                    Some((index, error)) => CliError::ProgramError(index, error),
+                    None => CliError::SimulationFailed(transaction_error.to_string()),
+                }
+                .into(),
+            );
+        }
+        Ok(cli_transaction)
+    } else {
+        let signature = send_with_retries(config, transaction, signers, lamports + fee)?;
+        Ok(CliTransaction {
+            signature: Some(signature.to_string()),
+            ..CliTransaction::default()
+        })
+    }
+}
+
+/// Sends and confirms `transaction`, retrying `--retries` times with
+/// exponential backoff while the cluster is unreachable or doesn't confirm it
+fn send_with_retries(
+    config: &Config,
+    mut transaction: Transaction,
+    signers: &[&dyn Signer],
+    required_balance: u64,
+) -> Result<Signature, Error> {
+    // A blockhash passed with `--blockhash`, or a durable nonce, is all the
+    // signatures can be for
+    let can_refresh_blockhash =
+        config.nonce_account.is_none() && matches!(config.blockhash_query, BlockhashQuery::All(_));
+    let mut retry_delay = INITIAL_RETRY_DELAY;
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let err = match config
+            .rpc_client
+            .send_and_confirm_transaction_with_spinner(&transaction)
+        {
+            Ok(signature) => return Ok(signature),
+            Err(err) => err,
+        };
+        match err.get_transaction_error() {
+            Some(TransactionError::BlockhashNotFound) if can_refresh_blockhash => {}
+            Some(transaction_error) => {
+                return Err(classify_transaction_error(
+                    config,
+                    &transaction,
+                    transaction_error,
+                    required_balance,
+                )
+                .unwrap_or_else(|| err.into()))
+            }
+            None if !is_transient(&err) => return Err(err.into()),
+            None => {}
+        }
+        if attempts > config.retries {
+            return Err(CliError::NetworkTimeout(attempts, err.to_string()).into());
+        }
+        eprintln!(
+            "{}, retrying in {}ms ({}/{})...",
+            err,
+            retry_delay.as_millis(),
+            attempts,
+            config.retries
+        );
+        sleep(retry_delay);
+        retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
+
+        let blockhash_expired = !config
+            .rpc_client
+            .is_blockhash_valid(
+                &transaction.message.recent_blockhash,
+                CommitmentConfig::processed(),
+            )
+            .unwrap_or(true);
+        if can_refresh_blockhash && blockhash_expired {
+            // The transaction can't land anymore, so signing it anew can't
+            // execute it twice. It may have landed before it expired though.
+            let signature = transaction.signatures[0];
+            match config.rpc_client.get_signature_status(&signature) {
+                Ok(Some(Ok(()))) => return Ok(signature),
+                Ok(Some(Err(transaction_error))) => {
+                    return Err(classify_transaction_error(
+                        config,
+                        &transaction,
+                        transaction_error.clone(),
+                        required_balance,
+                    )
+                    .unwrap_or_else(|| ClientError::from(transaction_error).into()))
+                }
+                _ => {}
+            }
+            let blockhash = config.rpc_client.get_latest_blockhash()?;
+            transaction.try_sign(&signers.to_vec(), blockhash)?;
+        }
+    }
+}
+
+/// Whether `err` is worth retrying: the cluster was unreachable or
+/// unhealthy, or didn't confirm the transaction in time
+fn is_transient(err: &ClientError) -> bool {
+    matches!(
+        err.kind(),
+        ClientErrorKind::Io(_)
+            | ClientErrorKind::Reqwest(_)
+            | ClientErrorKind::RpcError(
+                RpcError::ForUser(_)
+                    | RpcError::RpcRequestError(_)
+                    | RpcError::RpcResponseError {
+                        data: RpcResponseErrorData::Empty
+                            | RpcResponseErrorData::NodeUnhealthy { .. },
+                        ..
+                    }
+            )
+    )
+}
+
+/// The `CliError` a failed transaction stands for: insufficient funds, or a
+/// `GreetingError` from the program. `None` for any other failure.
+fn classify_transaction_error(
+    config: &Config,
+    transaction: &Transaction,
+    transaction_error: TransactionError,
+    required_balance: u64,
+) -> Option<Error> {
+    // Unless the balance went up again since
+    let insufficient_funds = || check_fee_payer_balance(config, required_balance).err();
+    match transaction_error {
+        TransactionError::InsufficientFundsForFee
+        | TransactionError::InsufficientFundsForRent { .. } => insufficient_funds(),
+        TransactionError::InstructionError(index, InstructionError::Custom(code)) => {
+            if let Some((index, error)) = greeting_error(config, transaction, &transaction_error) {
+                return Some(CliError::ProgramError(index, error).into());
+            }
+            let message = &transaction.message;
+            let program_id = message
+                .instructions
+                .get(index as usize)
+                .map(|instruction| message.account_keys[instruction.program_id_index as usize])?;
+            if program_id == system_program::id()
+                && code == SystemError::ResultWithNegativeLamports as u32
+            {
+                insufficient_funds()
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The instruction `transaction` failed at and its `GreetingError`, if the
+/// program failed it with one
+fn greeting_error(
+    config: &Config,
+    transaction: &Transaction,
+    transaction_error: &TransactionError,
+) -> Option<(u8, GreetingError)> {
+    match transaction_error {
+        TransactionError::InstructionError(index, InstructionError::Custom(code)) => {
+            let message = &transaction.message;
+            let instruction = message.instructions.get(*index as usize)?;
+            if message.account_keys[instruction.program_id_index as usize] != config.program_id {
+                return None;
+            }
+            Some((*index, GreetingError::from_code(*code)?))
+        }
+        _ => None,
+    }
+}
+
+fn check_fee_payer_balance(config: &Config, required_balance: u64) -> Result<(), Error> {
+    let balance = config.rpc_client.get_balance(&config.fee_payer.pubkey())?;
+    if balance < required_balance {
+        Err(CliError::InsufficientFunds(
+            config.fee_payer.pubkey(),
+            lamports_to_sol(required_balance),
+            lamports_to_sol(balance),
+        )
+        .into())
+    } else {
+        Ok(())
+    }
+}
+
+/// The process exit code `err` maps to
+pub fn exit_code_of(err: &Error) -> i32 {
+    if let Some(err) = err.downcast_ref::<CliError>() {
+        err.exit_code()
+    } else if err
+        .downcast_ref::<ClientError>()
+        .and_then(ClientError::get_transaction_error)
+        .is_some()
+    {
+        exit_code::TRANSACTION_FAILED
+    } else {
+        exit_code::FAILURE
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        solana_sdk::{
+            hash::Hash,
+            signature::{write_keypair_file, Keypair},
+            system_instruction,
+        },
+    };
+
+    /// Writes `keypair` to a file named after `name` for keypair arguments
+    fn write_keypair(keypair: &Keypair, name: &str) -> String {
+        let path =
+            std::env::temp_dir().join(format!("cli-rust-{}-{}.json", name, std::process::id()));
+        write_keypair_file(keypair, &path).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    /// The config of `cli-rust <args>`, without the `solana` CLI config
+    fn config_of(args: &[&str]) -> Config {
+        let mut command_line = vec!["cli-rust"];
+        command_line.extend_from_slice(args);
+        command_line.extend_from_slice(&["--config", "/dev/null/config.yml"]);
+        let app_matches = app().get_matches_from(command_line);
+        let (sub_command, matches) = app_matches.subcommand();
+        Config::from_matches(sub_command, matches.unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_config_from_matches() {
+        let fee_payer = Keypair::new();
+        let authority = Keypair::new();
+        let fee_payer_path = write_keypair(&fee_payer, "config-fee-payer");
+        let authority_path = write_keypair(&authority, "config-authority");
+        let program_id = Pubkey::new_unique().to_string();
+        let config_of = |args: &[&str]| {
+            let mut command_line = vec!["show", "--fee-payer", &fee_payer_path];
+            command_line.extend_from_slice(&["--program", &program_id]);
+            command_line.extend_from_slice(args);
+            config_of(&command_line)
+        };
+
+        let config = config_of(&[]);
+        assert_eq!(config.fee_payer.pubkey(), fee_payer.pubkey());
+        assert_eq!(config.authority().pubkey(), fee_payer.pubkey());
+        assert_eq!(
+            config.rpc_client.url(),
+            "https://api.mainnet-beta.solana.com"
+        );
+        assert_eq!(
+            config.rpc_client.commitment(),
+            CommitmentConfig::confirmed()
+        );
+
+        let config = config_of(&[
+            "--url",
+            "localhost",
+            "--commitment",
+            "finalized",
+            "--authority",
+            &authority_path,
+        ]);
+        assert_eq!(config.fee_payer.pubkey(), fee_payer.pubkey());
+        assert_eq!(config.authority().pubkey(), authority.pubkey());
+        assert_eq!(config.rpc_client.url(), "http://localhost:8899");
+        assert_eq!(config.websocket_url, "ws://localhost:8900/");
+        assert_eq!(
+            config.rpc_client.commitment(),
+            CommitmentConfig::finalized()
+        );
+
+        let config = config_of(&["--url", "http://example.com:1234"]);
+        assert_eq!(config.rpc_client.url(), "http://example.com:1234");
+
+        std::fs::remove_file(fee_payer_path).unwrap();
+        std::fs::remove_file(authority_path).unwrap();
+    }
+
+    #[test]
+    fn test_sign_only_with_nonce() {
+        let fee_payer = Keypair::new();
+        let fee_payer_path = write_keypair(&fee_payer, "sign-only-fee-payer");
+        let program_id = Pubkey::new_unique();
+        let nonce_account = Pubkey::new_unique();
+        let durable_blockhash = Hash::new_unique();
+        // `--sign-only` with `--blockhash` never reaches the cluster
+        let config = config_of(&[
+            "greet",
+            "--fee-payer",
+            &fee_payer_path,
+            "--program",
+            &program_id.to_string(),
+            "--sign-only",
+            "--dump-transaction-message",
+            "--blockhash",
+            &durable_blockhash.to_string(),
+            "--nonce",
+            &nonce_account.to_string(),
+        ]);
+        let greeting_account_id = Pubkey::new_unique();
+        let transaction = send_instructions(
+            &config,
+            &[init_greeting(
+                program_id,
+                greeting_account_id,
+                1,
+                "hello".to_string(),
+            )],
+            &[],
+            0,
+        )
+        .unwrap();
+        assert!(transaction.signature.is_none());
+        let sign_only = transaction.sign_only.unwrap();
+        assert_eq!(sign_only.blockhash, durable_blockhash.to_string());
+
+        // The nonce is advanced first, by the fee payer as nonce authority
+        let message: Message =
+            bincode::deserialize(&base64::decode(sign_only.message.unwrap()).unwrap()).unwrap();
+        assert_eq!(message.recent_blockhash, durable_blockhash);
+        let advance_nonce_account =
+            system_instruction::advance_nonce_account(&nonce_account, &fee_payer.pubkey());
+        assert_eq!(message.instructions[0].data, advance_nonce_account.data);
+        assert_eq!(
+            message.account_keys[message.instructions[0].program_id_index as usize],
+            advance_nonce_account.program_id
+        );
+        assert_eq!(
+            sign_only.signers,
+            vec![format!(
+                "{}={}",
+                fee_payer.pubkey(),
+                fee_payer.sign_message(&message.serialize())
+            )]
+        );
+
+        std::fs::remove_file(fee_payer_path).unwrap();
+    }
+
+    #[test]
+    fn test_compute_budget_instructions() {
+        let fee_payer = Keypair::new();
+        let fee_payer_path = write_keypair(&fee_payer, "compute-budget-fee-payer");
+        let program_id = Pubkey::new_unique();
+        let program_id_arg = program_id.to_string();
+        let config_of = |args: &[&str]| {
+            let mut command_line = vec!["greet", "--fee-payer", &fee_payer_path];
+            command_line.extend_from_slice(&["--program", &program_id_arg]);
+            command_line.extend_from_slice(args);
+            config_of(&command_line)
+        };
+
+        assert!(config_of(&[]).compute_budget_instructions(1000).is_empty());
+        assert_eq!(
+            config_of(&["--compute-unit-price", "5", "--compute-unit-limit", "2000"])
+                .compute_budget_instructions(1000),
+            [
+                ComputeBudgetInstruction::set_compute_unit_limit(2000),
+                ComputeBudgetInstruction::set_compute_unit_price(5),
+            ]
+        );
+        assert_eq!(
+            config_of(&["--auto-compute-limit"]).compute_budget_instructions(1000),
+            [ComputeBudgetInstruction::set_compute_unit_limit(1000)]
+        );
+
+        // They come first in the transaction
+        let blockhash = Hash::new_unique();
+        let blockhash_arg = blockhash.to_string();
+        let config = config_of(&[
+            "--compute-unit-price",
+            "5",
+            "--sign-only",
+            "--dump-transaction-message",
+            "--blockhash",
+            &blockhash_arg,
+        ]);
+        let greeting = init_greeting(program_id, Pubkey::new_unique(), 1, "hello".to_string());
+        let transaction =
+            send_instructions(&config, std::slice::from_ref(&greeting), &[], 0).unwrap();
+        let message: Message = bincode::deserialize(
+            &base64::decode(transaction.sign_only.unwrap().message.unwrap()).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            message,
+            Message::new_with_blockhash(
+                &[
+                    ComputeBudgetInstruction::set_compute_unit_price(5),
+                    greeting.clone()
+                ],
+                Some(&fee_payer.pubkey()),
+                &blockhash,
+            )
+        );
+
+        // Simulating needs the cluster
+        let config = config_of(&[
+            "--auto-compute-limit",
+            "--sign-only",
+            "--blockhash",
+            &blockhash_arg,
+        ]);
+        let err = send_instructions(&config, &[greeting], &[], 0)
+            .err()
+            .unwrap();
+        assert!(err
+            .to_string()
+            .starts_with("--auto-compute-limit needs to simulate"));
+
+        std::fs::remove_file(fee_payer_path).unwrap();
+    }
+
+    #[test]
+    fn test_auto_compute_unit_limit() {
+        assert_eq!(auto_compute_unit_limit(0), 0);
+        assert_eq!(auto_compute_unit_limit(1000), 1100);
+        assert_eq!(
+            auto_compute_unit_limit(MAX_COMPUTE_UNIT_LIMIT as u64),
+            MAX_COMPUTE_UNIT_LIMIT
+        );
+        assert_eq!(auto_compute_unit_limit(u64::MAX), MAX_COMPUTE_UNIT_LIMIT);
+    }
+}
@@ -0,0 +1,266 @@
+//! `list`: finds the greeting accounts of the program with
+//! `getProgramAccounts`, then sorts, pages and exports them.
+
+use {
+    crate::{
+        output::{CliGreetingAccount, CliGreetingAccounts},
+        Config, Error,
+    },
+    clap::ArgMatches,
+    helloworld::processor::GreetingAccount,
+    solana_account_decoder::UiAccountEncoding,
+    solana_clap_utils::input_parsers::{pubkey_of, value_of, values_of},
+    solana_client::{
+        rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+        rpc_filter::{Memcmp, MemcmpEncodedBytes, RpcFilterType},
+    },
+    solana_program::program_pack::Pack,
+    std::{fs::File, io::Write, path::Path},
+};
+
+/// Filter given to `--memcmp`: `OFFSET:BYTES`, with the bytes in base58
+#[derive(Debug, PartialEq)]
+pub(crate) struct MemcmpArg {
+    offset: usize,
+    bytes: String,
+}
+
+impl std::str::FromStr for MemcmpArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (offset, bytes) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected OFFSET:BYTES, got {}", s))?;
+        let offset = offset
+            .parse()
+            .map_err(|e| format!("invalid offset {}: {}", offset, e))?;
+        bs58::decode(bytes)
+            .into_vec()
+            .map_err(|e| format!("invalid base58 bytes {}: {}", bytes, e))?;
+        Ok(Self {
+            offset,
+            bytes: bytes.to_string(),
+        })
+    }
+}
+
+pub(crate) fn is_memcmp(value: String) -> Result<(), String> {
+    value.parse::<MemcmpArg>().map(|_| ())
+}
+
+pub(crate) fn is_export_path(value: String) -> Result<(), String> {
+    match ExportFormat::of(Path::new(&value)) {
+        Some(_) => Ok(()),
+        None => Err(format!("{} must end in .csv or .json", value)),
+    }
+}
+
+enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    fn of(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "csv" => Some(Self::Csv),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn process_list(
+    config: &Config,
+    matches: &ArgMatches,
+) -> Result<CliGreetingAccounts, Error> {
+    let mut memcmps = vec![];
+    let with_authority = pubkey_of(matches, "with_authority");
+    if let Some(authority) = with_authority {
+        memcmps.push(RpcFilterType::Memcmp(Memcmp {
+            offset: GreetingAccount::AUTHORITY_OFFSET,
+            bytes: MemcmpEncodedBytes::Base58(authority.to_string()),
+            encoding: None,
+        }));
+    }
+    for memcmp in values_of::<MemcmpArg>(matches, "memcmp").unwrap_or_default() {
+        memcmps.push(RpcFilterType::Memcmp(Memcmp {
+            offset: memcmp.offset,
+            bytes: MemcmpEncodedBytes::Base58(memcmp.bytes),
+            encoding: None,
+        }));
+    }
+
+    // Legacy accounts have no authority to match
+    let mut data_sizes = vec![GreetingAccount::LEN];
+    if with_authority.is_none() {
+        data_sizes.push(GreetingAccount::LEGACY_LEN);
+    }
+    let mut accounts = vec![];
+    for data_size in data_sizes {
+        let mut filters = vec![RpcFilterType::DataSize(data_size as u64)];
+        filters.extend(memcmps.iter().cloned());
+        accounts.extend(config.rpc_client.get_program_accounts_with_config(
+            &config.program_id,
+            RpcProgramAccountsConfig {
+                filters: Some(filters),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    ..RpcAccountInfoConfig::default()
+                },
+                ..RpcProgramAccountsConfig::default()
+            },
+        )?);
+    }
+
+    // Accounts of the right size that don't decode aren't greeting accounts
+    let accounts = accounts
+        .into_iter()
+        .filter_map(|(address, account)| {
+            let greeting_account = GreetingAccount::unpack_versioned(&account.data).ok()?;
+            Some(CliGreetingAccount::new(&address, greeting_account))
+        })
+        .collect();
+    let cli_greeting_accounts = sort_and_page(
+        accounts,
+        matches.value_of("order") == Some("asc"),
+        value_of(matches, "offset").unwrap_or(0),
+        value_of(matches, "limit"),
+    );
+
+    if let Some(path) = matches.value_of("export") {
+        let mut file = File::create(path)?;
+        match ExportFormat::of(Path::new(path)) {
+            Some(ExportFormat::Csv) => write_csv(&mut file, &cli_greeting_accounts.accounts)?,
+            _ => serde_json::to_writer_pretty(&mut file, &cli_greeting_accounts)?,
+        }
+    }
+    Ok(cli_greeting_accounts)
+}
+
+/// Orders the accounts by counter, then address so that pages are stable,
+/// and keeps the `limit` accounts starting at `offset`
+fn sort_and_page(
+    mut accounts: Vec<CliGreetingAccount>,
+    ascending: bool,
+    offset: usize,
+    limit: Option<usize>,
+) -> CliGreetingAccounts {
+    accounts.sort_by(|a, b| {
+        let by_counter = a.account.counter.cmp(&b.account.counter);
+        let by_counter = if ascending {
+            by_counter
+        } else {
+            by_counter.reverse()
+        };
+        by_counter.then_with(|| a.address.cmp(&b.address))
+    });
+    let total = accounts.len();
+    let accounts = accounts
+        .into_iter()
+        .skip(offset)
+        .take(limit.unwrap_or(usize::MAX))
+        .collect();
+    CliGreetingAccounts {
+        accounts,
+        offset,
+        total,
+    }
+}
+
+/// Writes the accounts with the columns of the JSON schema. Every field is a
+/// number, bool or base58 string, so nothing needs quoting.
+fn write_csv(writer: &mut dyn Write, accounts: &[CliGreetingAccount]) -> Result<(), Error> {
+    writeln!(
+        writer,
+        "address,counter,counterTimes2,authority,flags,topLevelOnly,legacy"
+    )?;
+    for greeting_account in accounts {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{}",
+            greeting_account.address,
+            greeting_account.account.counter,
+            greeting_account.account.counter_times_2,
+            greeting_account.account.authority,
+            greeting_account.account.flags,
+            greeting_account.top_level_only,
+            greeting_account.legacy,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, solana_program::pubkey::Pubkey};
+
+    fn greeting_account(address: u8, counter: u32) -> CliGreetingAccount {
+        CliGreetingAccount::new(
+            &Pubkey::new_from_array([address; 32]),
+            GreetingAccount {
+                counter,
+                counter_times_2: counter * 2,
+                authority: Pubkey::new_from_array([9; 32]),
+                flags: 0,
+            },
+        )
+    }
+
+    #[test]
+    fn test_sort_and_page() {
+        let accounts = || {
+            vec![
+                greeting_account(1, 5),
+                greeting_account(2, 7),
+                greeting_account(3, 5),
+                greeting_account(4, 1),
+            ]
+        };
+        let counters = |page: &CliGreetingAccounts| -> Vec<u32> {
+            page.accounts.iter().map(|a| a.account.counter).collect()
+        };
+
+        let page = sort_and_page(accounts(), false, 0, None);
+        assert_eq!(counters(&page), [7, 5, 5, 1]);
+        assert_eq!(page.total, 4);
+
+        let page = sort_and_page(accounts(), true, 1, Some(2));
+        assert_eq!(counters(&page), [5, 5]);
+        assert_eq!(page.accounts[0].address, accounts()[0].address);
+        assert_eq!(page.total, 4);
+
+        let page = sort_and_page(accounts(), true, 10, Some(2));
+        assert!(page.accounts.is_empty());
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let mut csv = vec![];
+        write_csv(&mut csv, &[greeting_account(1, 3)]).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            format!(
+                "address,counter,counterTimes2,authority,flags,topLevelOnly,legacy\n\
+                {},3,6,{},0,false,false\n",
+                Pubkey::new_from_array([1; 32]),
+                Pubkey::new_from_array([9; 32]),
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_memcmp() {
+        assert_eq!(
+            "8:2j".parse(),
+            Ok(MemcmpArg {
+                offset: 8,
+                bytes: "2j".to_string()
+            })
+        );
+        assert!("8".parse::<MemcmpArg>().is_err());
+        assert!("x:2j".parse::<MemcmpArg>().is_err());
+        assert!("8:0OIl".parse::<MemcmpArg>().is_err());
+    }
+}
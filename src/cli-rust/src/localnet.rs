@@ -0,0 +1,144 @@
+//! `localnet up`: a local validator with the program deployed, a funded fee
+//! payer and a greeting account, recorded in the project config.
+
+use {
+    crate::{
+        create_greeting_account,
+        deploy::{deploy_or_upgrade, read_program, read_program_keypair},
+        get_greeting_account,
+        output::{CliGreetingAccount, CliLocalnet},
+        println_display,
+        project_config::ProjectConfig,
+        CliError, Config, Error,
+    },
+    clap::ArgMatches,
+    helloworld_client::address::greeting_account_address,
+    solana_clap_utils::input_parsers::value_of,
+    solana_program::native_token::{lamports_to_sol, sol_to_lamports},
+    solana_sdk::signature::Signer,
+    std::{
+        process::{Command, Stdio},
+        thread::sleep,
+        time::{Duration, Instant},
+    },
+};
+
+/// The RPC URL `solana-test-validator` listens on by default
+pub(crate) const LOCALNET_URL: &str = "http://127.0.0.1:8899";
+const VALIDATOR_STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub(crate) fn process_localnet(
+    config: &Config,
+    matches: &ArgMatches,
+) -> Result<CliLocalnet, Error> {
+    let validator = if config.rpc_client.get_health().is_ok() {
+        println_display(config, "Found a running validator".to_string());
+        "running"
+    } else {
+        start_validator(config, matches.value_of("ledger").unwrap())?;
+        "started"
+    };
+
+    let fee_payer = config.fee_payer.pubkey();
+    let airdrop = sol_to_lamports(value_of(matches, "airdrop").unwrap());
+    let mut balance = config.rpc_client.get_balance(&fee_payer)?;
+    if balance < airdrop {
+        println_display(
+            config,
+            format!(
+                "Airdropping {} SOL to {}",
+                lamports_to_sol(airdrop),
+                fee_payer
+            ),
+        );
+        let signature = config.rpc_client.request_airdrop(&fee_payer, airdrop)?;
+        config
+            .rpc_client
+            .poll_for_signature_with_commitment(&signature, config.rpc_client.commitment())?;
+        balance = config.rpc_client.get_balance(&fee_payer)?;
+    }
+
+    let program_keypair = read_program_keypair(matches)?;
+    let program_id = program_keypair.pubkey();
+    if program_id != config.program_id {
+        return Err(format!(
+            "The program keypair is for {}, but the program ID is {}",
+            program_id, config.program_id
+        )
+        .into());
+    }
+    let program = deploy_or_upgrade(config, &program_keypair, &read_program(matches)?)?;
+
+    let greeting_account_id =
+        greeting_account_address(&fee_payer, &config.greeting_account_seed, &program_id)?;
+    let greeting_account = match get_greeting_account(config, &greeting_account_id) {
+        Ok(greeting_account) => greeting_account,
+        Err(err) if matches!(err.downcast_ref(), Some(CliError::AccountNotFound(_))) => {
+            create_greeting_account(config, &config.greeting_account_seed, 0)?;
+            get_greeting_account(config, &greeting_account_id)?
+        }
+        Err(err) => return Err(err),
+    };
+
+    // Into the selected profile, so that selecting it again finds this setup
+    let mut project_config = ProjectConfig::load(&config.project_config_path)?.unwrap_or_default();
+    let profile = project_config.profile_mut(config.profile.as_deref());
+    profile.json_rpc_url = Some(config.rpc_client.url());
+    profile.commitment = Some(config.rpc_client.commitment().commitment.to_string());
+    profile.fee_payer = Some(config.fee_payer_path.clone());
+    profile.program_id = Some(program_id.to_string());
+    profile.greeting_account_seed = Some(config.greeting_account_seed.clone());
+    project_config.save(&config.project_config_path)?;
+
+    Ok(CliLocalnet {
+        json_rpc_url: config.rpc_client.url(),
+        validator: validator.to_string(),
+        fee_payer: fee_payer.to_string(),
+        balance: lamports_to_sol(balance),
+        program,
+        greeting_account: CliGreetingAccount::new(&greeting_account_id, greeting_account),
+        project_config: config.project_config_path.clone(),
+    })
+}
+
+/// Starts `solana-test-validator` in the background and waits until it's
+/// healthy. It keeps running after this command exits.
+fn start_validator(config: &Config, ledger: &str) -> Result<(), Error> {
+    let url = config.rpc_client.url();
+    if url != LOCALNET_URL {
+        return Err(format!("No validator is running at {}", url).into());
+    }
+    println_display(
+        config,
+        format!("Starting solana-test-validator with ledger {}", ledger),
+    );
+    let mut validator = Command::new("solana-test-validator")
+        .args(["--quiet", "--ledger", ledger])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| {
+            CliError::ExternalCommandFailed("solana-test-validator".to_string(), e.to_string())
+        })?;
+
+    let started = Instant::now();
+    while config.rpc_client.get_health().is_err() {
+        if let Some(status) = validator.try_wait()? {
+            return Err(CliError::ExternalCommandFailed(
+                "solana-test-validator".to_string(),
+                status.to_string(),
+            )
+            .into());
+        }
+        if started.elapsed() > VALIDATOR_STARTUP_TIMEOUT {
+            return Err(format!(
+                "solana-test-validator isn't healthy after {}s",
+                VALIDATOR_STARTUP_TIMEOUT.as_secs()
+            )
+            .into());
+        }
+        sleep(Duration::from_millis(500));
+    }
+    Ok(())
+}
@@ -0,0 +1,1005 @@
+//! Results of the CLI commands, which `process_command` returns and prints.
+//!
+//! With `--output json` these are serialized as-is, so the field names are a
+//! stable schema: add fields, don't rename or remove them.
+
+use {
+    helloworld::{
+        instruction::GreetingInstruction, processor::GreetingAccount, validation::CharacterPolicy,
+    },
+    serde::{Deserialize, Serialize},
+    solana_cli_output::{
+        display::{unix_timestamp_to_string, writeln_name_value},
+        CliSignOnlyData, OutputFormat, QuietDisplay, VerboseDisplay,
+    },
+    solana_sdk::{
+        bpf_loader_upgradeable,
+        clock::{Slot, UnixTimestamp},
+        pubkey::Pubkey,
+    },
+    std::{collections::BTreeMap, convert::TryFrom, fmt},
+};
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliGreetingAccount {
+    pub address: String,
+    #[serde(flatten)]
+    pub account: GreetingAccount,
+    pub top_level_only: bool,
+    pub greeting_policy: String,
+    /// Still in the 8-byte layout from before authorities, until `migrate`
+    pub legacy: bool,
+}
+
+impl CliGreetingAccount {
+    pub fn new(address: &Pubkey, account: GreetingAccount) -> Self {
+        let greeting_policy = match GreetingAccount::character_policy(account.flags) {
+            Some(CharacterPolicy::Any) => "any",
+            Some(CharacterPolicy::Ascii) => "ascii",
+            Some(CharacterPolicy::Printable) => "printable",
+            None => "invalid",
+        };
+        Self {
+            address: address.to_string(),
+            top_level_only: account.top_level_only(),
+            greeting_policy: greeting_policy.to_string(),
+            // Initializing sets an authority, legacy accounts never had one
+            legacy: account.authority == Pubkey::default(),
+            account,
+        }
+    }
+}
+
+impl QuietDisplay for CliGreetingAccount {}
+impl VerboseDisplay for CliGreetingAccount {}
+
+impl fmt::Display for CliGreetingAccount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln_name_value(f, "Greeting account:", &self.address)?;
+        if self.legacy {
+            writeln_name_value(f, "Layout:", "legacy, run `migrate` to upgrade it")?;
+        }
+        if self.account.authority == Pubkey::default() {
+            writeln_name_value(f, "Authority:", "none")?;
+        } else {
+            writeln_name_value(f, "Authority:", &self.account.authority.to_string())?;
+        }
+        writeln_name_value(f, "Top-level only:", &self.top_level_only.to_string())?;
+        writeln_name_value(f, "Greeting policy:", &self.greeting_policy)?;
+        writeln_name_value(f, "Counter:", &self.account.counter.to_string())?;
+        writeln_name_value(
+            f,
+            "Counter times 2:",
+            &self.account.counter_times_2.to_string(),
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliGreetingAccounts {
+    pub accounts: Vec<CliGreetingAccount>,
+    /// Position of the first account of this page among all matching accounts
+    pub offset: usize,
+    /// Number of matching accounts, across all pages
+    pub total: usize,
+}
+
+impl QuietDisplay for CliGreetingAccounts {}
+impl VerboseDisplay for CliGreetingAccounts {}
+
+impl fmt::Display for CliGreetingAccounts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for greeting_account in &self.accounts {
+            writeln!(
+                f,
+                "{} {}{}",
+                greeting_account.address,
+                greeting_account.account.counter,
+                if greeting_account.legacy {
+                    " (legacy)"
+                } else {
+                    ""
+                }
+            )?;
+        }
+        if self.accounts.len() == self.total {
+            writeln!(f, "{} greeting account(s)", self.total)
+        } else {
+            writeln!(
+                f,
+                "Greeting accounts {}-{} of {}",
+                self.offset + 1,
+                self.offset + self.accounts.len(),
+                self.total
+            )
+        }
+    }
+}
+
+/// A change of a greeting account seen by `watch`
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliGreetingUpdate {
+    pub address: String,
+    pub slot: Slot,
+    pub counter: u32,
+    pub counter_times_2: u32,
+    /// Change since the previous update, `None` for the initial state
+    pub delta: Option<i64>,
+}
+
+impl QuietDisplay for CliGreetingUpdate {}
+impl VerboseDisplay for CliGreetingUpdate {}
+
+impl fmt::Display for CliGreetingUpdate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.delta {
+            Some(delta) => write!(
+                f,
+                "Slot {}: counter {} ({:+})",
+                self.slot, self.counter, delta
+            ),
+            None => write!(f, "Slot {}: counter {}", self.slot, self.counter),
+        }
+    }
+}
+
+/// A greeting instruction decoded from a transaction, `Unknown` if its data
+/// isn't a `GreetingInstruction`
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliGreetingInstruction {
+    pub instruction: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_greetings: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub greeting_string: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub greeting_nonce: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authority: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flags: Option<u8>,
+}
+
+impl CliGreetingInstruction {
+    pub fn new(instruction: Option<GreetingInstruction>) -> Self {
+        let named = |name: &str| Self {
+            instruction: name.to_string(),
+            num_greetings: None,
+            greeting_string: None,
+            greeting_nonce: None,
+            authority: None,
+            flags: None,
+        };
+        match instruction {
+            Some(GreetingInstruction::InitGreeting(args)) => Self {
+                num_greetings: Some(args.num_greetings),
+                greeting_string: Some(args.greeting_string),
+                greeting_nonce: args.nonce,
+                ..named("InitGreeting")
+            },
+            Some(GreetingInstruction::InitGreeting2(args)) => Self {
+                num_greetings: Some(args.num_greetings),
+                greeting_string: Some(args.greeting_string),
+                greeting_nonce: args.nonce,
+                ..named("InitGreeting2")
+            },
+            Some(GreetingInstruction::SignedGreeting(args)) => Self {
+                num_greetings: Some(args.num_greetings),
+                greeting_nonce: Some(args.nonce),
+                ..named("SignedGreeting")
+            },
+            Some(GreetingInstruction::Initialize(args)) => Self {
+                authority: Some(args.authority.to_string()),
+                flags: Some(args.flags),
+                ..named("Initialize")
+            },
+            Some(GreetingInstruction::Migrate(args)) => Self {
+                authority: Some(args.authority.to_string()),
+                flags: Some(args.flags),
+                ..named("Migrate")
+            },
+            Some(GreetingInstruction::SetFlags(flags)) => Self {
+                flags: Some(flags),
+                ..named("SetFlags")
+            },
+            Some(GreetingInstruction::Close) => named("Close"),
+            None => named("Unknown"),
+        }
+    }
+}
+
+impl fmt::Display for CliGreetingInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.instruction)?;
+        if let Some(num_greetings) = self.num_greetings {
+            write!(f, " {}", num_greetings)?;
+        }
+        if let Some(greeting_string) = &self.greeting_string {
+            write!(f, " {:?}", greeting_string)?;
+        }
+        if let Some(greeting_nonce) = self.greeting_nonce {
+            write!(f, ", nonce {}", greeting_nonce)?;
+        }
+        if let Some(authority) = &self.authority {
+            write!(f, ", authority {}", authority)?;
+        }
+        if let Some(flags) = self.flags {
+            write!(f, ", flags {:#04x}", flags)?;
+        }
+        Ok(())
+    }
+}
+
+/// A transaction that touched a greeting account, as listed by `history`
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliHistoryEntry {
+    pub signature: String,
+    pub slot: Slot,
+    pub block_time: Option<UnixTimestamp>,
+    pub fee_payer: String,
+    /// The top-level instructions of the program on the greeting account
+    pub instructions: Vec<CliGreetingInstruction>,
+    /// The counter after the transaction, if it greeted the account
+    pub counter: Option<u32>,
+    pub error: Option<String>,
+    /// The program's log messages for `instructions`
+    pub logs: Vec<String>,
+}
+
+impl fmt::Display for CliHistoryEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Slot {}", self.slot)?;
+        if let Some(block_time) = self.block_time {
+            write!(f, ", {}", unix_timestamp_to_string(block_time))?;
+        }
+        writeln!(f, ", paid by {}", self.fee_payer)?;
+        writeln!(f, "  Signature: {}", self.signature)?;
+        for instruction in &self.instructions {
+            writeln!(f, "  {}", instruction)?;
+        }
+        if let Some(counter) = self.counter {
+            writeln!(f, "  Counter: {}", counter)?;
+        }
+        if let Some(error) = &self.error {
+            writeln!(f, "  Failed: {}", error)?;
+        }
+        for log in &self.logs {
+            writeln!(f, "  > {}", log)?;
+        }
+        Ok(())
+    }
+}
+
+/// The timeline of a greeting account, oldest transaction first
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliHistory {
+    pub greeting_account: String,
+    pub entries: Vec<CliHistoryEntry>,
+    /// Pass as `--before` for the previous page, if there may be one
+    pub before: Option<String>,
+}
+
+impl QuietDisplay for CliHistory {}
+impl VerboseDisplay for CliHistory {}
+
+impl fmt::Display for CliHistory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln_name_value(f, "Greeting account:", &self.greeting_account)?;
+        for entry in &self.entries {
+            writeln!(f)?;
+            write!(f, "{}", entry)?;
+        }
+        if let Some(before) = &self.before {
+            writeln!(f)?;
+            writeln!(f, "For older transactions, pass --before {}", before)?;
+        }
+        Ok(())
+    }
+}
+
+/// One Borsh field found by `inspect`
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliDecodedField {
+    pub offset: usize,
+    pub length: usize,
+    pub name: String,
+    pub value: String,
+}
+
+/// Instruction data or account data decoded by `inspect`, up to the first
+/// byte that doesn't fit
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliInspection {
+    /// `instruction` or `account`
+    pub kind: String,
+    /// Encoding of instruction data, or where account data came from
+    pub source: String,
+    pub length: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    pub fields: Vec<CliDecodedField>,
+    pub error: Option<String>,
+    /// Byte at which decoding failed
+    pub error_offset: Option<usize>,
+}
+
+impl QuietDisplay for CliInspection {}
+impl VerboseDisplay for CliInspection {}
+
+impl fmt::Display for CliInspection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln_name_value(
+            f,
+            "Data:",
+            &format!("{} bytes of {} ({})", self.length, self.kind, self.source),
+        )?;
+        if let Some(owner) = &self.owner {
+            writeln_name_value(f, "Owner:", owner)?;
+        }
+        if let Some(variant) = &self.variant {
+            writeln_name_value(f, "Variant:", variant)?;
+        }
+        for field in &self.fields {
+            writeln!(
+                f,
+                "  {:>4}..{:<4} {:<16} {}",
+                field.offset,
+                field.offset + field.length,
+                field.name,
+                field.value
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of a transaction simulated with `--dry-run`
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliSimulation {
+    pub error: Option<String>,
+    pub units_consumed: Option<u64>,
+    pub logs: Vec<String>,
+    /// The `GreetingError` variant the program failed with
+    pub program_error: Option<String>,
+    pub invocations: Vec<CliInvocation>,
+    /// The greeting accounts as the transaction would leave them
+    pub greeting_accounts: Vec<CliGreetingAccount>,
+    /// In SOL
+    pub fee: f64,
+}
+
+impl QuietDisplay for CliSimulation {}
+impl VerboseDisplay for CliSimulation {}
+
+impl fmt::Display for CliSimulation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.error {
+            Some(error) => writeln_name_value(f, "Simulation:", &format!("failed, {}", error))?,
+            None => writeln_name_value(f, "Simulation:", "success")?,
+        }
+        if let Some(program_error) = &self.program_error {
+            writeln_name_value(f, "Program error:", program_error)?;
+        }
+        if let Some(units_consumed) = self.units_consumed {
+            writeln_name_value(f, "Compute units consumed:", &units_consumed.to_string())?;
+        }
+        writeln_name_value(f, "Fee:", &format!("{} SOL", self.fee))?;
+        writeln!(f, "Invocations:")?;
+        for invocation in &self.invocations {
+            write!(f, "{}", invocation)?;
+        }
+        for greeting_account in &self.greeting_accounts {
+            write!(f, "{}", greeting_account)?;
+        }
+        Ok(())
+    }
+}
+
+/// A program invocation in the logs of a simulation, nested ones included
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliInvocation {
+    pub program_id: String,
+    /// 1 for a top-level instruction, 2 for a program it invokes, and so on
+    pub depth: usize,
+    pub units_consumed: Option<u64>,
+    pub error: Option<String>,
+    pub logs: Vec<String>,
+}
+
+impl fmt::Display for CliInvocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let indent = "  ".repeat(self.depth);
+        write!(f, "{}{}", indent, self.program_id)?;
+        if let Some(units_consumed) = self.units_consumed {
+            write!(f, ", {} compute units", units_consumed)?;
+        }
+        match &self.error {
+            Some(error) => writeln!(f, ", failed: {}", error)?,
+            None => writeln!(f)?,
+        }
+        for log in &self.logs {
+            writeln!(f, "{}  {}", indent, log)?;
+        }
+        Ok(())
+    }
+}
+
+/// A sent, simulated or offline signed transaction, with the greeting account
+/// it changed
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CliTransaction {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub simulation: Option<CliSimulation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub greeting_account: Option<CliGreetingAccount>,
+    /// Blockhash and signatures of a transaction signed with `--sign-only`
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub sign_only: Option<CliSignOnlyData>,
+}
+
+impl QuietDisplay for CliTransaction {}
+impl VerboseDisplay for CliTransaction {}
+
+impl fmt::Display for CliTransaction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(signature) = &self.signature {
+            writeln_name_value(f, "Signature:", signature)?;
+        }
+        if let Some(simulation) = &self.simulation {
+            write!(f, "{}", simulation)?;
+        }
+        if let Some(greeting_account) = &self.greeting_account {
+            write!(f, "{}", greeting_account)?;
+        }
+        if let Some(sign_only) = &self.sign_only {
+            write!(f, "{}", sign_only)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliSignedGreeting {
+    pub greeter: String,
+    pub greeting_account: String,
+    pub greetings: u32,
+    pub nonce: u64,
+    pub signature: String,
+}
+
+impl QuietDisplay for CliSignedGreeting {}
+impl VerboseDisplay for CliSignedGreeting {}
+
+impl fmt::Display for CliSignedGreeting {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln_name_value(f, "Greeter:", &self.greeter)?;
+        writeln_name_value(f, "Greeting account:", &self.greeting_account)?;
+        writeln_name_value(f, "Greetings:", &self.greetings.to_string())?;
+        writeln_name_value(f, "Nonce:", &self.nonce.to_string())?;
+        writeln_name_value(f, "Signature:", &self.signature)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliConfig {
+    pub config_file: Option<String>,
+    pub project_config: Option<String>,
+    pub profile: Option<String>,
+    pub json_rpc_url: String,
+    pub websocket_url: String,
+    pub commitment: String,
+    pub fee_payer: String,
+    pub authority: String,
+    pub program_id: String,
+    pub greeting_account_seed: String,
+}
+
+impl QuietDisplay for CliConfig {}
+impl VerboseDisplay for CliConfig {}
+
+impl fmt::Display for CliConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln_name_value(
+            f,
+            "Config File:",
+            self.config_file.as_deref().unwrap_or("none"),
+        )?;
+        writeln_name_value(
+            f,
+            "Project Config:",
+            self.project_config.as_deref().unwrap_or("none"),
+        )?;
+        writeln_name_value(f, "Profile:", self.profile.as_deref().unwrap_or("none"))?;
+        writeln_name_value(f, "RPC URL:", &self.json_rpc_url)?;
+        writeln_name_value(f, "WebSocket URL:", &self.websocket_url)?;
+        writeln_name_value(f, "Commitment:", &self.commitment)?;
+        writeln_name_value(f, "Fee Payer:", &self.fee_payer)?;
+        writeln_name_value(f, "Authority:", &self.authority)?;
+        writeln_name_value(f, "Program Id:", &self.program_id)?;
+        writeln_name_value(f, "Greeting Account Seed:", &self.greeting_account_seed)
+    }
+}
+
+/// A program deployed, upgraded or handed to a new upgrade authority
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliProgram {
+    pub program_id: String,
+    pub programdata_address: String,
+    /// `None` once the program is final
+    pub upgrade_authority: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+impl CliProgram {
+    pub fn new(program_id: &Pubkey, upgrade_authority: Option<&Pubkey>) -> Self {
+        let (programdata_address, _) =
+            Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+        Self {
+            program_id: program_id.to_string(),
+            programdata_address: programdata_address.to_string(),
+            upgrade_authority: upgrade_authority.map(ToString::to_string),
+            signature: None,
+        }
+    }
+}
+
+impl QuietDisplay for CliProgram {}
+impl VerboseDisplay for CliProgram {}
+
+impl fmt::Display for CliProgram {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln_name_value(f, "Program Id:", &self.program_id)?;
+        writeln_name_value(f, "ProgramData Address:", &self.programdata_address)?;
+        writeln_name_value(
+            f,
+            "Upgrade Authority:",
+            self.upgrade_authority.as_deref().unwrap_or("none"),
+        )?;
+        if let Some(signature) = &self.signature {
+            writeln_name_value(f, "Signature:", signature)?;
+        }
+        Ok(())
+    }
+}
+
+/// A deployed program compared with a local build by `verify`
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliVerifiedProgram {
+    #[serde(flatten)]
+    pub program: CliProgram,
+    pub last_deploy_slot: Slot,
+    /// SHA-256 of the deployed program, without padding
+    pub deployed_trimmed_hash: String,
+    pub deployed_trimmed_len: usize,
+    /// SHA-256 of the local build, without trailing zeros
+    pub local_trimmed_hash: String,
+    pub local_trimmed_len: usize,
+    /// SHA-256 of the local build as is, what `sha256sum` shows
+    pub local_file_hash: String,
+    pub local_file_len: usize,
+    pub matches: bool,
+}
+
+impl QuietDisplay for CliVerifiedProgram {}
+impl VerboseDisplay for CliVerifiedProgram {}
+
+impl fmt::Display for CliVerifiedProgram {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.program)?;
+        writeln_name_value(f, "Last Deploy Slot:", &self.last_deploy_slot.to_string())?;
+        writeln_name_value(
+            f,
+            "Deployed Hash (trimmed):",
+            &format!(
+                "{} ({} bytes)",
+                self.deployed_trimmed_hash, self.deployed_trimmed_len
+            ),
+        )?;
+        writeln_name_value(
+            f,
+            "Local Hash (trimmed):",
+            &format!(
+                "{} ({} bytes)",
+                self.local_trimmed_hash, self.local_trimmed_len
+            ),
+        )?;
+        writeln_name_value(
+            f,
+            "Local File Hash:",
+            &format!("{} ({} bytes)", self.local_file_hash, self.local_file_len),
+        )?;
+        writeln_name_value(
+            f,
+            "Status:",
+            if self.matches { "match" } else { "MISMATCH" },
+        )
+    }
+}
+
+/// Confirmation latencies of the transactions `bench` sent
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliLatency {
+    pub p50_ms: u64,
+    pub p90_ms: u64,
+    pub p99_ms: u64,
+    pub max_ms: u64,
+}
+
+/// The results of a `bench` run
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliBench {
+    pub transactions: usize,
+    pub accounts: usize,
+    pub payers: usize,
+    pub concurrency: usize,
+    pub confirmed: usize,
+    pub failed: usize,
+    pub elapsed_secs: f64,
+    /// Confirmed transactions per second
+    pub tps: f64,
+    pub latency: CliLatency,
+    /// Failed transactions by error
+    pub failures: BTreeMap<String, usize>,
+    /// In SOL
+    pub total_fees: f64,
+}
+
+impl QuietDisplay for CliBench {}
+impl VerboseDisplay for CliBench {}
+
+impl fmt::Display for CliBench {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln_name_value(f, "Transactions:", &self.transactions.to_string())?;
+        writeln_name_value(f, "Greeting Accounts:", &self.accounts.to_string())?;
+        writeln_name_value(f, "Payers:", &self.payers.to_string())?;
+        writeln_name_value(f, "Concurrency:", &self.concurrency.to_string())?;
+        writeln_name_value(f, "Confirmed:", &self.confirmed.to_string())?;
+        writeln_name_value(f, "Failed:", &self.failed.to_string())?;
+        for (error, count) in &self.failures {
+            writeln_name_value(f, &format!("  {}:", error), &count.to_string())?;
+        }
+        writeln_name_value(f, "Elapsed:", &format!("{:.2}s", self.elapsed_secs))?;
+        writeln_name_value(f, "TPS:", &format!("{:.2}", self.tps))?;
+        writeln_name_value(
+            f,
+            "Latency:",
+            &format!(
+                "p50 {}ms, p90 {}ms, p99 {}ms, max {}ms",
+                self.latency.p50_ms, self.latency.p90_ms, self.latency.p99_ms, self.latency.max_ms
+            ),
+        )?;
+        writeln_name_value(f, "Total Fees:", &format!("{} SOL", self.total_fees))
+    }
+}
+
+/// The rent-exempt minimum of an account the program uses
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliAccountRent {
+    pub name: String,
+    pub size: usize,
+    /// In SOL
+    pub rent: f64,
+}
+
+/// What a transaction of one kind costs
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliInstructionCost {
+    pub instruction: String,
+    pub signatures: u8,
+    /// In SOL
+    pub fee: f64,
+    /// Deposited into rent-exempt accounts, negative if refunded, in SOL
+    pub rent: f64,
+}
+
+/// One line of a batch estimate
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliBatchLine {
+    pub instruction: String,
+    pub count: u64,
+    /// In SOL
+    pub fees: f64,
+    /// In SOL
+    pub rent: f64,
+}
+
+/// The projected cost of a planned batch of transactions
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliBatchEstimate {
+    pub file: String,
+    pub lines: Vec<CliBatchLine>,
+    pub transactions: u64,
+    /// In SOL
+    pub fees: f64,
+    /// In SOL
+    pub rent: f64,
+    /// In SOL
+    pub total: f64,
+    /// In SOL
+    pub fee_payer_balance: f64,
+    /// Whether the fee payer can afford the batch
+    pub sufficient: bool,
+}
+
+/// Rent and fees, as reported by `estimate`
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliEstimate {
+    pub accounts: Vec<CliAccountRent>,
+    pub instructions: Vec<CliInstructionCost>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub batch: Option<CliBatchEstimate>,
+}
+
+impl QuietDisplay for CliEstimate {}
+impl VerboseDisplay for CliEstimate {}
+
+impl fmt::Display for CliEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Rent-exempt Minimums:")?;
+        for account in &self.accounts {
+            writeln_name_value(
+                f,
+                &format!("  {} ({} bytes):", account.name, account.size),
+                &format!("{} SOL", account.rent),
+            )?;
+        }
+        writeln!(f, "Transaction Costs:")?;
+        for instruction in &self.instructions {
+            writeln_name_value(
+                f,
+                &format!("  {}:", instruction.instruction),
+                &format!(
+                    "{} SOL fee ({} signature(s)), {} SOL rent",
+                    instruction.fee, instruction.signatures, instruction.rent
+                ),
+            )?;
+        }
+        if let Some(batch) = &self.batch {
+            writeln!(f, "Batch {}:", batch.file)?;
+            for line in &batch.lines {
+                writeln_name_value(
+                    f,
+                    &format!("  {} x {}:", line.instruction, line.count),
+                    &format!("{} SOL fees, {} SOL rent", line.fees, line.rent),
+                )?;
+            }
+            writeln_name_value(f, "Transactions:", &batch.transactions.to_string())?;
+            writeln_name_value(f, "Fees:", &format!("{} SOL", batch.fees))?;
+            writeln_name_value(f, "Rent:", &format!("{} SOL", batch.rent))?;
+            writeln_name_value(f, "Total:", &format!("{} SOL", batch.total))?;
+            writeln_name_value(
+                f,
+                "Fee Payer Balance:",
+                &format!(
+                    "{} SOL ({})",
+                    batch.fee_payer_balance,
+                    if batch.sufficient {
+                        "sufficient"
+                    } else {
+                        "insufficient"
+                    }
+                ),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A local validator ready to greet on, as set up by `localnet up`
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliLocalnet {
+    pub json_rpc_url: String,
+    /// `running` if a validator was already up, `started` otherwise
+    pub validator: String,
+    pub fee_payer: String,
+    pub balance: f64,
+    pub program: CliProgram,
+    pub greeting_account: CliGreetingAccount,
+    pub project_config: String,
+}
+
+impl QuietDisplay for CliLocalnet {}
+impl VerboseDisplay for CliLocalnet {}
+
+impl fmt::Display for CliLocalnet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln_name_value(f, "RPC URL:", &self.json_rpc_url)?;
+        writeln_name_value(f, "Validator:", &self.validator)?;
+        writeln_name_value(f, "Fee Payer:", &self.fee_payer)?;
+        writeln_name_value(f, "Balance:", &format!("{} SOL", self.balance))?;
+        write!(f, "{}", self.program)?;
+        write!(f, "{}", self.greeting_account)?;
+        writeln_name_value(f, "Project Config:", &self.project_config)
+    }
+}
+
+/// The IDL generated by `idl`, and where it was written
+#[derive(Debug)]
+pub struct CliIdl {
+    /// The IDL as JSON
+    pub idl: String,
+    pub json_path: Option<String>,
+    pub ts_path: Option<String>,
+}
+
+impl fmt::Display for CliIdl {
+    /// The IDL, unless it was written to a file
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.json_path.is_none() && self.ts_path.is_none() {
+            write!(f, "{}", self.idl)?;
+        }
+        Ok(())
+    }
+}
+
+/// What a command returns
+#[derive(Debug)]
+pub enum CommandOutput {
+    Transaction(CliTransaction),
+    GreetingAccount(CliGreetingAccount),
+    GreetingAccounts(CliGreetingAccounts),
+    /// Printed by `watch` as they arrive
+    GreetingUpdates(Vec<CliGreetingUpdate>),
+    History(CliHistory),
+    Inspection(CliInspection),
+    SignedGreeting(CliSignedGreeting),
+    Config(CliConfig),
+    Program(CliProgram),
+    VerifiedProgram(CliVerifiedProgram),
+    Bench(CliBench),
+    Estimate(CliEstimate),
+    Localnet(CliLocalnet),
+    /// Printed as is, the IDL is JSON whatever the output format
+    Idl(CliIdl),
+}
+
+impl CommandOutput {
+    /// Prints the output in `output_format`, to stdout
+    pub fn print(&self, output_format: &OutputFormat) {
+        let formatted = match self {
+            Self::Transaction(output) => output_format.formatted_string(output),
+            Self::GreetingAccount(output) => output_format.formatted_string(output),
+            Self::GreetingAccounts(output) => output_format.formatted_string(output),
+            Self::GreetingUpdates(_) => return,
+            Self::History(output) => output_format.formatted_string(output),
+            Self::Inspection(output) => output_format.formatted_string(output),
+            Self::SignedGreeting(output) => output_format.formatted_string(output),
+            Self::Config(output) => output_format.formatted_string(output),
+            Self::Program(output) => output_format.formatted_string(output),
+            Self::VerifiedProgram(output) => output_format.formatted_string(output),
+            Self::Bench(output) => output_format.formatted_string(output),
+            Self::Estimate(output) => output_format.formatted_string(output),
+            Self::Localnet(output) => output_format.formatted_string(output),
+            Self::Idl(output) => return print!("{}", output),
+        };
+        println!("{}", formatted);
+    }
+}
+
+/// `From` each output into its `CommandOutput` variant, and `TryFrom` back
+macro_rules! command_output_conversions {
+    ($($variant:ident($output:ty),)*) => {
+        $(
+            impl From<$output> for CommandOutput {
+                fn from(output: $output) -> Self {
+                    Self::$variant(output)
+                }
+            }
+
+            impl TryFrom<CommandOutput> for $output {
+                type Error = CommandOutput;
+
+                fn try_from(output: CommandOutput) -> Result<Self, CommandOutput> {
+                    match output {
+                        CommandOutput::$variant(output) => Ok(output),
+                        output => Err(output),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+command_output_conversions! {
+    Transaction(CliTransaction),
+    GreetingAccount(CliGreetingAccount),
+    GreetingAccounts(CliGreetingAccounts),
+    GreetingUpdates(Vec<CliGreetingUpdate>),
+    History(CliHistory),
+    Inspection(CliInspection),
+    SignedGreeting(CliSignedGreeting),
+    Config(CliConfig),
+    Program(CliProgram),
+    VerifiedProgram(CliVerifiedProgram),
+    Bench(CliBench),
+    Estimate(CliEstimate),
+    Localnet(CliLocalnet),
+    Idl(CliIdl),
+}
+
+/// A failed command, printed to stderr
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CliCommandError {
+    pub error: String,
+    pub exit_code: i32,
+}
+
+impl QuietDisplay for CliCommandError {}
+impl VerboseDisplay for CliCommandError {}
+
+impl fmt::Display for CliCommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "error: {}", self.error)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_greeting_account_schema() {
+        let address = Pubkey::new_from_array([2; 32]);
+        let authority = Pubkey::new_from_array([1; 32]);
+        let cli_greeting_account = CliGreetingAccount::new(
+            &address,
+            GreetingAccount {
+                counter: 3,
+                counter_times_2: 6,
+                authority,
+                flags: GreetingAccount::TOP_LEVEL_ONLY | GreetingAccount::PRINTABLE_ONLY,
+            },
+        );
+        assert_eq!(
+            serde_json::to_value(&cli_greeting_account).unwrap(),
+            serde_json::json!({
+                "address": address.to_string(),
+                "counter": 3,
+                "counterTimes2": 6,
+                "authority": authority.to_string(),
+                "flags": 5,
+                "topLevelOnly": true,
+                "greetingPolicy": "printable",
+                "legacy": false,
+            })
+        );
+
+        let transaction = CliTransaction {
+            signature: Some("sig".to_string()),
+            ..CliTransaction::default()
+        };
+        assert_eq!(
+            serde_json::to_value(&transaction).unwrap(),
+            serde_json::json!({ "signature": "sig" })
+        );
+    }
+}
@@ -0,0 +1,139 @@
+//! The project config, `helloworld.toml` in the working directory.
+//!
+//! It sits between the command line and the `solana` CLI config: whatever it
+//! sets is used unless a flag overrides it. Settings at the top of the file
+//! are the defaults, `[profiles.<name>]` tables override them when selected
+//! with `--profile` or `--cluster`. `localnet up` writes it.
+
+use {
+    crate::Error,
+    serde::{Deserialize, Serialize},
+    std::{collections::BTreeMap, path::Path},
+};
+
+pub(crate) const PROJECT_CONFIG: &str = "helloworld.toml";
+
+/// The settings a profile can pin, all optional
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+pub(crate) struct Profile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) json_rpc_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) commitment: Option<String>,
+    /// Keypair path of the fee payer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) fee_payer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) program_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) greeting_account_seed: Option<String>,
+}
+
+impl Profile {
+    /// `self`, with the settings it leaves unset taken from `defaults`
+    pub(crate) fn or(self, defaults: &Profile) -> Profile {
+        Profile {
+            json_rpc_url: self.json_rpc_url.or_else(|| defaults.json_rpc_url.clone()),
+            commitment: self.commitment.or_else(|| defaults.commitment.clone()),
+            fee_payer: self.fee_payer.or_else(|| defaults.fee_payer.clone()),
+            program_id: self.program_id.or_else(|| defaults.program_id.clone()),
+            greeting_account_seed: self
+                .greeting_account_seed
+                .or_else(|| defaults.greeting_account_seed.clone()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+pub(crate) struct ProjectConfig {
+    #[serde(flatten)]
+    pub(crate) defaults: Profile,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) profiles: BTreeMap<String, Profile>,
+}
+
+impl ProjectConfig {
+    /// Loads the config at `path`, `None` if there is no such file
+    pub(crate) fn load(path: &str) -> Result<Option<Self>, Error> {
+        if !Path::new(path).exists() {
+            return Ok(None);
+        }
+        let contents = std::fs::read_to_string(path)?;
+        toml::from_str(&contents)
+            .map(Some)
+            .map_err(|e| format!("Invalid {}: {}", path, e).into())
+    }
+
+    pub(crate) fn save(&self, path: &str) -> Result<(), Error> {
+        std::fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// The settings of profile `name` over the defaults, `None` if there's no
+    /// such profile
+    pub(crate) fn profile(&self, name: &str) -> Option<Profile> {
+        self.profiles
+            .get(name)
+            .map(|profile| profile.clone().or(&self.defaults))
+    }
+
+    /// The profile `name`, created if missing, or the defaults
+    pub(crate) fn profile_mut(&mut self, name: Option<&str>) -> &mut Profile {
+        match name {
+            Some(name) => self.profiles.entry(name.to_string()).or_default(),
+            None => &mut self.defaults,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let path = std::env::temp_dir().join(format!("helloworld-{}.toml", std::process::id()));
+        let path = path.to_str().unwrap();
+        assert_eq!(ProjectConfig::load(path).unwrap(), None);
+
+        let mut project_config = ProjectConfig::default();
+        project_config.defaults.json_rpc_url = Some("http://127.0.0.1:8899".to_string());
+        project_config.defaults.greeting_account_seed = Some("hi".to_string());
+        project_config.profile_mut(Some("devnet")).json_rpc_url = Some("devnet".to_string());
+        project_config.save(path).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(path).unwrap(),
+            "json_rpc_url = \"http://127.0.0.1:8899\"\n\
+            greeting_account_seed = \"hi\"\n\
+            [profiles.devnet]\n\
+            json_rpc_url = \"devnet\"\n"
+        );
+        assert_eq!(ProjectConfig::load(path).unwrap(), Some(project_config));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_profile() {
+        let project_config: ProjectConfig = toml::from_str(
+            r#"
+            greeting_account_seed = "hello"
+            program_id = "default"
+
+            [profiles.devnet]
+            json_rpc_url = "devnet"
+            program_id = "devnet"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            project_config.profile("devnet"),
+            Some(Profile {
+                json_rpc_url: Some("devnet".to_string()),
+                program_id: Some("devnet".to_string()),
+                greeting_account_seed: Some("hello".to_string()),
+                ..Profile::default()
+            })
+        );
+        assert_eq!(project_config.profile("testnet"), None);
+    }
+}
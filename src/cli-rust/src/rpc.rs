@@ -0,0 +1,487 @@
+//! The cluster as the commands see it.
+//!
+//! Commands only reach the cluster through `Rpc`, which `RpcClient`
+//! implements, and `watch` through `Pubsub`, so tests can substitute an
+//! in-memory bank for a validator.
+
+// `Rpc` keeps `RpcClient`'s signatures, large `ClientError` and all
+#![allow(clippy::result_large_err)]
+
+use {
+    crate::Error,
+    solana_account_decoder::UiAccount,
+    solana_client::{
+        blockhash_query::{BlockhashQuery, Source},
+        client_error::Result as ClientResult,
+        nonce_utils,
+        pubsub_client::{PubsubClient, PubsubClientError},
+        rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient},
+        rpc_config::{
+            RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcSimulateTransactionConfig,
+            RpcTransactionConfig,
+        },
+        rpc_response::{
+            Response, RpcConfirmedTransactionStatusWithSignature, RpcResult,
+            RpcSimulateTransactionResult,
+        },
+    },
+    solana_sdk::{
+        account::Account,
+        commitment_config::CommitmentConfig,
+        hash::Hash,
+        message::Message,
+        pubkey::Pubkey,
+        signature::Signature,
+        transaction::{self, Transaction},
+    },
+    solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta,
+};
+
+/// The JSON RPC methods the commands use, with `RpcClient`'s signatures
+pub trait Rpc {
+    fn url(&self) -> String;
+    fn commitment(&self) -> CommitmentConfig;
+    fn get_health(&self) -> ClientResult<()>;
+    fn get_account(&self, pubkey: &Pubkey) -> ClientResult<Account>;
+    fn get_account_with_commitment(
+        &self,
+        pubkey: &Pubkey,
+        commitment: CommitmentConfig,
+    ) -> RpcResult<Option<Account>>;
+    fn get_balance(&self, pubkey: &Pubkey) -> ClientResult<u64>;
+    fn get_program_accounts_with_config(
+        &self,
+        program_id: &Pubkey,
+        config: RpcProgramAccountsConfig,
+    ) -> ClientResult<Vec<(Pubkey, Account)>>;
+    fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> ClientResult<u64>;
+    fn get_latest_blockhash(&self) -> ClientResult<Hash>;
+    fn is_blockhash_valid(
+        &self,
+        blockhash: &Hash,
+        commitment: CommitmentConfig,
+    ) -> ClientResult<bool>;
+    fn get_fee_for_message(&self, message: &Message) -> ClientResult<u64>;
+    fn simulate_transaction_with_config(
+        &self,
+        transaction: &Transaction,
+        config: RpcSimulateTransactionConfig,
+    ) -> RpcResult<RpcSimulateTransactionResult>;
+    fn send_and_confirm_transaction(&self, transaction: &Transaction) -> ClientResult<Signature>;
+    fn send_and_confirm_transaction_with_spinner(
+        &self,
+        transaction: &Transaction,
+    ) -> ClientResult<Signature>;
+    fn get_signature_status(
+        &self,
+        signature: &Signature,
+    ) -> ClientResult<Option<transaction::Result<()>>>;
+    fn poll_for_signature_with_commitment(
+        &self,
+        signature: &Signature,
+        commitment: CommitmentConfig,
+    ) -> ClientResult<()>;
+    fn request_airdrop(&self, pubkey: &Pubkey, lamports: u64) -> ClientResult<Signature>;
+    fn get_signatures_for_address_with_config(
+        &self,
+        address: &Pubkey,
+        config: GetConfirmedSignaturesForAddress2Config,
+    ) -> ClientResult<Vec<RpcConfirmedTransactionStatusWithSignature>>;
+    fn get_transaction_with_config(
+        &self,
+        signature: &Signature,
+        config: RpcTransactionConfig,
+    ) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta>;
+}
+
+impl Rpc for RpcClient {
+    fn url(&self) -> String {
+        RpcClient::url(self)
+    }
+
+    fn commitment(&self) -> CommitmentConfig {
+        RpcClient::commitment(self)
+    }
+
+    fn get_health(&self) -> ClientResult<()> {
+        RpcClient::get_health(self)
+    }
+
+    fn get_account(&self, pubkey: &Pubkey) -> ClientResult<Account> {
+        RpcClient::get_account(self, pubkey)
+    }
+
+    fn get_account_with_commitment(
+        &self,
+        pubkey: &Pubkey,
+        commitment: CommitmentConfig,
+    ) -> RpcResult<Option<Account>> {
+        RpcClient::get_account_with_commitment(self, pubkey, commitment)
+    }
+
+    fn get_balance(&self, pubkey: &Pubkey) -> ClientResult<u64> {
+        RpcClient::get_balance(self, pubkey)
+    }
+
+    fn get_program_accounts_with_config(
+        &self,
+        program_id: &Pubkey,
+        config: RpcProgramAccountsConfig,
+    ) -> ClientResult<Vec<(Pubkey, Account)>> {
+        RpcClient::get_program_accounts_with_config(self, program_id, config)
+    }
+
+    fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> ClientResult<u64> {
+        RpcClient::get_minimum_balance_for_rent_exemption(self, data_len)
+    }
+
+    fn get_latest_blockhash(&self) -> ClientResult<Hash> {
+        RpcClient::get_latest_blockhash(self)
+    }
+
+    fn is_blockhash_valid(
+        &self,
+        blockhash: &Hash,
+        commitment: CommitmentConfig,
+    ) -> ClientResult<bool> {
+        RpcClient::is_blockhash_valid(self, blockhash, commitment)
+    }
+
+    fn get_fee_for_message(&self, message: &Message) -> ClientResult<u64> {
+        RpcClient::get_fee_for_message(self, message)
+    }
+
+    fn simulate_transaction_with_config(
+        &self,
+        transaction: &Transaction,
+        config: RpcSimulateTransactionConfig,
+    ) -> RpcResult<RpcSimulateTransactionResult> {
+        RpcClient::simulate_transaction_with_config(self, transaction, config)
+    }
+
+    fn send_and_confirm_transaction(&self, transaction: &Transaction) -> ClientResult<Signature> {
+        RpcClient::send_and_confirm_transaction(self, transaction)
+    }
+
+    fn send_and_confirm_transaction_with_spinner(
+        &self,
+        transaction: &Transaction,
+    ) -> ClientResult<Signature> {
+        RpcClient::send_and_confirm_transaction_with_spinner(self, transaction)
+    }
+
+    fn get_signature_status(
+        &self,
+        signature: &Signature,
+    ) -> ClientResult<Option<transaction::Result<()>>> {
+        RpcClient::get_signature_status(self, signature)
+    }
+
+    fn poll_for_signature_with_commitment(
+        &self,
+        signature: &Signature,
+        commitment: CommitmentConfig,
+    ) -> ClientResult<()> {
+        RpcClient::poll_for_signature_with_commitment(self, signature, commitment)
+    }
+
+    fn request_airdrop(&self, pubkey: &Pubkey, lamports: u64) -> ClientResult<Signature> {
+        RpcClient::request_airdrop(self, pubkey, lamports)
+    }
+
+    fn get_signatures_for_address_with_config(
+        &self,
+        address: &Pubkey,
+        config: GetConfirmedSignaturesForAddress2Config,
+    ) -> ClientResult<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+        RpcClient::get_signatures_for_address_with_config(self, address, config)
+    }
+
+    fn get_transaction_with_config(
+        &self,
+        signature: &Signature,
+        config: RpcTransactionConfig,
+    ) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta> {
+        RpcClient::get_transaction_with_config(self, signature, config)
+    }
+}
+
+/// An `accountSubscribe` subscription
+pub struct AccountSubscription {
+    /// Ends when the subscription does
+    pub notifications: Box<dyn Iterator<Item = Response<UiAccount>>>,
+    pub shutdown: Box<dyn FnOnce()>,
+}
+
+/// The websocket subscriptions the commands use
+pub trait Pubsub {
+    fn account_subscribe(
+        &self,
+        pubkey: &Pubkey,
+        config: Option<RpcAccountInfoConfig>,
+    ) -> Result<AccountSubscription, PubsubClientError>;
+}
+
+/// `Pubsub` with a `PubsubClient` connection to `url` per subscription
+pub struct WebsocketPubsub {
+    pub url: String,
+}
+
+impl Pubsub for WebsocketPubsub {
+    fn account_subscribe(
+        &self,
+        pubkey: &Pubkey,
+        config: Option<RpcAccountInfoConfig>,
+    ) -> Result<AccountSubscription, PubsubClientError> {
+        let (mut subscription, receiver) =
+            PubsubClient::account_subscribe(&self.url, pubkey, config)?;
+        Ok(AccountSubscription {
+            notifications: Box::new(receiver.into_iter()),
+            shutdown: Box::new(move || {
+                // Best effort, the process is about to exit anyway
+                let _ = subscription.shutdown();
+            }),
+        })
+    }
+}
+
+/// `BlockhashQuery::get_blockhash`, which only takes an `RpcClient`
+pub(crate) fn get_blockhash(rpc_client: &dyn Rpc, query: &BlockhashQuery) -> Result<Hash, Error> {
+    let get_source_blockhash = |source: &Source| -> Result<Hash, Error> {
+        match source {
+            Source::Cluster => Ok(rpc_client.get_latest_blockhash()?),
+            Source::NonceAccount(nonce_account) => {
+                let account = rpc_client
+                    .get_account_with_commitment(nonce_account, rpc_client.commitment())?
+                    .value
+                    .ok_or_else(|| format!("Nonce account {} not found", nonce_account))?;
+                Ok(nonce_utils::data_from_account(&account)?.blockhash())
+            }
+        }
+    };
+    match query {
+        BlockhashQuery::None(blockhash) => Ok(*blockhash),
+        BlockhashQuery::FeeCalculator(source, blockhash) => {
+            let valid = match source {
+                Source::Cluster => {
+                    rpc_client.is_blockhash_valid(blockhash, rpc_client.commitment())?
+                }
+                // A durable nonce doesn't expire, but it has to be the one the
+                // nonce account holds now
+                Source::NonceAccount(nonce_account) => {
+                    let durable_blockhash = get_source_blockhash(source)?;
+                    if durable_blockhash != *blockhash {
+                        return Err(format!(
+                            "--blockhash {} isn't the durable blockhash of nonce account {}, {}",
+                            blockhash, nonce_account, durable_blockhash
+                        )
+                        .into());
+                    }
+                    true
+                }
+            };
+            if !valid {
+                return Err(format!("Hash has expired {:?}", blockhash).into());
+            }
+            Ok(*blockhash)
+        }
+        BlockhashQuery::All(source) => get_source_blockhash(source),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        solana_sdk::{
+            nonce::state::{Data, DurableNonce, State, Versions},
+            system_program,
+        },
+        std::collections::HashMap,
+    };
+
+    /// Serves accounts from a map, and nothing else
+    #[derive(Default)]
+    struct MockRpc {
+        accounts: HashMap<Pubkey, Account>,
+    }
+
+    impl Rpc for MockRpc {
+        fn url(&self) -> String {
+            "mock".to_string()
+        }
+
+        fn commitment(&self) -> CommitmentConfig {
+            CommitmentConfig::confirmed()
+        }
+
+        fn get_health(&self) -> ClientResult<()> {
+            Ok(())
+        }
+
+        fn get_account(&self, _pubkey: &Pubkey) -> ClientResult<Account> {
+            unimplemented!()
+        }
+
+        fn get_account_with_commitment(
+            &self,
+            pubkey: &Pubkey,
+            _commitment: CommitmentConfig,
+        ) -> RpcResult<Option<Account>> {
+            Ok(solana_client::rpc_response::Response {
+                context: solana_client::rpc_response::RpcResponseContext {
+                    slot: 0,
+                    api_version: None,
+                },
+                value: self.accounts.get(pubkey).cloned(),
+            })
+        }
+
+        fn get_balance(&self, _pubkey: &Pubkey) -> ClientResult<u64> {
+            unimplemented!()
+        }
+
+        fn get_program_accounts_with_config(
+            &self,
+            _program_id: &Pubkey,
+            _config: RpcProgramAccountsConfig,
+        ) -> ClientResult<Vec<(Pubkey, Account)>> {
+            unimplemented!()
+        }
+
+        fn get_minimum_balance_for_rent_exemption(&self, _data_len: usize) -> ClientResult<u64> {
+            unimplemented!()
+        }
+
+        fn get_latest_blockhash(&self) -> ClientResult<Hash> {
+            unimplemented!()
+        }
+
+        fn is_blockhash_valid(
+            &self,
+            _blockhash: &Hash,
+            _commitment: CommitmentConfig,
+        ) -> ClientResult<bool> {
+            unimplemented!()
+        }
+
+        fn get_fee_for_message(&self, _message: &Message) -> ClientResult<u64> {
+            unimplemented!()
+        }
+
+        fn simulate_transaction_with_config(
+            &self,
+            _transaction: &Transaction,
+            _config: RpcSimulateTransactionConfig,
+        ) -> RpcResult<RpcSimulateTransactionResult> {
+            unimplemented!()
+        }
+
+        fn send_and_confirm_transaction(
+            &self,
+            _transaction: &Transaction,
+        ) -> ClientResult<Signature> {
+            unimplemented!()
+        }
+
+        fn send_and_confirm_transaction_with_spinner(
+            &self,
+            _transaction: &Transaction,
+        ) -> ClientResult<Signature> {
+            unimplemented!()
+        }
+
+        fn get_signature_status(
+            &self,
+            _signature: &Signature,
+        ) -> ClientResult<Option<transaction::Result<()>>> {
+            unimplemented!()
+        }
+
+        fn poll_for_signature_with_commitment(
+            &self,
+            _signature: &Signature,
+            _commitment: CommitmentConfig,
+        ) -> ClientResult<()> {
+            unimplemented!()
+        }
+
+        fn request_airdrop(&self, _pubkey: &Pubkey, _lamports: u64) -> ClientResult<Signature> {
+            unimplemented!()
+        }
+
+        fn get_signatures_for_address_with_config(
+            &self,
+            _address: &Pubkey,
+            _config: GetConfirmedSignaturesForAddress2Config,
+        ) -> ClientResult<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+            unimplemented!()
+        }
+
+        fn get_transaction_with_config(
+            &self,
+            _signature: &Signature,
+            _config: RpcTransactionConfig,
+        ) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta> {
+            unimplemented!()
+        }
+    }
+
+    #[test]
+    fn test_get_blockhash_with_nonce() {
+        let nonce_account = Pubkey::new_unique();
+        let durable_nonce = DurableNonce::from_blockhash(&Hash::new_unique(), true);
+        let data = Data::new(Pubkey::new_unique(), durable_nonce, 5000);
+        let durable_blockhash = data.blockhash();
+        let mut rpc = MockRpc::default();
+        rpc.accounts.insert(
+            nonce_account,
+            Account::new_data(
+                1_000_000,
+                &Versions::new(State::Initialized(data), true),
+                &system_program::id(),
+            )
+            .unwrap(),
+        );
+        let source = || Source::NonceAccount(nonce_account);
+
+        assert_eq!(
+            get_blockhash(&rpc, &BlockhashQuery::All(source())).unwrap(),
+            durable_blockhash
+        );
+        assert_eq!(
+            get_blockhash(
+                &rpc,
+                &BlockhashQuery::FeeCalculator(source(), durable_blockhash)
+            )
+            .unwrap(),
+            durable_blockhash
+        );
+
+        // A stale `--blockhash`, from before the nonce last advanced
+        let stale_blockhash = Hash::new_unique();
+        let err = get_blockhash(
+            &rpc,
+            &BlockhashQuery::FeeCalculator(source(), stale_blockhash),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "--blockhash {} isn't the durable blockhash of nonce account {}, {}",
+                stale_blockhash, nonce_account, durable_blockhash
+            )
+        );
+
+        let missing = Pubkey::new_unique();
+        let err = get_blockhash(
+            &rpc,
+            &BlockhashQuery::FeeCalculator(Source::NonceAccount(missing), durable_blockhash),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("Nonce account {} not found", missing)
+        );
+    }
+}
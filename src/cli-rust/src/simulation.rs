@@ -0,0 +1,228 @@
+//! `--dry-run`: simulates a transaction and reports what it would have done,
+//! down to the greeting accounts it would leave behind.
+
+use {
+    crate::{
+        greeting_error,
+        output::{CliGreetingAccount, CliInvocation, CliSimulation},
+        Config, Error,
+    },
+    helloworld::processor::GreetingAccount,
+    solana_account_decoder::{UiAccount, UiAccountEncoding},
+    solana_client::rpc_config::{
+        RpcSimulateTransactionAccountsConfig, RpcSimulateTransactionConfig,
+    },
+    solana_program::{native_token::lamports_to_sol, pubkey::Pubkey},
+    solana_sdk::{
+        account::Account,
+        transaction::{Transaction, TransactionError},
+    },
+};
+
+/// Simulates `transaction`, which would cost `fee`, returning the report and
+/// the error it would fail with
+pub(crate) fn simulate(
+    config: &Config,
+    transaction: &Transaction,
+    fee: u64,
+) -> Result<(CliSimulation, Option<TransactionError>), Error> {
+    // The greeting account is the first account of every greeting instruction
+    let message = &transaction.message;
+    let mut greeting_account_ids: Vec<Pubkey> = vec![];
+    for instruction in &message.instructions {
+        if message.account_keys[instruction.program_id_index as usize] != config.program_id {
+            continue;
+        }
+        if let Some(index) = instruction.accounts.first() {
+            let greeting_account_id = message.account_keys[*index as usize];
+            if !greeting_account_ids.contains(&greeting_account_id) {
+                greeting_account_ids.push(greeting_account_id);
+            }
+        }
+    }
+
+    let result = config
+        .rpc_client
+        .simulate_transaction_with_config(
+            transaction,
+            RpcSimulateTransactionConfig {
+                commitment: Some(config.rpc_client.commitment()),
+                accounts: Some(RpcSimulateTransactionAccountsConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    addresses: greeting_account_ids
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect(),
+                }),
+                ..RpcSimulateTransactionConfig::default()
+            },
+        )?
+        .value;
+
+    // Closed accounts don't decode and are left out
+    let greeting_accounts = greeting_account_ids
+        .iter()
+        .zip(result.accounts.unwrap_or_default())
+        .filter_map(|(greeting_account_id, account)| {
+            let greeting_account = decode_greeting_account(&config.program_id, account?)?;
+            Some(CliGreetingAccount::new(
+                greeting_account_id,
+                greeting_account,
+            ))
+        })
+        .collect();
+    let logs = result.logs.unwrap_or_default();
+    let simulation = CliSimulation {
+        error: result.err.as_ref().map(ToString::to_string),
+        units_consumed: result.units_consumed,
+        invocations: invocations(&logs),
+        logs,
+        program_error: result
+            .err
+            .as_ref()
+            .and_then(|err| greeting_error(config, transaction, err))
+            .map(|(_, error)| format!("{:?}", error)),
+        greeting_accounts,
+        fee: lamports_to_sol(fee),
+    };
+    Ok((simulation, result.err))
+}
+
+/// Splits the logs of a transaction by program invocation, nested ones
+/// included, in the order they started
+fn invocations(logs: &[String]) -> Vec<CliInvocation> {
+    let mut invocations: Vec<CliInvocation> = vec![];
+    // Indexes of the invocations still running, innermost last
+    let mut running: Vec<usize> = vec![];
+    for log in logs {
+        let current = running.last().copied();
+        if let Some(message) = log.strip_prefix("Program log: ") {
+            if let Some(index) = current {
+                invocations[index].logs.push(message.to_string());
+            }
+            continue;
+        }
+        let rest = match log.strip_prefix("Program ") {
+            Some(rest) => rest,
+            None => continue,
+        };
+        let (program_id, event) = rest.split_once(' ').unwrap_or((rest, ""));
+        if let Some(depth) = event
+            .strip_prefix("invoke [")
+            .and_then(|depth| depth.strip_suffix(']'))
+        {
+            invocations.push(CliInvocation {
+                program_id: program_id.to_string(),
+                depth: depth.parse().unwrap_or(running.len() + 1),
+                units_consumed: None,
+                error: None,
+                logs: vec![],
+            });
+            running.push(invocations.len() - 1);
+        } else if let Some(index) = current {
+            let invocation = &mut invocations[index];
+            if let Some(units) = event
+                .strip_prefix("consumed ")
+                .and_then(|consumed| consumed.split(' ').next())
+            {
+                invocation.units_consumed = units.parse().ok();
+            } else if event == "success" {
+                running.pop();
+            } else if let Some(error) = event.strip_prefix("failed: ") {
+                invocation.error = Some(error.to_string());
+                running.pop();
+            } else {
+                // `Program data:`, `Program return:` and the like
+                invocation.logs.push(log.to_string());
+            }
+        }
+    }
+    invocations
+}
+
+/// Decodes a simulated greeting account of either layout, `None` if it is
+/// closed or isn't one. A legacy account shows up as such until `Migrate`
+/// reallocates it.
+fn decode_greeting_account(program_id: &Pubkey, account: UiAccount) -> Option<GreetingAccount> {
+    let account: Account = account.decode()?;
+    if account.owner != *program_id {
+        return None;
+    }
+    GreetingAccount::unpack_versioned(&account.data).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, borsh::BorshSerialize};
+
+    #[test]
+    fn test_decode_greeting_account() {
+        let program_id = Pubkey::new_unique();
+        let legacy = Account {
+            lamports: 1,
+            data: (3u32, 6u32).try_to_vec().unwrap(),
+            owner: program_id,
+            executable: false,
+            rent_epoch: 0,
+        };
+        let encode = |account: &Account| {
+            UiAccount::encode(
+                &Pubkey::new_unique(),
+                account,
+                UiAccountEncoding::Base64,
+                None,
+                None,
+            )
+        };
+
+        let greeting_account = decode_greeting_account(&program_id, encode(&legacy)).unwrap();
+        assert_eq!(greeting_account.counter, 3);
+        assert_eq!(greeting_account.counter_times_2, 6);
+        assert_eq!(greeting_account.authority, Pubkey::default());
+
+        assert!(decode_greeting_account(&Pubkey::new_unique(), encode(&legacy)).is_none());
+        let closed = Account {
+            data: vec![],
+            ..legacy
+        };
+        assert!(decode_greeting_account(&program_id, encode(&closed)).is_none());
+    }
+
+    #[test]
+    fn test_invocations() {
+        let program_id = Pubkey::new_unique();
+        let other_program_id = Pubkey::new_unique();
+        let logs = [
+            format!("Program {} invoke [1]", program_id),
+            "Program log: Greeted 2 time(s)!".to_string(),
+            format!("Program {} invoke [2]", other_program_id),
+            format!("Program {} success", other_program_id),
+            format!(
+                "Program {} consumed 1234 of 200000 compute units",
+                program_id
+            ),
+            format!("Program {} success", program_id),
+            format!("Program {} invoke [1]", program_id),
+            "Program log: Nonce reused".to_string(),
+            format!("Program {} failed: custom program error: 0x8", program_id),
+        ];
+        let invocations = invocations(&logs);
+        assert_eq!(invocations.len(), 3);
+
+        assert_eq!(invocations[0].program_id, program_id.to_string());
+        assert_eq!(invocations[0].depth, 1);
+        assert_eq!(invocations[0].logs, ["Greeted 2 time(s)!"]);
+        assert_eq!(invocations[0].units_consumed, Some(1234));
+        assert_eq!(invocations[0].error, None);
+
+        assert_eq!(invocations[1].program_id, other_program_id.to_string());
+        assert_eq!(invocations[1].depth, 2);
+        assert!(invocations[1].logs.is_empty());
+
+        assert_eq!(invocations[2].logs, ["Nonce reused"]);
+        assert_eq!(
+            invocations[2].error.as_deref(),
+            Some("custom program error: 0x8")
+        );
+    }
+}
@@ -0,0 +1,162 @@
+//! `watch`: streams the changes of a greeting account from an `accountSubscribe`
+//! websocket subscription, reconnecting whenever the connection drops.
+
+use {
+    crate::{greeting_account_of, output::CliGreetingUpdate, CliError, Config, Error},
+    clap::ArgMatches,
+    helloworld::processor::GreetingAccount,
+    solana_account_decoder::UiAccountEncoding,
+    solana_clap_utils::input_parsers::value_of,
+    solana_client::rpc_config::RpcAccountInfoConfig,
+    solana_sdk::{account::Account, clock::Slot, pubkey::Pubkey},
+    std::{thread::sleep, time::Duration},
+};
+
+const INITIAL_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// Why a subscription stopped
+enum WatchEnd {
+    /// `--count` updates were printed
+    Done,
+    /// The websocket closed, reconnect
+    Disconnected,
+    /// The greeting account doesn't exist (anymore)
+    AccountNotFound,
+    /// The account doesn't hold a `GreetingAccount`
+    InvalidData,
+}
+
+struct Watcher<'a> {
+    config: &'a Config,
+    greeting_account_id: Pubkey,
+    last_counter: Option<u32>,
+    /// Changes left to print before exiting, `None` to watch forever
+    remaining: Option<u64>,
+    /// Printed so far, kept only with a `--count` to bound them
+    updates: Vec<CliGreetingUpdate>,
+}
+
+/// Prints the greeting account's changes as they come, returning them once
+/// `--count` were printed
+pub(crate) fn process_watch(
+    config: &Config,
+    matches: &ArgMatches,
+) -> Result<Vec<CliGreetingUpdate>, Error> {
+    let mut watcher = Watcher {
+        config,
+        greeting_account_id: greeting_account_of(config, matches, &config.fee_payer.pubkey())?,
+        last_counter: None,
+        remaining: value_of(matches, "count"),
+        updates: vec![],
+    };
+
+    let mut reconnect_delay = INITIAL_RECONNECT_DELAY;
+    loop {
+        match watcher.subscribe(&mut reconnect_delay) {
+            Ok(WatchEnd::Done) => return Ok(watcher.updates),
+            Ok(WatchEnd::AccountNotFound) => {
+                return Err(CliError::AccountNotFound(watcher.greeting_account_id).into())
+            }
+            Ok(WatchEnd::InvalidData) => {
+                return Err(
+                    format!("{} is not a greeting account", watcher.greeting_account_id).into(),
+                )
+            }
+            Ok(WatchEnd::Disconnected) => eprintln!("Subscription closed"),
+            Err(err) => eprintln!("Subscription failed: {}", err),
+        }
+        eprintln!("Reconnecting in {}s...", reconnect_delay.as_secs());
+        sleep(reconnect_delay);
+        reconnect_delay = (reconnect_delay * 2).min(MAX_RECONNECT_DELAY);
+    }
+}
+
+impl Watcher<'_> {
+    /// Prints updates until the subscription ends
+    fn subscribe(&mut self, reconnect_delay: &mut Duration) -> Result<WatchEnd, Error> {
+        let commitment = self.config.rpc_client.commitment();
+        let subscription = self.config.pubsub_client.account_subscribe(
+            &self.greeting_account_id,
+            Some(RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                commitment: Some(commitment),
+                ..RpcAccountInfoConfig::default()
+            }),
+        )?;
+        *reconnect_delay = INITIAL_RECONNECT_DELAY;
+
+        // Changes made while we weren't subscribed are only visible through RPC.
+        // Fetching after subscribing means none can fall between the two.
+        let response = self
+            .config
+            .rpc_client
+            .get_account_with_commitment(&self.greeting_account_id, commitment)?;
+        let mut end = match response.value {
+            Some(account) => self.update(response.context.slot, &account),
+            None => Some(WatchEnd::AccountNotFound),
+        };
+
+        if end.is_none() {
+            for response in subscription.notifications {
+                end = match response.value.decode::<Account>() {
+                    Some(account) => self.update(response.context.slot, &account),
+                    None => Some(WatchEnd::AccountNotFound),
+                };
+                if end.is_some() {
+                    break;
+                }
+            }
+        }
+        match end {
+            Some(end) => {
+                (subscription.shutdown)();
+                Ok(end)
+            }
+            None => Ok(WatchEnd::Disconnected),
+        }
+    }
+
+    /// Prints the account if its counter changed, returning how the watch ends
+    /// if it does
+    fn update(&mut self, slot: Slot, account: &Account) -> Option<WatchEnd> {
+        // A closed account is handed back empty and owned by the system program
+        if account.lamports == 0 || account.data.is_empty() {
+            return Some(WatchEnd::AccountNotFound);
+        }
+        let greeting_account = match GreetingAccount::unpack_versioned(&account.data) {
+            Ok(greeting_account) => greeting_account,
+            Err(_) => return Some(WatchEnd::InvalidData),
+        };
+        if self.last_counter == Some(greeting_account.counter) {
+            return None;
+        }
+
+        let update = CliGreetingUpdate {
+            address: self.greeting_account_id.to_string(),
+            slot,
+            counter: greeting_account.counter,
+            counter_times_2: greeting_account.counter_times_2,
+            delta: self
+                .last_counter
+                .map(|last_counter| greeting_account.counter as i64 - last_counter as i64),
+        };
+        println!("{}", self.config.output_format.formatted_string(&update));
+        if self.remaining.is_some() {
+            self.updates.push(update);
+        }
+
+        // The first update is the state we start from, not a change
+        let is_change = self.last_counter.is_some();
+        self.last_counter = Some(greeting_account.counter);
+        if let Some(remaining) = self.remaining.as_mut() {
+            if is_change {
+                *remaining = remaining.saturating_sub(1);
+            }
+            if *remaining == 0 {
+                return Some(WatchEnd::Done);
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,941 @@
+//! The subcommands end to end, against an in-memory bank instead of a
+//! validator
+
+mod common;
+
+use {
+    borsh::{BorshDeserialize, BorshSerialize},
+    cli_rust::{
+        exit_code, exit_code_of,
+        output::{
+            CliConfig, CliEstimate, CliGreetingAccount, CliGreetingAccounts, CliGreetingUpdate,
+            CliHistory, CliIdl, CliInspection, CliLocalnet, CliProgram, CliSignedGreeting,
+            CliTransaction, CliVerifiedProgram,
+        },
+        rpc::Rpc,
+    },
+    common::{temp_path, write_keypair, TestCli},
+    helloworld::{
+        instruction::{init_greeting, migrate, GreetingPayload},
+        processor::GreetingAccount,
+    },
+    helloworld_client::address::{greeting_account_address, DEFAULT_SEED},
+    solana_client::nonce_utils,
+    solana_program_test::programs::spl_programs,
+    solana_sdk::{
+        account::{Account, ReadableAccount},
+        hash::Hash,
+        message::Message,
+        nonce::State as NonceState,
+        program_pack::Pack,
+        pubkey::Pubkey,
+        rent::Rent,
+        signature::{Keypair, Signature, Signer},
+        system_instruction,
+        transaction::Transaction,
+    },
+    std::{path::PathBuf, str::FromStr, time::Duration},
+};
+
+/// Whether the bank processed the transaction of `signature` successfully
+fn succeeded(cli: &TestCli, signature: &Option<String>) -> bool {
+    let signature = Signature::from_str(signature.as_ref().unwrap()).unwrap();
+    matches!(cli.rpc.get_signature_status(&signature), Ok(Some(Ok(()))))
+}
+
+fn greeting_account(cli: &TestCli) -> Option<GreetingAccount> {
+    let account = cli
+        .rpc
+        .get_account_with_commitment(&cli.greeting_account_id(), cli.rpc.commitment())
+        .unwrap()
+        .value?;
+    Some(GreetingAccount::try_from_slice(&account.data).unwrap())
+}
+
+#[test]
+fn test_create_greet_close() {
+    let cli = TestCli::start();
+    let created: CliTransaction = cli.run_as(&["create"]).unwrap();
+    assert!(succeeded(&cli, &created.signature));
+    let created = greeting_account(&cli).unwrap();
+    assert_eq!(created.counter, 0);
+    assert_eq!(created.authority, cli.fee_payer.pubkey());
+
+    let greeted: CliTransaction = cli.run_as(&["greet", "--greetings", "3"]).unwrap();
+    assert!(succeeded(&cli, &greeted.signature));
+    assert_eq!(greeted.greeting_account.unwrap().account.counter, 3);
+    cli.run(&["greet"]).unwrap();
+    let shown: CliGreetingAccount = cli.run_as(&["show"]).unwrap();
+    assert_eq!(shown.address, cli.greeting_account_id().to_string());
+    assert_eq!(shown.account.counter, 4);
+    assert_eq!(shown.account.counter_times_2, 8);
+    assert_eq!(shown.greeting_policy, "any");
+    assert!(!shown.legacy);
+
+    // Native `processor!` programs can't move lamports to an account passed
+    // twice, so the authority doesn't get them back
+    let recipient = Pubkey::new_unique();
+    let closed: CliTransaction = cli
+        .run_as(&["close", "--recipient", &recipient.to_string()])
+        .unwrap();
+    assert!(succeeded(&cli, &closed.signature));
+    assert!(greeting_account(&cli).is_none());
+    assert!(cli.rpc.get_balance(&recipient).unwrap() > 0);
+}
+
+#[test]
+fn test_authority() {
+    let cli = TestCli::start();
+    let authority = Keypair::new();
+    let authority_path = write_keypair(&authority);
+    let authority_path = authority_path.to_str().unwrap();
+    cli.run(&[
+        "create",
+        "--authority",
+        authority_path,
+        "--commitment",
+        "finalized",
+    ])
+    .unwrap();
+    assert_eq!(
+        greeting_account(&cli).unwrap().authority,
+        authority.pubkey()
+    );
+
+    // The fee payer is no longer the authority
+    let err = cli.run(&["close"]).unwrap_err();
+    assert_eq!(exit_code_of(&err), exit_code::PROGRAM_ERROR);
+    cli.run(&["close", "--authority", authority_path]).unwrap();
+    assert!(greeting_account(&cli).is_none());
+    std::fs::remove_file(authority_path).unwrap();
+}
+
+#[test]
+fn test_exit_codes() {
+    let cli = TestCli::start();
+    let err = cli.run(&["greet"]).unwrap_err();
+    assert_eq!(exit_code_of(&err), exit_code::ACCOUNT_NOT_FOUND);
+    let err = cli.run(&["show"]).unwrap_err();
+    assert_eq!(exit_code_of(&err), exit_code::ACCOUNT_NOT_FOUND);
+
+    cli.run(&["create"]).unwrap();
+    let err = cli.run(&["create"]).unwrap_err();
+    assert_eq!(exit_code_of(&err), exit_code::ACCOUNT_EXISTS);
+
+    // The program rejects reusing a greeting nonce, even in a transaction
+    // that differs otherwise
+    cli.run(&["greet", "--greeting-nonce", "1"]).unwrap();
+    let err = cli
+        .run(&[
+            "greet",
+            "--greeting-nonce",
+            "1",
+            "--greeting-string",
+            "again",
+        ])
+        .unwrap_err();
+    assert_eq!(exit_code_of(&err), exit_code::PROGRAM_ERROR);
+    assert_eq!(greeting_account(&cli).unwrap().counter, 1);
+}
+
+#[test]
+fn test_greeting_policy() {
+    let cli = TestCli::start();
+    cli.run(&["create", "--greeting-policy", "printable"])
+        .unwrap();
+    assert_eq!(
+        greeting_account(&cli).unwrap().flags,
+        GreetingAccount::PRINTABLE_ONLY
+    );
+
+    let err = cli
+        .run(&["greet", "--greeting-string", "hello\u{200b}"])
+        .unwrap_err();
+    assert_eq!(exit_code_of(&err), exit_code::USAGE);
+    cli.run(&["greet", "--greeting-string", "¡hola!"]).unwrap();
+    assert_eq!(greeting_account(&cli).unwrap().counter, 1);
+}
+
+#[test]
+fn test_greeting_policy_checked_before_sending() {
+    let cli = TestCli::start();
+    cli.run(&["create", "--greeting-policy", "ascii"]).unwrap();
+    let balance = cli.rpc.get_balance(&cli.fee_payer.pubkey()).unwrap();
+
+    // Rejected by the CLI, not by the program: nothing is sent
+    let err = cli
+        .run(&["greet", "--greeting-string", "¡hola!"])
+        .unwrap_err();
+    assert_eq!(exit_code_of(&err), exit_code::USAGE);
+    assert_eq!(
+        err.to_string(),
+        "Invalid greeting string: Greeting String Not Ascii"
+    );
+    assert_eq!(
+        cli.rpc.get_balance(&cli.fee_payer.pubkey()).unwrap(),
+        balance
+    );
+    cli.run(&["greet", "--greeting-string", "hola"]).unwrap();
+    assert_eq!(greeting_account(&cli).unwrap().counter, 1);
+}
+
+#[test]
+fn test_offline_signing() {
+    let cli = TestCli::start();
+    cli.run(&["create"]).unwrap();
+    let blockhash = cli.rpc.get_latest_blockhash().unwrap();
+    let blockhash_arg = blockhash.to_string();
+
+    // Signing only sends nothing
+    let signed: CliTransaction = cli
+        .run_as(&["greet", "--sign-only", "--blockhash", &blockhash_arg])
+        .unwrap();
+    assert!(signed.signature.is_none());
+    assert!(signed.greeting_account.is_none());
+    assert_eq!(greeting_account(&cli).unwrap().counter, 0);
+    let sign_only = signed.sign_only.unwrap();
+    assert_eq!(sign_only.blockhash, blockhash_arg);
+
+    // The signing machine signed the same message the online one builds
+    let mut message = Message::new(
+        &[init_greeting(
+            cli.program_id,
+            cli.greeting_account_id(),
+            1,
+            "hello".to_string(),
+        )],
+        Some(&cli.fee_payer.pubkey()),
+    );
+    message.recent_blockhash = blockhash;
+    let signature = cli.fee_payer.sign_message(&message.serialize());
+    let fee_payer = cli.fee_payer.pubkey().to_string();
+    let signer = format!("{}={}", fee_payer, signature);
+    assert_eq!(sign_only.signers, std::slice::from_ref(&signer));
+    cli.run(&[
+        "greet",
+        "--blockhash",
+        &blockhash_arg,
+        "--fee-payer",
+        &fee_payer,
+        "--signer",
+        &signer,
+    ])
+    .unwrap();
+    assert_eq!(greeting_account(&cli).unwrap().counter, 1);
+
+    // A signature of another message doesn't sign this one
+    assert!(cli
+        .run(&[
+            "greet",
+            "--greetings",
+            "2",
+            "--blockhash",
+            &blockhash_arg,
+            "--fee-payer",
+            &fee_payer,
+            "--signer",
+            &signer,
+        ])
+        .is_err());
+    assert_eq!(greeting_account(&cli).unwrap().counter, 1);
+}
+
+#[test]
+fn test_relay_greeting() {
+    let cli = TestCli::start();
+    cli.run(&["create"]).unwrap();
+    let greeting_account_id = cli.greeting_account_id().to_string();
+    let payload = GreetingPayload {
+        program_id: cli.program_id,
+        greeting_account: cli.greeting_account_id(),
+        num_greetings: 2,
+        nonce: 7,
+    };
+    let signature = cli.greeter.sign_message(&payload.try_to_vec().unwrap());
+    let relay_greeting = [
+        "relay-greeting",
+        "--greeter",
+        &cli.greeter.pubkey().to_string(),
+        "--signature",
+        &signature.to_string(),
+        "--greetings",
+        "2",
+        "--greeting-nonce",
+        "7",
+        "--greeting-account",
+        &greeting_account_id,
+    ]
+    .map(String::from);
+    let relay_greeting = relay_greeting
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    let relayed: CliTransaction = cli.run_as(&relay_greeting).unwrap();
+    assert!(succeeded(&cli, &relayed.signature));
+    assert_eq!(relayed.greeting_account.unwrap().account.counter, 2);
+    assert_eq!(greeting_account(&cli).unwrap().counter, 2);
+
+    // Replaying it fails, even with a priority fee to tell the transactions
+    // apart
+    let mut replay = relay_greeting;
+    replay.extend_from_slice(&["--compute-unit-price", "1"]);
+    let err = cli.run(&replay).unwrap_err();
+    assert_eq!(exit_code_of(&err), exit_code::PROGRAM_ERROR);
+}
+
+#[test]
+fn test_list_history_inspect() {
+    let cli = TestCli::start();
+    cli.run(&["create"]).unwrap();
+    cli.run(&["create", "--seed", "other"]).unwrap();
+    let greeted: CliTransaction = cli.run_as(&["greet"]).unwrap();
+    let greeting_account_id = cli.greeting_account_id().to_string();
+
+    let listed: CliGreetingAccounts = cli.run_as(&["list", "--limit", "1"]).unwrap();
+    assert_eq!(listed.total, 2);
+    assert_eq!(listed.accounts.len(), 1);
+    // Highest counter first
+    assert_eq!(listed.accounts[0].address, greeting_account_id);
+    assert_eq!(listed.accounts[0].account.counter, 1);
+    let listed: CliGreetingAccounts = cli
+        .run_as(&[
+            "list",
+            "--with-authority",
+            &Pubkey::new_unique().to_string(),
+        ])
+        .unwrap();
+    assert_eq!(listed.total, 0);
+
+    let history: CliHistory = cli.run_as(&["history", "--limit", "1"]).unwrap();
+    assert_eq!(history.greeting_account, greeting_account_id);
+    assert_eq!(history.entries.len(), 1);
+    assert_eq!(
+        Some(&history.entries[0].signature),
+        greeted.signature.as_ref()
+    );
+    assert_eq!(
+        history.entries[0].instructions[0].instruction,
+        "InitGreeting"
+    );
+    assert_eq!(history.entries[0].counter, Some(1));
+    assert_eq!(history.before, greeted.signature);
+
+    let inspection: CliInspection = cli
+        .run_as(&["inspect", "account", &greeting_account_id])
+        .unwrap();
+    assert_eq!(inspection.length, GreetingAccount::LEN);
+    assert_eq!(inspection.owner, Some(cli.program_id.to_string()));
+    let fields: Vec<_> = inspection
+        .fields
+        .iter()
+        .map(|field| (field.name.as_str(), field.value.as_str()))
+        .collect();
+    assert_eq!(fields[0], ("counter", "1"));
+    assert_eq!(
+        fields[2],
+        ("authority", &*cli.fee_payer.pubkey().to_string())
+    );
+    let err = cli
+        .run(&["inspect", "account", &cli.fee_payer.pubkey().to_string()])
+        .unwrap_err();
+    assert_eq!(exit_code_of(&err), exit_code::DECODE_FAILED);
+}
+
+#[test]
+fn test_estimate_and_config() {
+    let cli = TestCli::start();
+    let batch = std::env::temp_dir().join(format!("cli-rust-batch-{}.csv", std::process::id()));
+    std::fs::write(&batch, "instruction,count\ncreate,1\ngreet,10\nclose,1\n").unwrap();
+    let estimate: CliEstimate = cli
+        .run_as(&["estimate", "--batch", batch.to_str().unwrap()])
+        .unwrap();
+    let batch_estimate = estimate.batch.unwrap();
+    assert_eq!(batch_estimate.transactions, 12);
+    assert_eq!(batch_estimate.lines.len(), 3);
+    assert!(batch_estimate.sufficient);
+    std::fs::write(&batch, "wave,1\n").unwrap();
+    assert!(cli
+        .run(&["estimate", "--batch", batch.to_str().unwrap()])
+        .is_err());
+    std::fs::remove_file(&batch).unwrap();
+
+    let config: CliConfig = cli.run_as(&["config"]).unwrap();
+    assert_eq!(config.program_id, cli.program_id.to_string());
+    assert_eq!(config.fee_payer, cli.fee_payer.pubkey().to_string());
+    assert_eq!(config.greeting_account_seed, DEFAULT_SEED);
+
+    let signed: CliSignedGreeting = cli
+        .run_as(&["sign-greeting", "--greeting-nonce", "1"])
+        .unwrap();
+    let payload = GreetingPayload {
+        program_id: cli.program_id,
+        greeting_account: cli.greeting_account_id(),
+        num_greetings: 1,
+        nonce: 1,
+    };
+    assert_eq!(signed.greeter, cli.fee_payer.pubkey().to_string());
+    assert!(Signature::from_str(&signed.signature).unwrap().verify(
+        cli.fee_payer.pubkey().as_ref(),
+        &payload.try_to_vec().unwrap()
+    ));
+}
+
+#[test]
+fn test_dry_run() {
+    let cli = TestCli::start();
+    let simulated: CliTransaction = cli.run_as(&["create", "--dry-run"]).unwrap();
+    assert!(simulated.signature.is_none());
+    let simulation = simulated.simulation.unwrap();
+    assert!(simulation.error.is_none());
+    assert_eq!(
+        simulation.greeting_accounts[0].address,
+        cli.greeting_account_id().to_string()
+    );
+    assert_eq!(
+        simulation.greeting_accounts[0].account.authority,
+        cli.fee_payer.pubkey()
+    );
+    assert!(greeting_account(&cli).is_none());
+
+    cli.run(&["create"]).unwrap();
+    let simulated: CliTransaction = cli
+        .run_as(&["greet", "--greetings", "2", "--dry-run"])
+        .unwrap();
+    let simulation = simulated.simulation.unwrap();
+    assert_eq!(simulation.greeting_accounts[0].account.counter, 2);
+    let program_id = cli.program_id.to_string();
+    let invocation = simulation
+        .invocations
+        .iter()
+        .find(|invocation| invocation.program_id == program_id)
+        .unwrap();
+    assert!(invocation.logs.contains(&"Greeted 2 time(s)!".to_string()));
+    assert_eq!(greeting_account(&cli).unwrap().counter, 0);
+
+    // A simulation that fails is an error too
+    cli.run(&["greet", "--greeting-nonce", "1"]).unwrap();
+    let err = cli
+        .run(&["greet", "--greeting-nonce", "1", "--dry-run"])
+        .unwrap_err();
+    assert_eq!(exit_code_of(&err), exit_code::PROGRAM_ERROR);
+    assert_eq!(greeting_account(&cli).unwrap().counter, 1);
+}
+
+#[test]
+fn test_auto_compute_limit() {
+    let cli = TestCli::start();
+    cli.run(&["create", "--auto-compute-limit"]).unwrap();
+    let greeted: CliTransaction = cli.run_as(&["greet", "--auto-compute-limit"]).unwrap();
+    assert!(succeeded(&cli, &greeted.signature));
+    assert_eq!(greeting_account(&cli).unwrap().counter, 1);
+
+    let err = cli
+        .run(&[
+            "greet",
+            "--auto-compute-limit",
+            "--sign-only",
+            "--blockhash",
+            &cli.rpc.get_latest_blockhash().unwrap().to_string(),
+        ])
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "--auto-compute-limit needs to simulate, which --sign-only can't"
+    );
+}
+
+/// The blockhash `nonce_account` holds
+fn durable_blockhash(cli: &TestCli, nonce_account: &Pubkey) -> Hash {
+    let account = cli.rpc.get_account(nonce_account).unwrap();
+    nonce_utils::data_from_account(&account)
+        .unwrap()
+        .blockhash()
+}
+
+/// Waits for the bank to move past its latest blockhash, which a nonce
+/// account can't be advanced with
+fn wait_for_new_blockhash(cli: &TestCli) {
+    let blockhash = cli.rpc.get_latest_blockhash().unwrap();
+    while cli.rpc.get_latest_blockhash().unwrap() == blockhash {
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+#[test]
+fn test_durable_nonce() {
+    let cli = TestCli::start();
+    cli.run(&["create"]).unwrap();
+    let nonce_account = Keypair::new();
+    let fee_payer = cli.fee_payer.pubkey();
+    let lamports = cli
+        .rpc
+        .get_minimum_balance_for_rent_exemption(NonceState::size())
+        .unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &system_instruction::create_nonce_account(
+            &fee_payer,
+            &nonce_account.pubkey(),
+            &fee_payer,
+            lamports,
+        ),
+        Some(&fee_payer),
+        &[&cli.fee_payer, &nonce_account],
+        cli.rpc.get_latest_blockhash().unwrap(),
+    );
+    cli.rpc.send_and_confirm_transaction(&transaction).unwrap();
+    let nonce_arg = nonce_account.pubkey().to_string();
+
+    wait_for_new_blockhash(&cli);
+    let durable = durable_blockhash(&cli, &nonce_account.pubkey());
+    let greeted: CliTransaction = cli
+        .run_as(&[
+            "greet",
+            "--nonce",
+            &nonce_arg,
+            "--blockhash",
+            &durable.to_string(),
+        ])
+        .unwrap();
+    assert!(succeeded(&cli, &greeted.signature));
+    assert_eq!(greeting_account(&cli).unwrap().counter, 1);
+    assert_ne!(durable_blockhash(&cli, &nonce_account.pubkey()), durable);
+
+    // Signed offline with the durable blockhash, however long ago
+    wait_for_new_blockhash(&cli);
+    let durable = durable_blockhash(&cli, &nonce_account.pubkey()).to_string();
+    let signed: CliTransaction = cli
+        .run_as(&[
+            "greet",
+            "--sign-only",
+            "--nonce",
+            &nonce_arg,
+            "--blockhash",
+            &durable,
+        ])
+        .unwrap();
+    let signer = signed.sign_only.unwrap().signers.remove(0);
+    let relayed: CliTransaction = cli
+        .run_as(&[
+            "greet",
+            "--nonce",
+            &nonce_arg,
+            "--blockhash",
+            &durable,
+            "--fee-payer",
+            &fee_payer.to_string(),
+            "--signer",
+            &signer,
+        ])
+        .unwrap();
+    assert!(succeeded(&cli, &relayed.signature));
+    assert_eq!(greeting_account(&cli).unwrap().counter, 2);
+
+    // The nonce advanced, so the same transaction can't be sent again
+    let err = cli
+        .run(&[
+            "greet",
+            "--nonce",
+            &nonce_arg,
+            "--blockhash",
+            &durable,
+            "--fee-payer",
+            &fee_payer.to_string(),
+            "--signer",
+            &signer,
+        ])
+        .unwrap_err();
+    assert!(err.to_string().contains("isn't the durable blockhash"));
+    assert_eq!(greeting_account(&cli).unwrap().counter, 2);
+}
+
+#[test]
+fn test_watch() {
+    let cli = TestCli::start();
+    cli.run(&["create"]).unwrap();
+    std::thread::scope(|scope| {
+        let watch = scope.spawn(|| {
+            // Errors aren't `Send`, their exit code is
+            cli.run_as::<Vec<CliGreetingUpdate>>(&["watch", "--count", "2"])
+                .map_err(|err| exit_code_of(&err))
+        });
+        while cli.rpc.subscriptions() == 0 && !watch.is_finished() {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        // Greeting before `watch` read the account it subscribed to only
+        // moves its starting point
+        while !watch.is_finished() {
+            cli.run(&["greet"]).unwrap();
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        let updates = watch.join().unwrap().unwrap();
+        assert_eq!(updates.len(), 3);
+        assert_eq!(updates[0].delta, None);
+        for (previous, update) in updates.iter().zip(&updates[1..]) {
+            assert_eq!(update.address, cli.greeting_account_id().to_string());
+            assert_eq!(update.counter, previous.counter + 1);
+            assert_eq!(update.counter_times_2, update.counter * 2);
+            assert_eq!(update.delta, Some(1));
+        }
+    });
+
+    // Watching forever ends when the account is closed
+    std::thread::scope(|scope| {
+        let watch = scope.spawn(|| {
+            cli.run(&["watch"])
+                .map(drop)
+                .map_err(|err| exit_code_of(&err))
+        });
+        while cli.rpc.subscriptions() == 0 && !watch.is_finished() {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        cli.run(&["close", "--recipient", &Pubkey::new_unique().to_string()])
+            .unwrap();
+        assert_eq!(watch.join().unwrap(), Err(exit_code::ACCOUNT_NOT_FOUND));
+    });
+}
+
+#[test]
+fn test_bench_needs_a_local_validator() {
+    let cli = TestCli::start();
+    assert!(cli.run(&["bench"]).is_err());
+}
+
+/// A legacy 8-byte greeting account at the address `create` would use
+fn legacy_account(program_id: &Pubkey, fee_payer: &Pubkey, counter: u32) -> (Pubkey, Account) {
+    let address = greeting_account_address(fee_payer, DEFAULT_SEED, program_id).unwrap();
+    let data = (counter, counter * 2).try_to_vec().unwrap();
+    let account = Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: *program_id,
+        ..Account::default()
+    };
+    (address, account)
+}
+
+#[test]
+fn test_legacy_account() {
+    let cli =
+        TestCli::start_with(|program_id, fee_payer| vec![legacy_account(program_id, fee_payer, 3)]);
+    cli.run(&["create", "--seed", "other"]).unwrap();
+
+    let export = std::env::temp_dir().join(format!("cli-rust-legacy-{}.json", std::process::id()));
+    cli.run(&["list", "--export", export.to_str().unwrap()])
+        .unwrap();
+    let listed: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&export).unwrap()).unwrap();
+    std::fs::remove_file(&export).unwrap();
+    let accounts = listed["accounts"].as_array().unwrap();
+    assert_eq!(accounts.len(), 2);
+    assert_eq!(
+        accounts[0]["address"],
+        cli.greeting_account_id().to_string()
+    );
+    assert_eq!(accounts[0]["counter"], 3);
+    assert_eq!(accounts[0]["legacy"], true);
+    assert_eq!(accounts[1]["legacy"], false);
+
+    let shown: CliGreetingAccount = cli.run_as(&["show"]).unwrap();
+    assert!(shown.legacy);
+    assert_eq!(shown.account.counter_times_2, 6);
+
+    // `inspect` decodes the legacy layout rather than failing on it
+    let inspection: CliInspection = cli
+        .run_as(&["inspect", "account", &cli.greeting_account_id().to_string()])
+        .unwrap();
+    assert_eq!(inspection.variant.as_deref(), Some("legacy"));
+    assert_eq!(inspection.fields.len(), 2);
+    assert_eq!(inspection.fields[1].name, "counter_times_2");
+    assert_eq!(inspection.fields[1].value, "6");
+
+    // Only accounts with an authority match one
+    cli.run(&[
+        "list",
+        "--with-authority",
+        &cli.fee_payer.pubkey().to_string(),
+        "--export",
+        export.to_str().unwrap(),
+    ])
+    .unwrap();
+    let listed: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&export).unwrap()).unwrap();
+    std::fs::remove_file(&export).unwrap();
+    assert_eq!(listed["total"], 1);
+    assert_eq!(listed["accounts"][0]["legacy"], false);
+}
+
+#[test]
+fn test_migrate() {
+    let cli = TestCli::start();
+    let err = cli.run(&["migrate"]).unwrap_err();
+    assert_eq!(exit_code_of(&err), exit_code::ACCOUNT_NOT_FOUND);
+
+    // Native `processor!` programs can't grow account data, so `migrate` is
+    // only signed here
+    let cli =
+        TestCli::start_with(|program_id, fee_payer| vec![legacy_account(program_id, fee_payer, 3)]);
+    let blockhash = cli.rpc.get_latest_blockhash().unwrap();
+    let signed: CliTransaction = cli
+        .run_as(&[
+            "migrate",
+            "--sign-only",
+            "--blockhash",
+            &blockhash.to_string(),
+        ])
+        .unwrap();
+    let fee_payer = cli.fee_payer.pubkey();
+    let mut message = Message::new(
+        &[migrate(
+            cli.program_id,
+            cli.greeting_account_id(),
+            fee_payer,
+            fee_payer,
+            DEFAULT_SEED.to_string(),
+            fee_payer,
+            0,
+        )],
+        Some(&fee_payer),
+    );
+    message.recent_blockhash = blockhash;
+    let signature = cli.fee_payer.sign_message(&message.serialize());
+    assert_eq!(
+        signed.sign_only.unwrap().signers,
+        [format!("{}={}", fee_payer, signature)]
+    );
+    let account = cli.rpc.get_account(&cli.greeting_account_id()).unwrap();
+    assert_eq!(account.data.len(), GreetingAccount::LEGACY_LEN);
+}
+
+#[test]
+fn test_idl() {
+    let cli = TestCli::start();
+    let printed: CliIdl = cli.run_as(&["idl"]).unwrap();
+    assert!(printed.json_path.is_none());
+    let idl: serde_json::Value = serde_json::from_str(&printed.idl).unwrap();
+    let instructions = idl["instructions"].as_array().unwrap();
+    assert_eq!(instructions[0]["name"], "InitGreeting");
+
+    let json = temp_path("json");
+    let ts = temp_path("ts");
+    let written: CliIdl = cli
+        .run_as(&[
+            "idl",
+            "--json",
+            json.to_str().unwrap(),
+            "--ts",
+            ts.to_str().unwrap(),
+        ])
+        .unwrap();
+    assert_eq!(written.json_path.as_deref(), json.to_str());
+    assert_eq!(std::fs::read_to_string(&json).unwrap(), printed.idl);
+    let schema = std::fs::read_to_string(&ts).unwrap();
+    assert!(schema.contains("GreetingAccount"));
+    std::fs::remove_file(json).unwrap();
+    std::fs::remove_file(ts).unwrap();
+}
+
+/// Two builds to deploy: the ELFs of SPL Memo 1.0 and 3.0, written to files
+fn program_files() -> (PathBuf, PathBuf) {
+    let programs = spl_programs(&Rent::default());
+    let write_program = |program_id: &str| {
+        let (_, account) = programs
+            .iter()
+            .find(|(id, _)| id.to_string() == program_id)
+            .unwrap();
+        let path = temp_path("so");
+        std::fs::write(&path, account.data()).unwrap();
+        path
+    };
+    (
+        write_program("Memo1UhkJRfHyvLMcVucJwxXeuD728EqVDDwQDxFMNo"),
+        write_program("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr"),
+    )
+}
+
+#[test]
+fn test_deploy_upgrade_verify() {
+    let cli = TestCli::start();
+    let (memo_1, memo_3) = program_files();
+    let (memo_1, memo_3) = (memo_1.to_str().unwrap(), memo_3.to_str().unwrap());
+    let program_keypair = Keypair::new();
+    let program_keypair_path = write_keypair(&program_keypair);
+    let program_id = program_keypair.pubkey().to_string();
+    let fee_payer = cli.fee_payer.pubkey().to_string();
+
+    // Room for the larger build
+    let deployed: CliProgram = cli
+        .run_as(&[
+            "deploy",
+            memo_1,
+            "--program",
+            program_keypair_path.to_str().unwrap(),
+            "--max-len",
+            "80000",
+        ])
+        .unwrap();
+    assert_eq!(deployed.program_id, program_id);
+    assert_eq!(deployed.upgrade_authority.as_ref(), Some(&fee_payer));
+    assert!(succeeded(&cli, &deployed.signature));
+    let err = cli
+        .run(&[
+            "deploy",
+            memo_1,
+            "--program",
+            program_keypair_path.to_str().unwrap(),
+        ])
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!("Program {} is already deployed, use `upgrade`", program_id)
+    );
+
+    let verified: CliVerifiedProgram = cli
+        .run_as(&["verify", memo_1, "--program", &program_id])
+        .unwrap();
+    assert!(verified.matches);
+    assert_eq!(verified.deployed_trimmed_hash, verified.local_trimmed_hash);
+    let err = cli
+        .run(&["verify", memo_3, "--program", &program_id])
+        .unwrap_err();
+    assert_eq!(exit_code_of(&err), exit_code::PROGRAM_MISMATCH);
+
+    let upgraded: CliProgram = cli
+        .run_as(&["upgrade", memo_3, "--program", &program_id])
+        .unwrap();
+    assert!(succeeded(&cli, &upgraded.signature));
+    let verified: CliVerifiedProgram = cli
+        .run_as(&["verify", memo_3, "--program", &program_id])
+        .unwrap();
+    assert!(verified.matches);
+
+    // Only the new upgrade authority can upgrade, or make the program final
+    let upgrade_authority = Keypair::new();
+    let upgrade_authority_path = write_keypair(&upgrade_authority);
+    let transferred: CliProgram = cli
+        .run_as(&[
+            "set-upgrade-authority",
+            &upgrade_authority.pubkey().to_string(),
+            "--program",
+            &program_id,
+        ])
+        .unwrap();
+    assert_eq!(
+        transferred.upgrade_authority,
+        Some(upgrade_authority.pubkey().to_string())
+    );
+    let err = cli
+        .run(&["upgrade", memo_1, "--program", &program_id])
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Program {} can only be upgraded by {}, pass it with --upgrade-authority",
+            program_id,
+            upgrade_authority.pubkey()
+        )
+    );
+    let finalized: CliProgram = cli
+        .run_as(&[
+            "set-upgrade-authority",
+            "--final",
+            "--upgrade-authority",
+            upgrade_authority_path.to_str().unwrap(),
+            "--program",
+            &program_id,
+        ])
+        .unwrap();
+    assert!(finalized.upgrade_authority.is_none());
+    let verified: CliVerifiedProgram = cli
+        .run_as(&["verify", memo_3, "--program", &program_id])
+        .unwrap();
+    assert!(verified.program.upgrade_authority.is_none());
+
+    for path in [memo_1, memo_3] {
+        std::fs::remove_file(path).unwrap();
+    }
+    std::fs::remove_file(program_keypair_path).unwrap();
+    std::fs::remove_file(upgrade_authority_path).unwrap();
+}
+
+#[test]
+fn test_localnet() {
+    // The bank answers health checks like a running validator. The program
+    // deployed is SPL Memo, which can't initialize a greeting account, so
+    // the bank starts with one.
+    let program_keypair = Keypair::new();
+    let program_id = program_keypair.pubkey();
+    let cli = TestCli::start_with(|_, fee_payer| {
+        let data = GreetingAccount {
+            counter: 0,
+            counter_times_2: 0,
+            authority: *fee_payer,
+            flags: 0,
+        }
+        .try_to_vec()
+        .unwrap();
+        let account = Account {
+            lamports: Rent::default().minimum_balance(data.len()),
+            data,
+            owner: program_id,
+            ..Account::default()
+        };
+        vec![(
+            greeting_account_address(fee_payer, DEFAULT_SEED, &program_id).unwrap(),
+            account,
+        )]
+    });
+    let (memo_1, memo_3) = program_files();
+    let program_keypair_path = write_keypair(&program_keypair);
+    let localnet_up = [
+        "localnet",
+        "up",
+        "--program-file",
+        memo_1.to_str().unwrap(),
+        "--program",
+        program_keypair_path.to_str().unwrap(),
+        "--airdrop",
+        "20",
+    ];
+
+    let localnet: CliLocalnet = cli.run_as(&localnet_up).unwrap();
+    assert_eq!(localnet.validator, "running");
+    // The fee payer's 10 SOL, topped up with 20
+    assert_eq!(localnet.balance, 30.0);
+    assert_eq!(localnet.program.program_id, program_id.to_string());
+    assert!(succeeded(&cli, &localnet.program.signature));
+    assert_eq!(
+        localnet.greeting_account.account.authority,
+        cli.fee_payer.pubkey()
+    );
+    let project_config: toml::Value =
+        toml::from_str(&std::fs::read_to_string(&cli.project_config_path).unwrap()).unwrap();
+    assert_eq!(
+        project_config["program_id"].as_str(),
+        Some(&*program_id.to_string())
+    );
+    assert_eq!(project_config["json_rpc_url"].as_str(), Some("banks"));
+
+    // Nothing to deploy the second time
+    let localnet: CliLocalnet = cli.run_as(&localnet_up).unwrap();
+    assert!(localnet.program.signature.is_none());
+
+    // The program keypair has to be the program's
+    let err = cli
+        .run(&[
+            "localnet",
+            "up",
+            "--program-file",
+            memo_1.to_str().unwrap(),
+            "--program",
+            &program_id.to_string(),
+        ])
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "The program keypair is needed to deploy, pass its path with --program"
+    );
+
+    for path in [memo_1, memo_3, program_keypair_path] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
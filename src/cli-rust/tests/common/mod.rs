@@ -0,0 +1,850 @@
+//! An in-memory cluster for the command tests: `BanksRpc` serves the `Rpc`
+//! methods from a `solana-program-test` bank running the program natively.
+
+// `Rpc` keeps `RpcClient`'s signatures, large `ClientError` and all
+#![allow(clippy::result_large_err)]
+
+use {
+    cli_rust::{
+        app,
+        output::CommandOutput,
+        process_command,
+        rpc::{AccountSubscription, Pubsub, Rpc},
+        CommandResult, Config, Error,
+    },
+    helloworld::{process_instruction, processor::GreeterNonce},
+    helloworld_client::address::{greeter_nonce_address, greeting_account_address, DEFAULT_SEED},
+    solana_account_decoder::{UiAccount, UiAccountEncoding},
+    solana_client::{
+        client_error::{ClientError, ClientErrorKind, Result as ClientResult},
+        pubsub_client::PubsubClientError,
+        rpc_client::GetConfirmedSignaturesForAddress2Config,
+        rpc_config::{
+            RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcSimulateTransactionConfig,
+            RpcTransactionConfig,
+        },
+        rpc_request::RpcError,
+        rpc_response::{
+            Response, RpcConfirmedTransactionStatusWithSignature, RpcResponseContext, RpcResult,
+            RpcSimulateTransactionResult,
+        },
+    },
+    solana_program_test::{
+        processor, tokio::runtime::Runtime, BanksClient, BanksClientError, ProgramTest,
+    },
+    solana_sdk::{
+        account::{Account, AccountSharedData},
+        account_info::AccountInfo,
+        commitment_config::{CommitmentConfig, CommitmentLevel},
+        entrypoint::ProgramResult,
+        hash::Hash,
+        instruction::{CompiledInstruction, InstructionError},
+        message::Message,
+        msg,
+        native_token::sol_to_lamports,
+        program_error::ProgramError,
+        program_pack::Pack,
+        pubkey::Pubkey,
+        rent::Rent,
+        signature::{write_keypair_file, Keypair, Signature, Signer},
+        system_instruction, system_program,
+        transaction::{self, uses_durable_nonce, Transaction, TransactionError},
+    },
+    solana_transaction_status::{
+        option_serializer::OptionSerializer, EncodedConfirmedTransactionWithStatusMeta,
+        EncodedTransaction, EncodedTransactionWithStatusMeta, TransactionBinaryEncoding,
+        UiTransactionStatusMeta,
+    },
+    std::{
+        collections::{BTreeSet, HashMap},
+        convert::TryFrom,
+        path::PathBuf,
+        str::FromStr,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            mpsc, Arc, Mutex,
+        },
+    },
+    tarpc::context,
+};
+
+/// A transaction the bank processed, kept for `history`
+struct ProcessedTransaction {
+    /// As sent, before any re-signing
+    transaction: Transaction,
+    slot: u64,
+    err: Option<TransactionError>,
+    logs: Vec<String>,
+}
+
+/// What `BanksRpc` remembers that a bank can't be asked for
+#[derive(Default)]
+struct Ledger {
+    /// Every account a processed transaction referenced, to scan for
+    /// `getProgramAccounts`
+    addresses: BTreeSet<Pubkey>,
+    /// Oldest first
+    transactions: Vec<ProcessedTransaction>,
+    /// The signature a re-signed transaction was processed under, by the one
+    /// it was sent with
+    signatures: HashMap<Signature, Signature>,
+    /// `accountSubscribe` subscriptions, until their receiver is dropped
+    subscriptions: Vec<(Pubkey, mpsc::Sender<Response<UiAccount>>)>,
+}
+
+/// The error `dump_accounts` fails with
+const DUMPED: u32 = 0xd0d0;
+
+/// Logs the accounts passed to it, one `<lamports> <owner> <executable>
+/// <base64 data>` line each, then fails so that nothing is committed
+fn dump_accounts(_program_id: &Pubkey, accounts: &[AccountInfo], _data: &[u8]) -> ProgramResult {
+    for account in accounts {
+        msg!(
+            "{} {} {} {}",
+            account.lamports(),
+            account.owner,
+            account.executable,
+            base64::encode(&account.data.borrow()[..])
+        );
+    }
+    Err(ProgramError::Custom(DUMPED))
+}
+
+/// An account `dump_accounts` logged, `None` if it doesn't exist
+fn dumped_account(log: &str) -> Option<Account> {
+    let fields: Vec<&str> = log.split(' ').collect();
+    let lamports = fields[0].parse().unwrap();
+    (lamports > 0).then(|| Account {
+        lamports,
+        owner: fields[1].parse().unwrap(),
+        executable: fields[2].parse().unwrap(),
+        data: base64::decode(fields[3]).unwrap(),
+        rent_epoch: 0,
+    })
+}
+
+/// What a transaction did in a simulation
+struct Simulation {
+    err: Option<TransactionError>,
+    logs: Vec<String>,
+    units_consumed: u64,
+    /// The accounts asked for, as the transaction left them if it succeeded
+    accounts: Option<Vec<Option<Account>>>,
+}
+
+/// `Rpc` on top of a `BanksClient`. Clones share the bank, so a test can
+/// look at what a command did.
+///
+/// Banks only report a simulation that failed, and never the accounts it
+/// left, so simulations end in `dump_accounts` to fail on purpose. Nor can
+/// banks process durable nonce transactions, whose blockhash isn't in their
+/// queue: those are checked by simulation, then signed again with a queued
+/// blockhash by the keypairs `add_signer` was given.
+#[derive(Clone)]
+pub struct BanksRpc {
+    runtime: Arc<Runtime>,
+    banks_client: BanksClient,
+    /// Pays for `request_airdrop`
+    faucet: Arc<Keypair>,
+    /// `dump_accounts`
+    dumper_id: Pubkey,
+    /// Keypairs to sign durable nonce transactions again with
+    signers: Arc<Mutex<Vec<Keypair>>>,
+    ledger: Arc<Mutex<Ledger>>,
+}
+
+impl BanksRpc {
+    /// Starts `program_test`, with `dump_accounts` added
+    pub fn start(mut program_test: ProgramTest) -> Self {
+        let dumper_id = Pubkey::new_unique();
+        program_test.add_program("dump_accounts", dumper_id, processor!(dump_accounts));
+        let runtime = Runtime::new().unwrap();
+        let (banks_client, faucet, _) = runtime.block_on(program_test.start());
+        Self {
+            runtime: Arc::new(runtime),
+            banks_client,
+            faucet: Arc::new(faucet),
+            dumper_id,
+            signers: Arc::default(),
+            ledger: Arc::default(),
+        }
+    }
+
+    /// Lets durable nonce transactions signed by `keypair` be processed
+    pub fn add_signer(&self, keypair: &Keypair) {
+        let keypair = Keypair::from_bytes(&keypair.to_bytes()).unwrap();
+        self.signers.lock().unwrap().push(keypair);
+    }
+
+    /// How many `accountSubscribe` subscriptions are open
+    pub fn subscriptions(&self) -> usize {
+        self.ledger.lock().unwrap().subscriptions.len()
+    }
+
+    /// Runs a `BanksClient` request to completion
+    fn block_on<T, F>(&self, request: impl FnOnce(BanksClient) -> F) -> ClientResult<T>
+    where
+        F: std::future::Future<Output = Result<T, BanksClientError>>,
+    {
+        self.runtime
+            .block_on(request(self.banks_client.clone()))
+            .map_err(client_error)
+    }
+
+    fn slot(&self) -> ClientResult<u64> {
+        self.block_on(|mut banks_client| async move { banks_client.get_root_slot().await })
+    }
+
+    /// Simulates `transaction`, signatures aside, reading `addresses` after
+    /// it
+    fn simulate(
+        &self,
+        transaction: &Transaction,
+        addresses: &[Pubkey],
+    ) -> ClientResult<Simulation> {
+        let mut message = transaction.message.clone();
+        // Accounts new to the message go last, with the read-only unsigned ones
+        let mut key_index = |key: &Pubkey| match message.account_keys.iter().position(|k| k == key)
+        {
+            Some(index) => index as u8,
+            None => {
+                message.account_keys.push(*key);
+                message.header.num_readonly_unsigned_accounts += 1;
+                (message.account_keys.len() - 1) as u8
+            }
+        };
+        let dump_accounts = CompiledInstruction {
+            program_id_index: key_index(&self.dumper_id),
+            accounts: addresses.iter().map(&mut key_index).collect(),
+            data: vec![],
+        };
+        message.instructions.push(dump_accounts);
+        let dump_index = message.instructions.len() - 1;
+
+        let transaction = Transaction::new_unsigned(message);
+        let result = self.block_on(|mut banks_client| async move {
+            banks_client
+                .process_transaction_with_preflight_and_commitment_and_context(
+                    context::current(),
+                    transaction,
+                    CommitmentLevel::default(),
+                )
+                .await
+        })?;
+        let (mut logs, units_consumed) = match result.simulation_details {
+            Some(details) => (details.logs, details.units_consumed),
+            None => (vec![], 0),
+        };
+        // `processor!` programs log their invocation and its end twice, as
+        // builtins and again as programs, where a validator logs them once
+        logs.dedup_by(|log, previous| log == previous && !log.starts_with("Program log: "));
+        let dump_invoked = format!("Program {} invoke [1]", self.dumper_id);
+        let dump_logs = logs.split_off(
+            logs.iter()
+                .position(|log| *log == dump_invoked)
+                .unwrap_or(logs.len()),
+        );
+        match result.result {
+            Some(Err(TransactionError::InstructionError(
+                index,
+                InstructionError::Custom(DUMPED),
+            ))) if index as usize == dump_index => Ok(Simulation {
+                err: None,
+                logs,
+                units_consumed,
+                accounts: Some(
+                    dump_logs
+                        .iter()
+                        .filter_map(|log| log.strip_prefix("Program log: "))
+                        .map(dumped_account)
+                        .collect(),
+                ),
+            }),
+            Some(Err(err)) => Ok(Simulation {
+                err: Some(err),
+                logs,
+                units_consumed,
+                accounts: None,
+            }),
+            result => Err(ClientErrorKind::Custom(format!(
+                "Simulation didn't end in dump_accounts: {:?}",
+                result
+            ))
+            .into()),
+        }
+    }
+
+    /// `transaction` with the latest blockhash, signed again
+    fn resign(&self, transaction: &Transaction) -> ClientResult<Transaction> {
+        let keypairs = self.signers.lock().unwrap();
+        let signers = transaction
+            .message
+            .signer_keys()
+            .into_iter()
+            .map(|signer| {
+                keypairs
+                    .iter()
+                    .find(|keypair| keypair.pubkey() == *signer)
+                    .ok_or_else(|| {
+                        ClientError::from(ClientErrorKind::Custom(format!(
+                            "BanksRpc has no keypair to sign for {}",
+                            signer
+                        )))
+                    })
+            })
+            .collect::<ClientResult<Vec<_>>>()?;
+        let mut transaction = transaction.clone();
+        transaction.try_sign(&signers, self.get_latest_blockhash()?)?;
+        Ok(transaction)
+    }
+
+    fn process_transaction(&self, transaction: &Transaction) -> ClientResult<Signature> {
+        // Simulated first for the logs, which banks don't keep
+        let simulation = self.simulate(transaction, &[])?;
+        let processed = if uses_durable_nonce(transaction).is_some() {
+            transaction
+                .verify()
+                .map_err(|err| ClientError::from(ClientErrorKind::TransactionError(err)))?;
+            if let Some(err) = simulation.err {
+                return Err(ClientErrorKind::TransactionError(err).into());
+            }
+            self.resign(transaction)?
+        } else if simulation.err == Some(TransactionError::BlockhashNotFound) {
+            // Banks panic on a blockhash they don't know
+            return Err(
+                ClientErrorKind::TransactionError(TransactionError::BlockhashNotFound).into(),
+            );
+        } else {
+            transaction.clone()
+        };
+        let result = self.block_on(|mut banks_client| {
+            let processed = processed.clone();
+            async move { banks_client.process_transaction(processed).await }
+        });
+        let err = match &result {
+            Ok(()) => None,
+            Err(err) => Some(err.get_transaction_error().ok_or_else(|| {
+                // Never reached the bank
+                ClientError::from(ClientErrorKind::Custom(err.to_string()))
+            })?),
+        };
+        let slot = self.slot()?;
+        let mut ledger = self.ledger.lock().unwrap();
+        ledger
+            .addresses
+            .extend(transaction.message.account_keys.iter().copied());
+        ledger
+            .signatures
+            .insert(transaction.signatures[0], processed.signatures[0]);
+        ledger.transactions.push(ProcessedTransaction {
+            transaction: transaction.clone(),
+            slot,
+            err,
+            logs: simulation.logs,
+        });
+        drop(ledger);
+        result?;
+        self.notify(&processed)?;
+        Ok(transaction.signatures[0])
+    }
+
+    /// Notifies the subscriptions to the accounts `transaction` wrote
+    fn notify(&self, transaction: &Transaction) -> ClientResult<()> {
+        let message = &transaction.message;
+        let written: Vec<Pubkey> = message
+            .account_keys
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| message.is_writable(*index))
+            .map(|(_, pubkey)| *pubkey)
+            .collect();
+        let subscribed: BTreeSet<Pubkey> = self
+            .ledger
+            .lock()
+            .unwrap()
+            .subscriptions
+            .iter()
+            .map(|(pubkey, _)| *pubkey)
+            .filter(|pubkey| written.contains(pubkey))
+            .collect();
+        for pubkey in subscribed {
+            let response = self.get_account_with_commitment(&pubkey, self.commitment())?;
+            // Like a validator, a closed account is notified as an empty one
+            let account = response
+                .value
+                .unwrap_or_else(|| Account::new(0, 0, &system_program::id()));
+            let notification = Response {
+                context: response.context,
+                value: UiAccount::encode(&pubkey, &account, UiAccountEncoding::Base64, None, None),
+            };
+            self.ledger
+                .lock()
+                .unwrap()
+                .subscriptions
+                .retain(|(subscribed, sender)| {
+                    *subscribed != pubkey || sender.send(notification.clone()).is_ok()
+                });
+        }
+        Ok(())
+    }
+}
+
+fn client_error(err: BanksClientError) -> ClientError {
+    match err {
+        BanksClientError::TransactionError(err) | BanksClientError::SimulationError { err, .. } => {
+            ClientErrorKind::TransactionError(err)
+        }
+        err => ClientErrorKind::Custom(err.to_string()),
+    }
+    .into()
+}
+
+impl Rpc for BanksRpc {
+    fn url(&self) -> String {
+        "banks".to_string()
+    }
+
+    fn commitment(&self) -> CommitmentConfig {
+        CommitmentConfig::finalized()
+    }
+
+    fn get_health(&self) -> ClientResult<()> {
+        Ok(())
+    }
+
+    fn get_account(&self, pubkey: &Pubkey) -> ClientResult<Account> {
+        self.get_account_with_commitment(pubkey, self.commitment())?
+            .value
+            .ok_or_else(|| RpcError::ForUser(format!("AccountNotFound: pubkey={}", pubkey)).into())
+    }
+
+    fn get_account_with_commitment(
+        &self,
+        pubkey: &Pubkey,
+        _commitment: CommitmentConfig,
+    ) -> RpcResult<Option<Account>> {
+        let pubkey = *pubkey;
+        let value = self
+            .block_on(|mut banks_client| async move { banks_client.get_account(pubkey).await })?;
+        Ok(Response {
+            context: RpcResponseContext {
+                slot: self.slot()?,
+                api_version: None,
+            },
+            value,
+        })
+    }
+
+    fn get_balance(&self, pubkey: &Pubkey) -> ClientResult<u64> {
+        let pubkey = *pubkey;
+        self.block_on(|mut banks_client| async move { banks_client.get_balance(pubkey).await })
+    }
+
+    fn get_program_accounts_with_config(
+        &self,
+        program_id: &Pubkey,
+        config: RpcProgramAccountsConfig,
+    ) -> ClientResult<Vec<(Pubkey, Account)>> {
+        let filters = config.filters.unwrap_or_default();
+        let addresses = self.ledger.lock().unwrap().addresses.clone();
+        let mut accounts = vec![];
+        for address in addresses {
+            if let Some(account) = self
+                .get_account_with_commitment(&address, self.commitment())?
+                .value
+            {
+                let shared_account = AccountSharedData::from(account.clone());
+                if account.owner == *program_id
+                    && filters.iter().all(|filter| filter.allows(&shared_account))
+                {
+                    accounts.push((address, account));
+                }
+            }
+        }
+        Ok(accounts)
+    }
+
+    fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> ClientResult<u64> {
+        let rent =
+            self.block_on(|mut banks_client| async move { banks_client.get_rent().await })?;
+        Ok(rent.minimum_balance(data_len))
+    }
+
+    fn get_latest_blockhash(&self) -> ClientResult<Hash> {
+        self.block_on(|mut banks_client| async move { banks_client.get_latest_blockhash().await })
+    }
+
+    fn is_blockhash_valid(
+        &self,
+        blockhash: &Hash,
+        _commitment: CommitmentConfig,
+    ) -> ClientResult<bool> {
+        // The bank only prices messages with a blockhash it still accepts
+        let message = Message::new_with_blockhash(&[], Some(&self.faucet.pubkey()), blockhash);
+        let fee = self.block_on(|mut banks_client| async move {
+            banks_client
+                .get_fee_for_message_with_commitment_and_context(
+                    context::current(),
+                    Default::default(),
+                    message,
+                )
+                .await
+        })?;
+        Ok(fee.is_some())
+    }
+
+    fn get_fee_for_message(&self, message: &Message) -> ClientResult<u64> {
+        let message = message.clone();
+        self.block_on(|mut banks_client| async move {
+            banks_client
+                .get_fee_for_message_with_commitment_and_context(
+                    context::current(),
+                    Default::default(),
+                    message,
+                )
+                .await
+        })?
+        .ok_or_else(|| RpcError::ForUser("Blockhash not found".to_string()).into())
+    }
+
+    fn simulate_transaction_with_config(
+        &self,
+        transaction: &Transaction,
+        config: RpcSimulateTransactionConfig,
+    ) -> RpcResult<RpcSimulateTransactionResult> {
+        if config.sig_verify {
+            transaction
+                .verify()
+                .map_err(|err| ClientError::from(ClientErrorKind::TransactionError(err)))?;
+        }
+        let mut transaction = transaction.clone();
+        if config.replace_recent_blockhash {
+            transaction.message.recent_blockhash = self.get_latest_blockhash()?;
+        }
+        let addresses = config
+            .accounts
+            .map(|accounts| {
+                accounts
+                    .addresses
+                    .iter()
+                    .map(|address| Pubkey::from_str(address))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()
+            .map_err(|err| ClientError::from(ClientErrorKind::Custom(err.to_string())))?;
+
+        let simulation = self.simulate(&transaction, addresses.as_deref().unwrap_or_default())?;
+        let dumped = simulation.accounts;
+        let accounts = addresses.map(|addresses| match dumped {
+            Some(accounts) => addresses
+                .iter()
+                .zip(accounts)
+                .map(|(address, account)| {
+                    account.map(|account| {
+                        UiAccount::encode(address, &account, UiAccountEncoding::Base64, None, None)
+                    })
+                })
+                .collect(),
+            None => vec![None; addresses.len()],
+        });
+        Ok(Response {
+            context: RpcResponseContext {
+                slot: self.slot()?,
+                api_version: None,
+            },
+            value: RpcSimulateTransactionResult {
+                err: simulation.err,
+                logs: Some(simulation.logs),
+                accounts,
+                units_consumed: Some(simulation.units_consumed),
+            },
+        })
+    }
+
+    fn send_and_confirm_transaction(&self, transaction: &Transaction) -> ClientResult<Signature> {
+        self.process_transaction(transaction)
+    }
+
+    fn send_and_confirm_transaction_with_spinner(
+        &self,
+        transaction: &Transaction,
+    ) -> ClientResult<Signature> {
+        self.process_transaction(transaction)
+    }
+
+    fn get_signature_status(
+        &self,
+        signature: &Signature,
+    ) -> ClientResult<Option<transaction::Result<()>>> {
+        let signature = self
+            .ledger
+            .lock()
+            .unwrap()
+            .signatures
+            .get(signature)
+            .copied()
+            .unwrap_or(*signature);
+        let status = self.block_on(|mut banks_client| async move {
+            banks_client.get_transaction_status(signature).await
+        })?;
+        Ok(status.map(|status| match status.err {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }))
+    }
+
+    fn poll_for_signature_with_commitment(
+        &self,
+        _signature: &Signature,
+        _commitment: CommitmentConfig,
+    ) -> ClientResult<()> {
+        // Banks confirm transactions before returning
+        Ok(())
+    }
+
+    fn request_airdrop(&self, pubkey: &Pubkey, lamports: u64) -> ClientResult<Signature> {
+        let transaction = Transaction::new_signed_with_payer(
+            &[system_instruction::transfer(
+                &self.faucet.pubkey(),
+                pubkey,
+                lamports,
+            )],
+            Some(&self.faucet.pubkey()),
+            &[self.faucet.as_ref()],
+            self.get_latest_blockhash()?,
+        );
+        self.process_transaction(&transaction)
+    }
+
+    fn get_signatures_for_address_with_config(
+        &self,
+        address: &Pubkey,
+        config: GetConfirmedSignaturesForAddress2Config,
+    ) -> ClientResult<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+        let ledger = self.ledger.lock().unwrap();
+        let newest_first = ledger
+            .transactions
+            .iter()
+            .rev()
+            .filter(|processed| processed.transaction.message.account_keys.contains(address));
+        let mut statuses = vec![];
+        let mut started = config.before.is_none();
+        for processed in newest_first {
+            let signature = processed.transaction.signatures[0];
+            if Some(signature) == config.until {
+                break;
+            }
+            if started {
+                statuses.push(RpcConfirmedTransactionStatusWithSignature {
+                    signature: signature.to_string(),
+                    slot: processed.slot,
+                    err: processed.err.clone(),
+                    memo: None,
+                    block_time: None,
+                    confirmation_status: None,
+                });
+            }
+            started |= Some(signature) == config.before;
+        }
+        statuses.truncate(config.limit.unwrap_or(usize::MAX));
+        Ok(statuses)
+    }
+
+    fn get_transaction_with_config(
+        &self,
+        signature: &Signature,
+        _config: RpcTransactionConfig,
+    ) -> ClientResult<EncodedConfirmedTransactionWithStatusMeta> {
+        let ledger = self.ledger.lock().unwrap();
+        let processed = ledger
+            .transactions
+            .iter()
+            .find(|processed| processed.transaction.signatures[0] == *signature)
+            .ok_or_else(|| {
+                ClientError::from(RpcError::ForUser("Transaction not found".to_string()))
+            })?;
+        let bytes = bincode::serialize(&processed.transaction)
+            .map_err(|e| ClientError::from(ClientErrorKind::Custom(e.to_string())))?;
+        Ok(EncodedConfirmedTransactionWithStatusMeta {
+            slot: processed.slot,
+            transaction: EncodedTransactionWithStatusMeta {
+                transaction: EncodedTransaction::Binary(
+                    base64::encode(bytes),
+                    TransactionBinaryEncoding::Base64,
+                ),
+                meta: Some(UiTransactionStatusMeta {
+                    err: processed.err.clone(),
+                    status: processed.err.clone().map_or(Ok(()), Err),
+                    fee: 0,
+                    pre_balances: vec![],
+                    post_balances: vec![],
+                    inner_instructions: OptionSerializer::None,
+                    log_messages: OptionSerializer::Some(processed.logs.clone()),
+                    pre_token_balances: OptionSerializer::None,
+                    post_token_balances: OptionSerializer::None,
+                    rewards: OptionSerializer::None,
+                    loaded_addresses: OptionSerializer::Skip,
+                }),
+                version: None,
+            },
+            block_time: None,
+        })
+    }
+}
+
+impl Pubsub for BanksRpc {
+    fn account_subscribe(
+        &self,
+        pubkey: &Pubkey,
+        _config: Option<RpcAccountInfoConfig>,
+    ) -> Result<AccountSubscription, PubsubClientError> {
+        let (sender, receiver) = mpsc::channel();
+        self.ledger
+            .lock()
+            .unwrap()
+            .subscriptions
+            .push((*pubkey, sender));
+        Ok(AccountSubscription {
+            notifications: Box::new(receiver.into_iter()),
+            // The sender goes with the receiver
+            shutdown: Box::new(|| ()),
+        })
+    }
+}
+
+/// The CLI against a fresh bank with the program deployed
+pub struct TestCli {
+    pub rpc: BanksRpc,
+    pub program_id: Pubkey,
+    pub fee_payer: Keypair,
+    fee_payer_path: PathBuf,
+    /// A second, unfunded key, for relayed greetings
+    pub greeter: Keypair,
+    /// Where `localnet up` records its setup, rather than the working
+    /// directory
+    pub project_config_path: PathBuf,
+}
+
+impl TestCli {
+    pub fn start() -> Self {
+        Self::start_with(|_, _| vec![])
+    }
+
+    /// Starts with the accounts `accounts` returns for the program id and
+    /// fee payer, such as greeting accounts in a layout the program no longer
+    /// creates
+    pub fn start_with(accounts: impl FnOnce(&Pubkey, &Pubkey) -> Vec<(Pubkey, Account)>) -> Self {
+        let program_id = Pubkey::new_unique();
+        let mut program_test =
+            ProgramTest::new("helloworld", program_id, processor!(process_instruction));
+        let fee_payer = Keypair::new();
+        let greeter = Keypair::new();
+        program_test.add_account(
+            fee_payer.pubkey(),
+            Account::new(sol_to_lamports(10.0), 0, &system_program::id()),
+        );
+        // Native `processor!` programs can't grow account data through CPI,
+        // so the greeter nonce accounts exist up front
+        for greeter in [&fee_payer, &greeter] {
+            program_test.add_account(
+                greeter_nonce_address(&program_id, &greeter.pubkey()),
+                Account {
+                    lamports: Rent::default().minimum_balance(GreeterNonce::LEN),
+                    data: vec![0; GreeterNonce::LEN],
+                    owner: program_id,
+                    ..Account::default()
+                },
+            );
+        }
+
+        let accounts = accounts(&program_id, &fee_payer.pubkey());
+        for (address, account) in &accounts {
+            program_test.add_account(*address, account.clone());
+        }
+
+        let rpc = BanksRpc::start(program_test);
+        rpc.add_signer(&fee_payer);
+        rpc.add_signer(&greeter);
+        let fee_payer_path = write_keypair(&fee_payer);
+        rpc.ledger
+            .lock()
+            .unwrap()
+            .addresses
+            .extend(accounts.iter().map(|(address, _)| *address));
+        Self {
+            rpc,
+            program_id,
+            fee_payer,
+            fee_payer_path,
+            greeter,
+            project_config_path: temp_path("toml"),
+        }
+    }
+
+    /// Runs `cli-rust` with `args`, for `program_id`, paid for by `fee_payer`
+    /// and with the default seed unless `args` pass `--program`,
+    /// `--fee-payer` or `--seed`
+    pub fn run(&self, args: &[&str]) -> CommandResult {
+        let program_id = self.program_id.to_string();
+        let mut command_line = vec!["cli-rust"];
+        command_line.extend_from_slice(args);
+        command_line.extend_from_slice(&[
+            // Neither the `solana` CLI config nor a project config apply
+            "--config",
+            "/dev/null/config.yml",
+        ]);
+        if !args.contains(&"--program") {
+            command_line.extend_from_slice(&["--program", &program_id]);
+        }
+        if !args.contains(&"--seed") {
+            command_line.extend_from_slice(&["--seed", DEFAULT_SEED]);
+        }
+        if !args.contains(&"--fee-payer") {
+            command_line.extend_from_slice(&["--fee-payer", self.fee_payer_path.to_str().unwrap()]);
+        }
+        let app_matches = app().get_matches_from_safe(command_line)?;
+        let (sub_command, matches) = app_matches.subcommand();
+        let matches = matches.unwrap();
+        let config = Config::from_matches(sub_command, matches)?
+            .with_rpc_client(Box::new(self.rpc.clone()))
+            .with_pubsub_client(Box::new(self.rpc.clone()))
+            .with_project_config_path(self.project_config_path.to_str().unwrap().to_string());
+        process_command(&config, sub_command, matches)
+    }
+
+    /// `run`, for a command whose output is a `T`
+    pub fn run_as<T: TryFrom<CommandOutput>>(&self, args: &[&str]) -> Result<T, Error> {
+        let output = self.run(args)?;
+        Ok(T::try_from(output).unwrap_or_else(|_| panic!("unexpected output of {:?}", args)))
+    }
+
+    /// The greeting account `create` makes for the fee payer
+    pub fn greeting_account_id(&self) -> Pubkey {
+        greeting_account_address(&self.fee_payer.pubkey(), DEFAULT_SEED, &self.program_id).unwrap()
+    }
+}
+
+impl Drop for TestCli {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.fee_payer_path);
+        let _ = std::fs::remove_file(&self.project_config_path);
+    }
+}
+
+/// Writes `keypair` to a file no other test uses, for keypair arguments
+pub fn write_keypair(keypair: &Keypair) -> PathBuf {
+    let path = temp_path("json");
+    write_keypair_file(keypair, &path).unwrap();
+    path
+}
+
+/// A file path with `extension` no other test uses
+pub fn temp_path(extension: &str) -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    std::env::temp_dir().join(format!(
+        "cli-rust-test-{}-{}.{}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed),
+        extension
+    ))
+}
@@ -0,0 +1,93 @@
+//! Runs `watch` against a local `solana-test-validator` with the program
+//! deployed (`npm run build:program-rust` and `solana program deploy`):
+//!
+//! ```text
+//! cargo test -p cli-rust -- --ignored
+//! ```
+
+use serde_json::Value;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    native_token::LAMPORTS_PER_SOL,
+    signature::{write_keypair_file, Keypair, Signer},
+};
+use std::{
+    io::{BufRead, BufReader},
+    path::Path,
+    process::{Command, Stdio},
+    thread::sleep,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const URL: &str = "http://127.0.0.1:8899";
+
+fn cli(fee_payer: &Path, seed: &str) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_cli-rust"));
+    command
+        .args(["--url", URL, "--commitment", "confirmed", "--seed", seed])
+        .arg("--fee-payer")
+        .arg(fee_payer);
+    command
+}
+
+fn run(command: &mut Command) {
+    let output = command.output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+#[ignore = "needs a local solana-test-validator with the program deployed"]
+fn test_watch() {
+    let rpc_client = RpcClient::new_with_commitment(URL, CommitmentConfig::confirmed());
+    let fee_payer = Keypair::new();
+    let signature = rpc_client
+        .request_airdrop(&fee_payer.pubkey(), LAMPORTS_PER_SOL)
+        .unwrap();
+    while !rpc_client.confirm_transaction(&signature).unwrap() {
+        sleep(Duration::from_millis(200));
+    }
+
+    let fee_payer_path = std::env::temp_dir().join(format!("{}.json", fee_payer.pubkey()));
+    write_keypair_file(&fee_payer, &fee_payer_path).unwrap();
+    let seed = format!(
+        "watch-{}",
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis()
+    );
+    run(cli(&fee_payer_path, &seed).arg("create"));
+
+    let mut watch = cli(&fee_payer_path, &seed)
+        .args(["--output", "json-compact", "watch", "--count", "2"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut lines = BufReader::new(watch.stdout.take().unwrap()).lines();
+    let mut updates = vec![serde_json::from_str::<Value>(&lines.next().unwrap().unwrap()).unwrap()];
+
+    for greetings in ["2", "3"] {
+        run(cli(&fee_payer_path, &seed).args(["greet", "--greetings", greetings]));
+    }
+    for line in lines {
+        updates.push(serde_json::from_str(&line.unwrap()).unwrap());
+    }
+    assert!(watch.wait().unwrap().success());
+    std::fs::remove_file(&fee_payer_path).unwrap();
+
+    let counters: Vec<_> = updates
+        .iter()
+        .map(|update| update["counter"].clone())
+        .collect();
+    let deltas: Vec<_> = updates
+        .iter()
+        .map(|update| update["delta"].clone())
+        .collect();
+    assert_eq!(counters, [0, 2, 5]);
+    assert_eq!(deltas, [Value::Null, 2.into(), 3.into()]);
+}
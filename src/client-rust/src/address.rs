@@ -0,0 +1,52 @@
+//! The addresses of the program's accounts, derived the one way every client
+//! has to agree on.
+
+use {
+    helloworld::processor::find_greeter_nonce_address,
+    solana_sdk::pubkey::{Pubkey, PubkeyError},
+};
+
+/// Seed of the greeting account a base creates when none is chosen
+pub const DEFAULT_SEED: &str = "hello";
+
+/// The greeting account `base` creates with `seed`
+pub fn greeting_account_address(
+    base: &Pubkey,
+    seed: &str,
+    program_id: &Pubkey,
+) -> Result<Pubkey, PubkeyError> {
+    Pubkey::create_with_seed(base, seed, program_id)
+}
+
+/// The account holding the greeting nonces `greeter` used
+pub fn greeter_nonce_address(program_id: &Pubkey, greeter: &Pubkey) -> Pubkey {
+    find_greeter_nonce_address(program_id, greeter).0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_addresses() {
+        let program_id = Pubkey::new_unique();
+        let base = Pubkey::new_unique();
+        let greeting_account_id =
+            greeting_account_address(&base, DEFAULT_SEED, &program_id).unwrap();
+        assert_ne!(
+            greeting_account_id,
+            greeting_account_address(&base, "other", &program_id).unwrap()
+        );
+        assert_eq!(
+            greeting_account_address(&base, &"a".repeat(33), &program_id),
+            Err(PubkeyError::MaxSeedLengthExceeded)
+        );
+
+        let nonce_account_id = greeter_nonce_address(&program_id, &base);
+        assert!(!nonce_account_id.is_on_curve());
+        assert_ne!(
+            nonce_account_id,
+            greeter_nonce_address(&program_id, &Pubkey::new_unique())
+        );
+    }
+}
@@ -0,0 +1,524 @@
+use {
+    crate::{
+        address::greeting_account_address,
+        error::{Error, Result},
+    },
+    futures_util::StreamExt,
+    helloworld::{
+        instruction::{close, init_greeting, initialize, migrate},
+        processor::GreetingAccount,
+    },
+    solana_account_decoder::{UiAccount, UiAccountEncoding},
+    solana_client::{
+        nonblocking::{
+            pubsub_client::{PubsubClient, PubsubClientError},
+            rpc_client::RpcClient,
+        },
+        rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+        rpc_filter::{Memcmp, MemcmpEncodedBytes, RpcFilterType},
+        rpc_response::Response as RpcResponse,
+    },
+    solana_sdk::{
+        account::Account,
+        commitment_config::CommitmentConfig,
+        instruction::Instruction,
+        program_pack::Pack,
+        pubkey::Pubkey,
+        signature::Signature,
+        signer::{unique_signers, Signer},
+        system_instruction::create_account_with_seed,
+        transaction::Transaction,
+    },
+    tokio::sync::{mpsc, oneshot},
+};
+
+/// Creates, greets, reads, lists, migrates, closes and watches the greeting
+/// accounts of one deployment of the program
+pub struct GreetingClient {
+    rpc_client: RpcClient,
+    websocket_url: String,
+    program_id: Pubkey,
+}
+
+impl GreetingClient {
+    /// A client of the program at `program_id` on the cluster at
+    /// `json_rpc_url`, watching accounts over its default websocket
+    pub fn new(json_rpc_url: &str, commitment: CommitmentConfig, program_id: Pubkey) -> Self {
+        Self::new_with_rpc_client(
+            RpcClient::new_with_commitment(json_rpc_url.to_string(), commitment),
+            solana_cli_config::Config::compute_websocket_url(json_rpc_url),
+            program_id,
+        )
+    }
+
+    pub fn new_with_rpc_client(
+        rpc_client: RpcClient,
+        websocket_url: String,
+        program_id: Pubkey,
+    ) -> Self {
+        Self {
+            rpc_client,
+            websocket_url,
+            program_id,
+        }
+    }
+
+    pub fn rpc_client(&self) -> &RpcClient {
+        &self.rpc_client
+    }
+
+    pub fn program_id(&self) -> &Pubkey {
+        &self.program_id
+    }
+
+    /// Creates and initializes the greeting account `payer` derives from
+    /// `seed`, returning its address
+    pub async fn create_account(
+        &self,
+        payer: &(dyn Signer + Sync),
+        seed: &str,
+        authority: &Pubkey,
+        flags: u8,
+    ) -> Result<(Pubkey, Signature)> {
+        let greeting_account_id =
+            greeting_account_address(&payer.pubkey(), seed, &self.program_id)?;
+        if self.fetch_account(&greeting_account_id).await?.is_some() {
+            return Err(Error::AccountExists(greeting_account_id));
+        }
+        let lamports = self
+            .rpc_client
+            .get_minimum_balance_for_rent_exemption(GreetingAccount::LEN)
+            .await?;
+        let signature = self
+            .send(
+                payer,
+                &[
+                    create_account_with_seed(
+                        &payer.pubkey(),
+                        &greeting_account_id,
+                        &payer.pubkey(),
+                        seed,
+                        lamports,
+                        GreetingAccount::LEN as u64,
+                        &self.program_id,
+                    ),
+                    initialize(
+                        self.program_id,
+                        greeting_account_id,
+                        payer.pubkey(),
+                        seed.to_string(),
+                        *authority,
+                        flags,
+                    ),
+                ],
+                &[],
+            )
+            .await?;
+        Ok((greeting_account_id, signature))
+    }
+
+    /// Adds `num_greetings` greetings to `greeting_account_id`
+    pub async fn greet(
+        &self,
+        payer: &(dyn Signer + Sync),
+        greeting_account_id: &Pubkey,
+        num_greetings: u32,
+        greeting_string: String,
+    ) -> Result<Signature> {
+        self.get_account(greeting_account_id).await?;
+        self.send(
+            payer,
+            &[init_greeting(
+                self.program_id,
+                *greeting_account_id,
+                num_greetings,
+                greeting_string,
+            )],
+            &[],
+        )
+        .await
+    }
+
+    pub async fn get_account(&self, greeting_account_id: &Pubkey) -> Result<GreetingAccount> {
+        let account = self
+            .fetch_account(greeting_account_id)
+            .await?
+            .ok_or(Error::AccountNotFound(*greeting_account_id))?;
+        decode_greeting_account(&self.program_id, greeting_account_id, &account)
+    }
+
+    /// The program's greeting accounts, only those of `authority` if given.
+    /// Legacy accounts have no authority and are only listed without one.
+    pub async fn list_accounts(
+        &self,
+        authority: Option<&Pubkey>,
+    ) -> Result<Vec<(Pubkey, GreetingAccount)>> {
+        let mut data_sizes = vec![GreetingAccount::LEN];
+        let mut memcmps = vec![];
+        match authority {
+            Some(authority) => memcmps.push(RpcFilterType::Memcmp(Memcmp {
+                offset: GreetingAccount::AUTHORITY_OFFSET,
+                bytes: MemcmpEncodedBytes::Base58(authority.to_string()),
+                encoding: None,
+            })),
+            None => data_sizes.push(GreetingAccount::LEGACY_LEN),
+        }
+        let mut accounts = vec![];
+        for data_size in data_sizes {
+            let mut filters = vec![RpcFilterType::DataSize(data_size as u64)];
+            filters.extend(memcmps.iter().cloned());
+            accounts.extend(
+                self.rpc_client
+                    .get_program_accounts_with_config(
+                        &self.program_id,
+                        RpcProgramAccountsConfig {
+                            filters: Some(filters),
+                            account_config: RpcAccountInfoConfig {
+                                encoding: Some(UiAccountEncoding::Base64),
+                                ..RpcAccountInfoConfig::default()
+                            },
+                            ..RpcProgramAccountsConfig::default()
+                        },
+                    )
+                    .await?,
+            );
+        }
+
+        // Accounts of the right size that don't decode aren't greeting accounts
+        Ok(accounts
+            .into_iter()
+            .filter_map(|(address, account)| {
+                let greeting_account = GreetingAccount::unpack_versioned(&account.data).ok()?;
+                Some((address, greeting_account))
+            })
+            .collect())
+    }
+
+    /// Grows the legacy greeting account `payer` derived from `seed` to the
+    /// current layout, giving it `authority` and `flags`, and returns its
+    /// address. `payer` tops up its rent.
+    pub async fn migrate_account(
+        &self,
+        payer: &(dyn Signer + Sync),
+        seed: &str,
+        authority: &Pubkey,
+        flags: u8,
+    ) -> Result<(Pubkey, Signature)> {
+        let greeting_account_id =
+            greeting_account_address(&payer.pubkey(), seed, &self.program_id)?;
+        self.get_account(&greeting_account_id).await?;
+        let signature = self
+            .send(
+                payer,
+                &[migrate(
+                    self.program_id,
+                    greeting_account_id,
+                    payer.pubkey(),
+                    payer.pubkey(),
+                    seed.to_string(),
+                    *authority,
+                    flags,
+                )],
+                &[],
+            )
+            .await?;
+        Ok((greeting_account_id, signature))
+    }
+
+    /// Closes `greeting_account_id`, sending its lamports to `recipient`
+    pub async fn close(
+        &self,
+        payer: &(dyn Signer + Sync),
+        authority: &(dyn Signer + Sync),
+        greeting_account_id: &Pubkey,
+        recipient: &Pubkey,
+    ) -> Result<Signature> {
+        self.get_account(greeting_account_id).await?;
+        self.send(
+            payer,
+            &[close(
+                self.program_id,
+                *greeting_account_id,
+                authority.pubkey(),
+                *recipient,
+            )],
+            &[authority],
+        )
+        .await
+    }
+
+    /// Watches `greeting_account_id` over the websocket until the
+    /// subscription is dropped or the account is closed
+    pub async fn subscribe(&self, greeting_account_id: &Pubkey) -> Result<GreetingSubscription> {
+        let pubsub_client = PubsubClient::new(&self.websocket_url).await?;
+        let greeting_account_id = *greeting_account_id;
+        let program_id = self.program_id;
+        let config = RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            commitment: Some(self.rpc_client.commitment()),
+            ..RpcAccountInfoConfig::default()
+        };
+        let (subscribed_sender, subscribed) = oneshot::channel();
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        // The notifications borrow the pubsub client, so both live in the task
+        tokio::spawn(async move {
+            let (mut notifications, unsubscribe) = match pubsub_client
+                .account_subscribe(&greeting_account_id, Some(config))
+                .await
+            {
+                Ok(subscription) => {
+                    let _ = subscribed_sender.send(Ok(()));
+                    subscription
+                }
+                Err(err) => {
+                    let _ = subscribed_sender.send(Err(Error::from(err)));
+                    return;
+                }
+            };
+            loop {
+                let notification = tokio::select! {
+                    notification = notifications.next() => notification,
+                    _ = sender.closed() => break,
+                };
+                let update = match notification {
+                    Some(notification) => {
+                        GreetingUpdate::decode(&program_id, &greeting_account_id, notification)
+                    }
+                    None => break,
+                };
+                let closed = matches!(
+                    update,
+                    Ok(GreetingUpdate {
+                        greeting_account: None,
+                        ..
+                    })
+                );
+                if sender.send(update).is_err() || closed {
+                    break;
+                }
+            }
+            drop(notifications);
+            unsubscribe().await;
+            let _ = pubsub_client.shutdown().await;
+        });
+
+        match subscribed.await {
+            Ok(subscribed) => subscribed?,
+            Err(_) => {
+                return Err(PubsubClientError::ConnectionClosed(
+                    "subscription task ended".to_string(),
+                )
+                .into())
+            }
+        }
+        Ok(GreetingSubscription { receiver })
+    }
+
+    async fn fetch_account(&self, address: &Pubkey) -> Result<Option<Account>> {
+        Ok(self
+            .rpc_client
+            .get_account_with_commitment(address, self.rpc_client.commitment())
+            .await?
+            .value)
+    }
+
+    /// Signs `instructions` with `payer` and `signers` and sends them,
+    /// decoding the program's errors if they fail
+    async fn send(
+        &self,
+        payer: &(dyn Signer + Sync),
+        instructions: &[Instruction],
+        signers: &[&(dyn Signer + Sync)],
+    ) -> Result<Signature> {
+        let blockhash = self.rpc_client.get_latest_blockhash().await?;
+        let mut transaction = Transaction::new_with_payer(instructions, Some(&payer.pubkey()));
+        {
+            let mut all_signers: Vec<&dyn Signer> = vec![payer];
+            all_signers.extend(signers.iter().map(|signer| *signer as &dyn Signer));
+            transaction.try_sign(&unique_signers(all_signers), blockhash)?;
+        }
+        self.rpc_client
+            .send_and_confirm_transaction(&transaction)
+            .await
+            .map_err(|err| Error::from_transaction(err, &transaction, &self.program_id))
+    }
+}
+
+/// A greeting account as of `slot`, `None` once it is closed
+#[derive(Debug, Clone, PartialEq)]
+pub struct GreetingUpdate {
+    pub slot: u64,
+    pub greeting_account: Option<GreetingAccount>,
+}
+
+impl GreetingUpdate {
+    fn decode(
+        program_id: &Pubkey,
+        greeting_account_id: &Pubkey,
+        notification: RpcResponse<UiAccount>,
+    ) -> Result<Self> {
+        let account = notification
+            .value
+            .decode::<Account>()
+            .ok_or(Error::InvalidAccount(*greeting_account_id))?;
+        let greeting_account = if account.lamports == 0 {
+            None
+        } else {
+            Some(decode_greeting_account(
+                program_id,
+                greeting_account_id,
+                &account,
+            )?)
+        };
+        Ok(Self {
+            slot: notification.context.slot,
+            greeting_account,
+        })
+    }
+}
+
+/// Updates of a greeting account, unsubscribing when dropped
+pub struct GreetingSubscription {
+    receiver: mpsc::UnboundedReceiver<Result<GreetingUpdate>>,
+}
+
+impl GreetingSubscription {
+    /// The next update, `None` after the account is closed or the websocket
+    /// disconnects
+    pub async fn next(&mut self) -> Option<Result<GreetingUpdate>> {
+        self.receiver.recv().await
+    }
+}
+
+fn decode_greeting_account(
+    program_id: &Pubkey,
+    greeting_account_id: &Pubkey,
+    account: &Account,
+) -> Result<GreetingAccount> {
+    if account.owner != *program_id {
+        return Err(Error::InvalidAccount(*greeting_account_id));
+    }
+    GreetingAccount::unpack_versioned(&account.data)
+        .map_err(|_| Error::InvalidAccount(*greeting_account_id))
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        borsh::BorshSerialize,
+        solana_client::rpc_response::RpcResponseContext,
+        solana_sdk::{signature::Keypair, system_program},
+    };
+
+    fn notification(
+        slot: u64,
+        greeting_account_id: &Pubkey,
+        account: &Account,
+    ) -> RpcResponse<UiAccount> {
+        RpcResponse {
+            context: RpcResponseContext {
+                slot,
+                api_version: None,
+            },
+            value: UiAccount::encode(
+                greeting_account_id,
+                account,
+                UiAccountEncoding::Base64,
+                None,
+                None,
+            ),
+        }
+    }
+
+    #[test]
+    fn test_decode_update() {
+        let program_id = Pubkey::new_unique();
+        let greeting_account_id = Pubkey::new_unique();
+        let greeting_account = GreetingAccount {
+            counter: 2,
+            counter_times_2: 4,
+            authority: Pubkey::new_unique(),
+            flags: 0,
+        };
+        let mut account = Account::new(1, GreetingAccount::LEN, &program_id);
+        account.data = greeting_account.try_to_vec().unwrap();
+
+        let update = GreetingUpdate::decode(
+            &program_id,
+            &greeting_account_id,
+            notification(5, &greeting_account_id, &account),
+        )
+        .unwrap();
+        assert_eq!(
+            update,
+            GreetingUpdate {
+                slot: 5,
+                greeting_account: Some(greeting_account),
+            }
+        );
+
+        let closed = Account::new(0, 0, &system_program::id());
+        let update = GreetingUpdate::decode(
+            &program_id,
+            &greeting_account_id,
+            notification(6, &greeting_account_id, &closed),
+        )
+        .unwrap();
+        assert_eq!(update.greeting_account, None);
+
+        account.owner = Pubkey::new_unique();
+        assert!(matches!(
+            GreetingUpdate::decode(
+                &program_id,
+                &greeting_account_id,
+                notification(7, &greeting_account_id, &account),
+            ),
+            Err(Error::InvalidAccount(_))
+        ));
+    }
+
+    #[test]
+    fn test_decode_legacy_account() {
+        let program_id = Pubkey::new_unique();
+        let greeting_account_id = Pubkey::new_unique();
+        let mut account = Account::new(1, GreetingAccount::LEGACY_LEN, &program_id);
+        account.data = (3u32, 6u32).try_to_vec().unwrap();
+
+        let greeting_account =
+            decode_greeting_account(&program_id, &greeting_account_id, &account).unwrap();
+        assert_eq!(
+            greeting_account,
+            GreetingAccount {
+                counter: 3,
+                counter_times_2: 6,
+                authority: Pubkey::default(),
+                flags: 0,
+            }
+        );
+
+        account.data.truncate(4);
+        assert!(matches!(
+            decode_greeting_account(&program_id, &greeting_account_id, &account),
+            Err(Error::InvalidAccount(_))
+        ));
+    }
+
+    #[test]
+    fn test_futures_are_send() {
+        fn assert_send<T: Send>(_: T) {}
+        let client = GreetingClient::new(
+            "http://localhost:8899",
+            CommitmentConfig::confirmed(),
+            Pubkey::new_unique(),
+        );
+        let payer = Keypair::new();
+        let greeting_account_id = Pubkey::new_unique();
+        assert_send(client.create_account(&payer, "hello", &payer.pubkey(), 0));
+        assert_send(client.greet(&payer, &greeting_account_id, 1, String::new()));
+        assert_send(client.migrate_account(&payer, "hello", &payer.pubkey(), 0));
+        assert_send(client.close(&payer, &payer, &greeting_account_id, &payer.pubkey()));
+        assert_send(client.subscribe(&greeting_account_id));
+    }
+}
@@ -0,0 +1,142 @@
+//! Errors of `GreetingClient`, with the program's failures decoded.
+
+use {
+    helloworld::error::GreetingError,
+    solana_client::{client_error::ClientError, nonblocking::pubsub_client::PubsubClientError},
+    solana_sdk::{
+        instruction::InstructionError,
+        pubkey::{Pubkey, PubkeyError},
+        signer::SignerError,
+        transaction::{Transaction, TransactionError},
+    },
+    thiserror::Error,
+};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Greeting account {0} not found")]
+    AccountNotFound(Pubkey),
+    #[error("Greeting account {0} already exists")]
+    AccountExists(Pubkey),
+    /// The account isn't owned by the program or doesn't hold a
+    /// `GreetingAccount`
+    #[error("{0} is not a greeting account")]
+    InvalidAccount(Pubkey),
+    /// Instruction `0` of the transaction, one of the program's, failed
+    #[error("Instruction {0} failed: {1}")]
+    Program(u8, GreetingError),
+    /// The cluster rejected the transaction, or an instruction of another
+    /// program failed
+    #[error("Transaction failed: {0}")]
+    Transaction(TransactionError),
+    #[error(transparent)]
+    Address(#[from] PubkeyError),
+    #[error(transparent)]
+    Signer(#[from] SignerError),
+    // Boxed, they are several times the size of the other variants
+    #[error(transparent)]
+    Rpc(Box<ClientError>),
+    #[error(transparent)]
+    Pubsub(Box<PubsubClientError>),
+}
+
+impl Error {
+    /// The error `transaction` failed with, decoded to a `GreetingError` if
+    /// an instruction of `program_id` failed with one
+    pub(crate) fn from_transaction(
+        err: ClientError,
+        transaction: &Transaction,
+        program_id: &Pubkey,
+    ) -> Self {
+        let transaction_error = match err.get_transaction_error() {
+            Some(transaction_error) => transaction_error,
+            None => return err.into(),
+        };
+        if let TransactionError::InstructionError(index, InstructionError::Custom(code)) =
+            transaction_error
+        {
+            let message = &transaction.message;
+            let instruction_program_id =
+                message
+                    .instructions
+                    .get(index as usize)
+                    .and_then(|instruction| {
+                        message
+                            .account_keys
+                            .get(instruction.program_id_index as usize)
+                    });
+            if instruction_program_id == Some(program_id) {
+                if let Some(greeting_error) = GreetingError::from_code(code) {
+                    return Error::Program(index, greeting_error);
+                }
+            }
+        }
+        Error::Transaction(transaction_error)
+    }
+}
+
+impl From<ClientError> for Error {
+    fn from(err: ClientError) -> Self {
+        match err.get_transaction_error() {
+            Some(transaction_error) => Error::Transaction(transaction_error),
+            None => Error::Rpc(Box::new(err)),
+        }
+    }
+}
+
+impl From<PubsubClientError> for Error {
+    fn from(err: PubsubClientError) -> Self {
+        Error::Pubsub(Box::new(err))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        helloworld::instruction::init_greeting,
+        solana_client::client_error::ClientErrorKind,
+        solana_sdk::{system_instruction, system_program},
+    };
+
+    #[test]
+    fn test_from_transaction() {
+        let program_id = Pubkey::new_unique();
+        let payer = Pubkey::new_unique();
+        let greeting_account_id = Pubkey::new_unique();
+        let transaction = Transaction::new_with_payer(
+            &[
+                system_instruction::transfer(&payer, &greeting_account_id, 1),
+                init_greeting(program_id, greeting_account_id, 1, String::new()),
+            ],
+            Some(&payer),
+        );
+        let failed = |index, code| {
+            let err = ClientError::from(ClientErrorKind::TransactionError(
+                TransactionError::InstructionError(index, InstructionError::Custom(code)),
+            ));
+            Error::from_transaction(err, &transaction, &program_id)
+        };
+
+        assert!(matches!(
+            failed(1, GreetingError::NonceReused as u32),
+            Error::Program(1, GreetingError::NonceReused)
+        ));
+        // Custom errors of other programs aren't greeting errors
+        assert!(matches!(
+            failed(0, 1),
+            Error::Transaction(TransactionError::InstructionError(0, _))
+        ));
+        assert!(matches!(
+            failed(1, u32::MAX),
+            Error::Transaction(TransactionError::InstructionError(1, _))
+        ));
+        let err = ClientError::from(ClientErrorKind::Custom("timed out".to_string()));
+        assert!(matches!(
+            Error::from_transaction(err, &transaction, &system_program::id()),
+            Error::Rpc(_)
+        ));
+    }
+}
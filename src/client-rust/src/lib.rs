@@ -0,0 +1,16 @@
+//! Async client for the greeting program.
+//!
+//! `GreetingClient` creates, greets, reads, lists, migrates, closes and
+//! watches greeting accounts over the nonblocking RPC and pubsub clients. Failed
+//! transactions come back as `Error::Program` with the `GreetingError` the
+//! program failed with, and `address` derives the program's accounts.
+
+pub mod address;
+mod client;
+pub mod error;
+
+pub use {
+    client::{GreetingClient, GreetingSubscription, GreetingUpdate},
+    error::{Error, Result},
+    helloworld::{error::GreetingError, processor::GreetingAccount},
+};
@@ -16,6 +16,13 @@ import path from 'path';
 import * as borsh from 'borsh';
 
 import {getPayer, getRpcUrl, createKeypairFromFile} from './utils';
+import {
+  GREETING_ACCOUNT_SIZE,
+  GreetingAccount,
+  GreetingInstruction,
+  InitGreetingArgs,
+  SCHEMA,
+} from './schema';
 
 /**
  * Connection to the network
@@ -56,46 +63,6 @@ const PROGRAM_SO_PATH = path.join(PROGRAM_PATH, 'helloworld.so');
  */
 const PROGRAM_KEYPAIR_PATH = path.join(PROGRAM_PATH, 'helloworld-keypair.json');
 
-/**
- * The state of a greeting account managed by the hello world program
- */
-class GreetingAccount {
-  counter = 0;
-  counter_times_2 = 0;
-  constructor(
-    fields: {counter: number; counter_times_2: number} | undefined = undefined,
-  ) {
-    if (fields) {
-      this.counter = fields.counter;
-      this.counter_times_2 = fields.counter_times_2;
-    }
-  }
-}
-
-/**
- * Borsh schema definition for greeting accounts
- */
-const GreetingSchema = new Map([
-  [
-    GreetingAccount,
-    {
-      kind: 'struct',
-      fields: [
-        ['counter', 'u32'],
-        ['counter_times_2', 'u32'],
-      ],
-    },
-  ],
-]);
-
-/**
- * The expected size of each greeting account.
- */
-const GREETING_SIZE = borsh.serialize(
-  GreetingSchema,
-  new GreetingAccount(),
-).length;
-
 /**
  * Establish a connection to the cluster
  */
@@ -115,7 +82,9 @@ export async function establishPayer(): Promise<void> {
     const {feeCalculator} = await connection.getRecentBlockhash();
 
     // Calculate the cost to fund the greeter account
-    fees += await connection.getMinimumBalanceForRentExemption(GREETING_SIZE);
+    fees += await connection.getMinimumBalanceForRentExemption(
+      GREETING_ACCOUNT_SIZE,
+    );
 
     // Calculate the cost of sending transactions
     fees += feeCalculator.lamportsPerSignature * 100; // wag
@@ -191,7 +160,7 @@ export async function checkProgram(): Promise<void> {
       'to say hello to',
     );
     const lamports = await connection.getMinimumBalanceForRentExemption(
-      GREETING_SIZE,
+      GREETING_ACCOUNT_SIZE,
     );
 
     const transaction = new Transaction().add(
@@ -201,7 +170,7 @@ export async function checkProgram(): Promise<void> {
         seed: GREETING_SEED,
         newAccountPubkey: greetedPubkey,
         lamports,
-        space: GREETING_SIZE,
+        space: GREETING_ACCOUNT_SIZE,
         programId,
       }),
     );
@@ -217,12 +186,18 @@ export async function sayHello(): Promise<void> {
   const instruction = new TransactionInstruction({
     keys: [{pubkey: greetedPubkey, isSigner: false, isWritable: true}],
     programId,
-    // First byte is variant of GreetingInstruction enum in on chain program
-    // Next four bytes are number one get deserialized to num_greetings on GreetingInstruction struct
-    // Remaining bytes are the serialized string "hello" where the first four bytes are the
-    // length of the string and the remaining bytes are the utf-8 characters.
+    // Serialized with the schema generated from the program's Rust types
     data: Buffer.from(
-      new Uint8Array([0, 1, 0, 0, 0, 5, 0, 0, 0, 104, 101, 108, 108, 111]),
+      borsh.serialize(
+        SCHEMA,
+        new GreetingInstruction({
+          InitGreeting: new InitGreetingArgs({
+            num_greetings: 1,
+            greeting_string: 'hello',
+            nonce: undefined,
+          }),
+        }),
+      ),
     ),
   });
   await sendAndConfirmTransaction(
@@ -240,11 +215,7 @@ export async function reportGreetings(): Promise<void> {
   if (accountInfo === null) {
     throw 'Error: cannot find the greeted account';
   }
-  const greeting = borsh.deserialize(
-    GreetingSchema,
-    GreetingAccount,
-    accountInfo.data,
-  );
+  const greeting = borsh.deserialize(SCHEMA, GreetingAccount, accountInfo.data);
   console.log(
     greetedPubkey.toBase58(),
     'has been greeted',
@@ -0,0 +1,219 @@
+/**
+ * Borsh schema of the hello world program's instructions and accounts.
+ *
+ * Generated from the Rust types by `npm run generate:idl`, don't edit.
+ */
+
+import BN from 'bn.js';
+
+/**
+ * Size of a `GreetingAccount`, in bytes
+ */
+export const GREETING_ACCOUNT_SIZE = 41;
+
+/**
+ * Size of a `GreeterNonce`, in bytes
+ */
+export const GREETER_NONCE_SIZE = 8;
+
+export class GreeterNonce {
+  next_nonce: BN;
+  constructor(fields: {
+    next_nonce: BN;
+  }) {
+    this.next_nonce = fields.next_nonce;
+  }
+}
+
+export class GreetingAccount {
+  counter: number;
+  counter_times_2: number;
+  authority: Uint8Array;
+  flags: number;
+  constructor(fields: {
+    counter: number;
+    counter_times_2: number;
+    authority: Uint8Array;
+    flags: number;
+  }) {
+    this.counter = fields.counter;
+    this.counter_times_2 = fields.counter_times_2;
+    this.authority = fields.authority;
+    this.flags = fields.flags;
+  }
+}
+
+export class GreetingInstructionClose {}
+
+export class GreetingInstruction {
+  enum: string;
+  InitGreeting?: InitGreetingArgs;
+  InitGreeting2?: InitGreetingArgs;
+  SignedGreeting?: SignedGreetingArgs;
+  Initialize?: InitializeArgs;
+  SetFlags?: number;
+  Close?: GreetingInstructionClose;
+  Migrate?: InitializeArgs;
+  constructor(
+    fields:
+      | {InitGreeting: InitGreetingArgs}
+      | {InitGreeting2: InitGreetingArgs}
+      | {SignedGreeting: SignedGreetingArgs}
+      | {Initialize: InitializeArgs}
+      | {SetFlags: number}
+      | {Close: GreetingInstructionClose}
+      | {Migrate: InitializeArgs},
+  ) {
+    this.enum = Object.keys(fields)[0];
+    Object.assign(this, fields);
+  }
+}
+
+export class GreetingPayload {
+  program_id: Uint8Array;
+  greeting_account: Uint8Array;
+  num_greetings: number;
+  nonce: BN;
+  constructor(fields: {
+    program_id: Uint8Array;
+    greeting_account: Uint8Array;
+    num_greetings: number;
+    nonce: BN;
+  }) {
+    this.program_id = fields.program_id;
+    this.greeting_account = fields.greeting_account;
+    this.num_greetings = fields.num_greetings;
+    this.nonce = fields.nonce;
+  }
+}
+
+export class InitGreetingArgs {
+  num_greetings: number;
+  greeting_string: string;
+  nonce: BN | undefined;
+  constructor(fields: {
+    num_greetings: number;
+    greeting_string: string;
+    nonce: BN | undefined;
+  }) {
+    this.num_greetings = fields.num_greetings;
+    this.greeting_string = fields.greeting_string;
+    this.nonce = fields.nonce;
+  }
+}
+
+export class InitializeArgs {
+  authority: Uint8Array;
+  flags: number;
+  seed: string;
+  constructor(fields: {
+    authority: Uint8Array;
+    flags: number;
+    seed: string;
+  }) {
+    this.authority = fields.authority;
+    this.flags = fields.flags;
+    this.seed = fields.seed;
+  }
+}
+
+export class SignedGreetingArgs {
+  num_greetings: number;
+  nonce: BN;
+  constructor(fields: {
+    num_greetings: number;
+    nonce: BN;
+  }) {
+    this.num_greetings = fields.num_greetings;
+    this.nonce = fields.nonce;
+  }
+}
+
+export const SCHEMA = new Map<any, any>([
+  [
+    GreeterNonce,
+    {
+      kind: 'struct',
+      fields: [['next_nonce', 'u64']],
+    },
+  ],
+  [
+    GreetingAccount,
+    {
+      kind: 'struct',
+      fields: [
+        ['counter', 'u32'],
+        ['counter_times_2', 'u32'],
+        ['authority', [32]],
+        ['flags', 'u8'],
+      ],
+    },
+  ],
+  [
+    GreetingInstructionClose,
+    {
+      kind: 'struct',
+      fields: [],
+    },
+  ],
+  [
+    GreetingInstruction,
+    {
+      kind: 'enum',
+      field: 'enum',
+      values: [
+        ['InitGreeting', InitGreetingArgs],
+        ['InitGreeting2', InitGreetingArgs],
+        ['SignedGreeting', SignedGreetingArgs],
+        ['Initialize', InitializeArgs],
+        ['SetFlags', 'u8'],
+        ['Close', GreetingInstructionClose],
+        ['Migrate', InitializeArgs],
+      ],
+    },
+  ],
+  [
+    GreetingPayload,
+    {
+      kind: 'struct',
+      fields: [
+        ['program_id', [32]],
+        ['greeting_account', [32]],
+        ['num_greetings', 'u32'],
+        ['nonce', 'u64'],
+      ],
+    },
+  ],
+  [
+    InitGreetingArgs,
+    {
+      kind: 'struct',
+      fields: [
+        ['num_greetings', 'u32'],
+        ['greeting_string', 'string'],
+        ['nonce', {kind: 'option', type: 'u64'}],
+      ],
+    },
+  ],
+  [
+    InitializeArgs,
+    {
+      kind: 'struct',
+      fields: [
+        ['authority', [32]],
+        ['flags', 'u8'],
+        ['seed', 'string'],
+      ],
+    },
+  ],
+  [
+    SignedGreetingArgs,
+    {
+      kind: 'struct',
+      fields: [
+        ['num_greetings', 'u32'],
+        ['nonce', 'u64'],
+      ],
+    },
+  ],
+]);
@@ -0,0 +1,146 @@
+//! Helpers for Ed25519 program instructions that carry a single signature
+//! whose public key, signature and message are all stored inline.
+
+use crate::error::GreetingError;
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{ed25519_program, instruction::Instruction, pubkey::Pubkey};
+
+pub const PUBKEY_SERIALIZED_SIZE: usize = 32;
+pub const SIGNATURE_SERIALIZED_SIZE: usize = 64;
+pub const SIGNATURE_OFFSETS_SERIALIZED_SIZE: usize = 14;
+/// `num_signatures` and a padding byte precede the offsets
+pub const SIGNATURE_OFFSETS_START: usize = 2;
+pub const DATA_START: usize = SIGNATURE_OFFSETS_START + SIGNATURE_OFFSETS_SERIALIZED_SIZE;
+
+/// Instruction index meaning "the Ed25519 instruction itself"
+const CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+/// Mirrors `solana_sdk::ed25519_instruction::Ed25519SignatureOffsets`
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct Ed25519SignatureOffsets {
+    pub signature_offset: u16,
+    pub signature_instruction_index: u16,
+    pub public_key_offset: u16,
+    pub public_key_instruction_index: u16,
+    pub message_data_offset: u16,
+    pub message_data_size: u16,
+    pub message_instruction_index: u16,
+}
+
+/// Creates an Ed25519 program instruction verifying an already computed
+/// `signature` of `message` by `pubkey`.
+pub fn new_ed25519_instruction(pubkey: &Pubkey, signature: &[u8], message: &[u8]) -> Instruction {
+    assert_eq!(signature.len(), SIGNATURE_SERIALIZED_SIZE);
+
+    let public_key_offset = DATA_START;
+    let signature_offset = public_key_offset + PUBKEY_SERIALIZED_SIZE;
+    let message_data_offset = signature_offset + SIGNATURE_SERIALIZED_SIZE;
+    let offsets = Ed25519SignatureOffsets {
+        signature_offset: signature_offset as u16,
+        signature_instruction_index: CURRENT_INSTRUCTION,
+        public_key_offset: public_key_offset as u16,
+        public_key_instruction_index: CURRENT_INSTRUCTION,
+        message_data_offset: message_data_offset as u16,
+        message_data_size: message.len() as u16,
+        message_instruction_index: CURRENT_INSTRUCTION,
+    };
+
+    let mut data = Vec::with_capacity(message_data_offset + message.len());
+    data.extend_from_slice(&[1, 0]);
+    data.extend_from_slice(&offsets.try_to_vec().unwrap());
+    data.extend_from_slice(pubkey.as_ref());
+    data.extend_from_slice(signature);
+    data.extend_from_slice(message);
+
+    Instruction {
+        program_id: ed25519_program::id(),
+        accounts: vec![],
+        data,
+    }
+}
+
+/// Extracts the signer and the signed message from the data of an Ed25519
+/// program instruction.
+///
+/// Only instructions with exactly one signature whose public key and message
+/// live in the instruction itself are accepted, so that the returned values
+/// are exactly what the runtime verified.
+pub fn parse_ed25519_instruction(data: &[u8]) -> Result<(Pubkey, &[u8]), GreetingError> {
+    let invalid = GreetingError::InvalidSignatureInstruction;
+    if data.len() < DATA_START || data[0] != 1 {
+        return Err(invalid);
+    }
+    let offsets =
+        Ed25519SignatureOffsets::try_from_slice(&data[SIGNATURE_OFFSETS_START..DATA_START])
+            .map_err(|_| invalid)?;
+    if offsets.signature_instruction_index != CURRENT_INSTRUCTION
+        || offsets.public_key_instruction_index != CURRENT_INSTRUCTION
+        || offsets.message_instruction_index != CURRENT_INSTRUCTION
+    {
+        return Err(invalid);
+    }
+
+    let slice = |offset: u16, len: usize| {
+        let start = offset as usize;
+        data.get(start..start.checked_add(len)?)
+    };
+    let pubkey = slice(offsets.public_key_offset, PUBKEY_SERIALIZED_SIZE).ok_or(invalid)?;
+    let message = slice(
+        offsets.message_data_offset,
+        offsets.message_data_size as usize,
+    )
+    .ok_or(invalid)?;
+    Ok((Pubkey::new(pubkey), message))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let pubkey = Pubkey::new_unique();
+        let signature = [7; SIGNATURE_SERIALIZED_SIZE];
+        let instruction = new_ed25519_instruction(&pubkey, &signature, b"hello");
+
+        assert_eq!(instruction.program_id, ed25519_program::id());
+        assert_eq!(
+            &instruction.data[DATA_START + PUBKEY_SERIALIZED_SIZE..][..SIGNATURE_SERIALIZED_SIZE],
+            &signature[..]
+        );
+        assert_eq!(
+            parse_ed25519_instruction(&instruction.data),
+            Ok((pubkey, &b"hello"[..]))
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_foreign_data() {
+        let pubkey = Pubkey::new_unique();
+        let signature = [7; SIGNATURE_SERIALIZED_SIZE];
+        let instruction = new_ed25519_instruction(&pubkey, &signature, b"hello");
+
+        // truncated message
+        let data = &instruction.data[..instruction.data.len() - 1];
+        assert_eq!(
+            parse_ed25519_instruction(data),
+            Err(GreetingError::InvalidSignatureInstruction)
+        );
+
+        // more than one signature
+        let mut data = instruction.data.clone();
+        data[0] = 2;
+        assert_eq!(
+            parse_ed25519_instruction(&data),
+            Err(GreetingError::InvalidSignatureInstruction)
+        );
+
+        // message stored in another instruction
+        let mut data = instruction.data;
+        data[SIGNATURE_OFFSETS_START + 12..DATA_START].copy_from_slice(&0u16.to_le_bytes());
+        assert_eq!(
+            parse_ed25519_instruction(&data),
+            Err(GreetingError::InvalidSignatureInstruction)
+        );
+    }
+}
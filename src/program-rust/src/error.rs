@@ -1,12 +1,50 @@
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
 use thiserror::Error;
 
 use solana_program::program_error::ProgramError;
 
-#[derive(Error, Debug, Copy, Clone)]
+#[derive(Error, Debug, Copy, Clone, PartialEq, FromPrimitive)]
 pub enum GreetingError {
     /// Invalid instruction
     #[error("Invalid Instruction")]
     InvalidInstruction,
+    /// Greeting string is longer than the policy allows
+    #[error("Greeting String Too Long")]
+    GreetingTooLong,
+    /// Greeting string contains a control character
+    #[error("Greeting String Contains Control Character")]
+    GreetingControlCharacter,
+    /// Greeting string contains a non-ASCII character under an ASCII-only policy
+    #[error("Greeting String Not Ascii")]
+    GreetingNotAscii,
+    /// Greeting string contains a non-printable character under a printable-only policy
+    #[error("Greeting String Not Printable")]
+    GreetingNotPrintable,
+    /// A signed greeting is not preceded by an Ed25519 program instruction
+    #[error("Missing Signature Instruction")]
+    MissingSignatureInstruction,
+    /// The Ed25519 program instruction does not carry exactly one inline signature
+    #[error("Invalid Signature Instruction")]
+    InvalidSignatureInstruction,
+    /// The signed message is not the payload of this greeting
+    #[error("Signed Payload Mismatch")]
+    SignedPayloadMismatch,
+    /// The nonce was already used by this greeter
+    #[error("Nonce Reused")]
+    NonceReused,
+    /// The nonce account is not the greeter's nonce address
+    #[error("Invalid Nonce Account")]
+    InvalidNonceAccount,
+    /// The signer is not the greeting account's authority
+    #[error("Invalid Authority")]
+    InvalidAuthority,
+    /// The greeting account only accepts top-level instructions
+    #[error("Cross-Program Invocation Not Allowed")]
+    CpiNotAllowed,
+    /// The signer did not create the greeting account it initializes
+    #[error("Invalid Creator")]
+    InvalidCreator,
 }
 
 impl From<GreetingError> for ProgramError {
@@ -14,3 +52,32 @@ impl From<GreetingError> for ProgramError {
         ProgramError::Custom(e as u32)
     }
 }
+
+impl GreetingError {
+    /// The `GreetingError` a `ProgramError::Custom` code stands for, if any
+    pub fn from_code(code: u32) -> Option<Self> {
+        Self::from_u32(code)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_code() {
+        // Every variant round-trips through its code, up to the first unused one
+        let errors = (0..)
+            .map_while(GreetingError::from_code)
+            .collect::<Vec<_>>();
+        assert_eq!(errors.first(), Some(&GreetingError::InvalidInstruction));
+        assert_eq!(errors.last(), Some(&GreetingError::InvalidCreator));
+        for (code, error) in errors.iter().enumerate() {
+            assert_eq!(*error as u32, code as u32);
+        }
+        assert_eq!(
+            GreetingError::from_code(GreetingError::InvalidCreator as u32 + 1),
+            None
+        );
+    }
+}
@@ -1,16 +1,53 @@
+use crate::{ed25519::new_ed25519_instruction, processor::find_greeter_nonce_address};
 use solana_program::{
     instruction::{AccountMeta, Instruction},
-    msg,
     pubkey::Pubkey,
+    system_program, sysvar,
 };
 
-use borsh::{BorshDeserialize, BorshSerialize};
+use borsh::{
+    schema::{Declaration, Definition},
+    BorshDeserialize, BorshSchema, BorshSerialize,
+};
+use std::collections::HashMap;
 
 #[repr(C)]
-#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, PartialEq)]
 pub struct InitGreetingArgs {
     pub num_greetings: u32,
     pub greeting_string: String,
+    /// Optional per-greeter nonce; a greeting is rejected unless its nonce is
+    /// higher than every nonce the greeter used before
+    pub nonce: Option<u64>,
+}
+
+#[repr(C)]
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, PartialEq)]
+pub struct SignedGreetingArgs {
+    pub num_greetings: u32,
+    pub nonce: u64,
+}
+
+#[repr(C)]
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, PartialEq)]
+pub struct InitializeArgs {
+    pub authority: Pubkey,
+    pub flags: u8,
+    /// Seed the account's address was derived from with the creator, ignored
+    /// if the creator is the account itself
+    pub seed: String,
+}
+
+/// The message a greeter signs off-chain to authorize a relayed greeting
+#[repr(C)]
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, PartialEq)]
+pub struct GreetingPayload {
+    /// The program the greeting is meant for, so that a signature can't be
+    /// relayed to another deployment with the same account layout
+    pub program_id: Pubkey,
+    pub greeting_account: Pubkey,
+    pub num_greetings: u32,
+    pub nonce: u64,
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
@@ -21,11 +58,80 @@ pub enum GreetingInstruction {
     /// Accounts expected:
     ///
     /// 0. `[writeable]` The account that has the greeting counter data in it
+    ///
+    /// If `nonce` is set, additionally:
+    ///
+    /// 1. `[signer]` The greeter
+    /// 2. `[writeable]` The greeter's nonce account, see `find_greeter_nonce_address`
+    /// 3. `[writeable, signer]` Payer, funds the nonce account on first use
+    /// 4. `[]` System program
+    ///
+    /// Nonces are a high-water mark, see `GreeterNonce`: a nonce lower than the
+    /// greeter's last one is rejected even if it was never used.
+    ///
+    /// Top-level only accounts also need the Instructions sysvar, in any position.
+    /// The greeting string must pass the account's `GreetingAccount::greeting_policy`.
     InitGreeting(InitGreetingArgs),
 
     // Including this here to show how serialization / deserialization works with
     // borsh try_to_vec and try_from_slice where it adds the enum variant
     InitGreeting2(InitGreetingArgs),
+
+    /// Increments the counter on behalf of a greeter who signed a
+    /// `GreetingPayload` off-chain. Anyone may relay the greeting.
+    ///
+    /// The instruction immediately before this one must be an Ed25519 program
+    /// instruction verifying the greeter's signature of the payload. The payload's
+    /// nonce is checked like `InitGreeting`'s: it must exceed the greeter's last one.
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writeable]` The account that has the greeting counter data in it
+    /// 1. `[]` Instructions sysvar
+    /// 2. `[writeable]` The greeter's nonce account, see `find_greeter_nonce_address`
+    /// 3. `[writeable, signer]` Payer, funds the nonce account on first use
+    /// 4. `[]` System program
+    SignedGreeting(SignedGreetingArgs),
+
+    /// Sets the authority and flags of a freshly created greeting account.
+    /// Only its creator may, legacy accounts need `Migrate` instead.
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writeable]` The uninitialized greeting account
+    /// 1. `[signer]` The creator: the account itself, or the base its address
+    ///    was derived from with `seed`
+    Initialize(InitializeArgs),
+
+    /// Replaces the flags of a greeting account, see `GreetingAccount::TOP_LEVEL_ONLY`.
+    /// `ASCII_ONLY` and `PRINTABLE_ONLY` select the greeting string policy and
+    /// may not both be set.
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writeable]` The greeting account
+    /// 1. `[signer]` The account's authority
+    SetFlags(u8),
+
+    /// Closes a greeting account, moving all of its lamports to the recipient
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writeable]` The greeting account
+    /// 1. `[signer]` The account's authority
+    /// 2. `[writeable]` The recipient of the account's lamports
+    Close,
+
+    /// Grows a legacy greeting account, which only holds the counters, to the
+    /// current layout and sets its authority and flags like `Initialize`
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writeable]` The legacy greeting account
+    /// 1. `[signer]` The creator, see `Initialize`
+    /// 2. `[writeable, signer]` Payer, funds the rent of the larger account
+    /// 3. `[]` System program
+    Migrate(InitializeArgs),
 }
 
 // Leaving this here for reference as an alternative method for matching
@@ -64,6 +170,37 @@ pub enum GreetingInstruction {
 //     }
 // }
 
+// Implemented by hand, the derive declares a struct per variant whose fields
+// it never reads, which `dead_code` rejects. Unit variants are `nil`.
+impl BorshSchema for GreetingInstruction {
+    fn add_definitions_recursively(definitions: &mut HashMap<Declaration, Definition>) {
+        let variants = vec![
+            ("InitGreeting".to_string(), InitGreetingArgs::declaration()),
+            ("InitGreeting2".to_string(), InitGreetingArgs::declaration()),
+            (
+                "SignedGreeting".to_string(),
+                SignedGreetingArgs::declaration(),
+            ),
+            ("Initialize".to_string(), InitializeArgs::declaration()),
+            ("SetFlags".to_string(), u8::declaration()),
+            ("Close".to_string(), <()>::declaration()),
+            ("Migrate".to_string(), InitializeArgs::declaration()),
+        ];
+        Self::add_definition(
+            Self::declaration(),
+            Definition::Enum { variants },
+            definitions,
+        );
+        InitGreetingArgs::add_definitions_recursively(definitions);
+        SignedGreetingArgs::add_definitions_recursively(definitions);
+        InitializeArgs::add_definitions_recursively(definitions);
+    }
+
+    fn declaration() -> Declaration {
+        "GreetingInstruction".to_string()
+    }
+}
+
 /// Creates an 'InitGreeting' instruction.
 pub fn init_greeting(
     program_id: Pubkey,
@@ -74,16 +211,232 @@ pub fn init_greeting(
     let greeting_instruction = GreetingInstruction::InitGreeting(InitGreetingArgs {
         num_greetings,
         greeting_string,
+        nonce: None,
     });
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(greeting_account_id, false),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
+        ],
+        data: greeting_instruction.try_to_vec().unwrap(),
+    }
+}
 
-    msg!(
-        "{:?}, {:?}",
-        greeting_instruction,
-        greeting_instruction.try_to_vec().unwrap()
-    );
+/// Creates an 'InitGreeting' instruction protected by the greeter's `nonce`.
+pub fn init_greeting_with_nonce(
+    program_id: Pubkey,
+    greeting_account_id: Pubkey,
+    greeter: &Pubkey,
+    payer: &Pubkey,
+    num_greetings: u32,
+    greeting_string: String,
+    nonce: u64,
+) -> Instruction {
+    let greeting_instruction = GreetingInstruction::InitGreeting(InitGreetingArgs {
+        num_greetings,
+        greeting_string,
+        nonce: Some(nonce),
+    });
+    let (nonce_account_id, _) = find_greeter_nonce_address(&program_id, greeter);
     Instruction {
         program_id,
-        accounts: vec![AccountMeta::new(greeting_account_id, false)],
+        accounts: vec![
+            AccountMeta::new(greeting_account_id, false),
+            AccountMeta::new_readonly(*greeter, true),
+            AccountMeta::new(nonce_account_id, false),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
+        ],
         data: greeting_instruction.try_to_vec().unwrap(),
     }
 }
+
+/// Creates the Ed25519 signature check and 'SignedGreeting' instructions
+/// relaying a greeting signed by `greeter`.
+pub fn signed_greeting(
+    program_id: Pubkey,
+    greeting_account_id: Pubkey,
+    greeter: &Pubkey,
+    payer: &Pubkey,
+    signature: &[u8],
+    num_greetings: u32,
+    nonce: u64,
+) -> Vec<Instruction> {
+    let payload = GreetingPayload {
+        program_id,
+        greeting_account: greeting_account_id,
+        num_greetings,
+        nonce,
+    };
+    let greeting_instruction = GreetingInstruction::SignedGreeting(SignedGreetingArgs {
+        num_greetings,
+        nonce,
+    });
+    let (nonce_account_id, _) = find_greeter_nonce_address(&program_id, greeter);
+    vec![
+        new_ed25519_instruction(greeter, signature, &payload.try_to_vec().unwrap()),
+        Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(greeting_account_id, false),
+                AccountMeta::new_readonly(sysvar::instructions::id(), false),
+                AccountMeta::new(nonce_account_id, false),
+                AccountMeta::new(*payer, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: greeting_instruction.try_to_vec().unwrap(),
+        },
+    ]
+}
+
+/// Creates an 'Initialize' instruction for an account `creator` derived with `seed`.
+pub fn initialize(
+    program_id: Pubkey,
+    greeting_account_id: Pubkey,
+    creator: Pubkey,
+    seed: String,
+    authority: Pubkey,
+    flags: u8,
+) -> Instruction {
+    let args = InitializeArgs {
+        authority,
+        flags,
+        seed,
+    };
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(greeting_account_id, false),
+            AccountMeta::new_readonly(creator, true),
+        ],
+        data: GreetingInstruction::Initialize(args).try_to_vec().unwrap(),
+    }
+}
+
+/// Creates a 'Migrate' instruction for a legacy account `creator` derived with `seed`.
+pub fn migrate(
+    program_id: Pubkey,
+    greeting_account_id: Pubkey,
+    creator: Pubkey,
+    payer: Pubkey,
+    seed: String,
+    authority: Pubkey,
+    flags: u8,
+) -> Instruction {
+    let args = InitializeArgs {
+        authority,
+        flags,
+        seed,
+    };
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(greeting_account_id, false),
+            AccountMeta::new_readonly(creator, true),
+            AccountMeta::new(payer, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: GreetingInstruction::Migrate(args).try_to_vec().unwrap(),
+    }
+}
+
+/// Creates a 'SetFlags' instruction.
+pub fn set_flags(
+    program_id: Pubkey,
+    greeting_account_id: Pubkey,
+    authority: Pubkey,
+    flags: u8,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(greeting_account_id, false),
+            AccountMeta::new_readonly(authority, true),
+        ],
+        data: GreetingInstruction::SetFlags(flags).try_to_vec().unwrap(),
+    }
+}
+
+/// Creates a 'Close' instruction.
+pub fn close(
+    program_id: Pubkey,
+    greeting_account_id: Pubkey,
+    authority: Pubkey,
+    recipient: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(greeting_account_id, false),
+            AccountMeta::new_readonly(authority, true),
+            AccountMeta::new(recipient, false),
+        ],
+        data: GreetingInstruction::Close.try_to_vec().unwrap(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_schema_variants() {
+        let container = GreetingInstruction::schema_container();
+        let variants = match &container.definitions[&container.declaration] {
+            Definition::Enum { variants } => variants,
+            definition => panic!("not an enum: {:?}", definition),
+        };
+        let init_greeting_args = InitGreetingArgs {
+            num_greetings: 1,
+            greeting_string: String::new(),
+            nonce: None,
+        };
+        let instructions = [
+            GreetingInstruction::InitGreeting(init_greeting_args.clone()),
+            GreetingInstruction::InitGreeting2(init_greeting_args),
+            GreetingInstruction::SignedGreeting(SignedGreetingArgs {
+                num_greetings: 1,
+                nonce: 0,
+            }),
+            GreetingInstruction::Initialize(InitializeArgs {
+                authority: Pubkey::new_unique(),
+                flags: 0,
+                seed: String::new(),
+            }),
+            GreetingInstruction::SetFlags(0),
+            GreetingInstruction::Close,
+            GreetingInstruction::Migrate(InitializeArgs {
+                authority: Pubkey::new_unique(),
+                flags: 0,
+                seed: String::new(),
+            }),
+        ];
+        assert_eq!(variants.len(), instructions.len());
+        for instruction in instructions {
+            // Fails to compile when a variant is added, update the schema too.
+            // The declarations come from the payload types, so changing one
+            // fails below until the schema follows.
+            let (name, (declaration, payload)) = match &instruction {
+                GreetingInstruction::InitGreeting(args) => ("InitGreeting", payload_of(args)),
+                GreetingInstruction::InitGreeting2(args) => ("InitGreeting2", payload_of(args)),
+                GreetingInstruction::SignedGreeting(args) => ("SignedGreeting", payload_of(args)),
+                GreetingInstruction::Initialize(args) => ("Initialize", payload_of(args)),
+                GreetingInstruction::SetFlags(flags) => ("SetFlags", payload_of(flags)),
+                GreetingInstruction::Close => ("Close", payload_of(&())),
+                GreetingInstruction::Migrate(args) => ("Migrate", payload_of(args)),
+            };
+            let data = instruction.try_to_vec().unwrap();
+            let (variant_name, variant_declaration) = &variants[data[0] as usize];
+            assert_eq!(variant_name, name);
+            assert_eq!(variant_declaration, &declaration);
+            assert_eq!(data[1..], payload);
+        }
+    }
+
+    /// The schema declaration and Borsh encoding of a variant's payload
+    fn payload_of<T: BorshSchema + BorshSerialize>(payload: &T) -> (Declaration, Vec<u8>) {
+        (T::declaration(), payload.try_to_vec().unwrap())
+    }
+}
@@ -2,9 +2,13 @@ use solana_program::{
     account_info::AccountInfo, entrypoint, entrypoint::ProgramResult, msg, pubkey::Pubkey,
 };
 
+pub mod ed25519;
 pub mod error;
 pub mod instruction;
 pub mod processor;
+#[cfg(feature = "serde")]
+mod serde_pubkey;
+pub mod validation;
 use processor::Processor;
 
 // Declare and export the program's entrypoint
@@ -1,57 +1,182 @@
-use crate::error::GreetingError::InvalidInstruction;
-use crate::instruction::GreetingInstruction;
-use arrayref::*;
-use borsh::{BorshDeserialize, BorshSerialize};
+use crate::ed25519::parse_ed25519_instruction;
+use crate::error::GreetingError::{
+    CpiNotAllowed, InvalidAuthority, InvalidCreator, InvalidInstruction, InvalidNonceAccount,
+    MissingSignatureInstruction, NonceReused, SignedPayloadMismatch,
+};
+use crate::instruction::{
+    GreetingInstruction, GreetingPayload, InitializeArgs, SignedGreetingArgs,
+};
+use crate::validation::{CharacterPolicy, GreetingPolicy};
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    ed25519_program,
     entrypoint::ProgramResult,
     msg,
+    program::{invoke, invoke_signed},
     program_error::ProgramError,
     program_pack::Pack,
     program_pack::{IsInitialized, Sealed},
     pubkey::Pubkey,
+    rent::Rent,
+    system_instruction,
+    sysvar::{
+        self,
+        instructions::{load_current_index_checked, load_instruction_at_checked},
+        Sysvar,
+    },
 };
 
 /// Define the type of state stored in accounts
+///
+/// The Borsh derive is the only definition of the account layout. `Pack` is
+/// implemented on top of it so that `LEN` and the on-chain bytes cannot
+/// disagree, and `BorshSchema` describes it to the generated IDL.
 #[repr(C)]
-#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
 pub struct GreetingAccount {
     /// number of greetings
     pub counter: u32,
     pub counter_times_2: u32,
+    /// may change the account's flags, unset until `Initialize`
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_pubkey"))]
+    pub authority: Pubkey,
+    /// `GreetingAccount::TOP_LEVEL_ONLY`, `ASCII_ONLY`, `PRINTABLE_ONLY` and future options
+    pub flags: u8,
+}
+
+impl GreetingAccount {
+    /// Only count greetings from top-level instructions, never from CPI
+    pub const TOP_LEVEL_ONLY: u8 = 1 << 0;
+    /// Only accept ASCII greeting strings, see `CharacterPolicy::Ascii`
+    pub const ASCII_ONLY: u8 = 1 << 1;
+    /// Only accept printable greeting strings, see `CharacterPolicy::Printable`
+    pub const PRINTABLE_ONLY: u8 = 1 << 2;
+    /// Offset of `authority` in the account data, for `memcmp` RPC filters
+    pub const AUTHORITY_OFFSET: usize = 4 + 4; // counter + counter_times_2
+    /// Size of the accounts created before `authority` and `flags` existed,
+    /// which `Migrate` grows to `LEN`
+    pub const LEGACY_LEN: usize = 4 + 4; // counter + counter_times_2
+
+    /// Unpacks an account of either layout. Legacy accounts have no
+    /// authority and no flags.
+    pub fn unpack_versioned(input: &[u8]) -> Result<Self, ProgramError> {
+        if input.len() != Self::LEGACY_LEN {
+            return Self::unpack_unchecked(input);
+        }
+        let (counter, counter_times_2) =
+            <(u32, u32)>::try_from_slice(input).map_err(|_| ProgramError::InvalidAccountData)?;
+        Ok(Self {
+            counter,
+            counter_times_2,
+            authority: Pubkey::default(),
+            flags: 0,
+        })
+    }
+
+    /// Packs into an account of either layout, see `unpack_versioned`
+    pub fn pack_versioned(self, output: &mut [u8]) -> ProgramResult {
+        if output.len() != Self::LEGACY_LEN {
+            return Self::pack(self, output);
+        }
+        let mut output = output;
+        (self.counter, self.counter_times_2)
+            .serialize(&mut output)
+            .map_err(|_| ProgramError::InvalidAccountData)
+    }
+
+    pub fn top_level_only(&self) -> bool {
+        self.flags & Self::TOP_LEVEL_ONLY != 0
+    }
+
+    /// The characters the account's flags allow in greeting strings, `None`
+    /// if they set both `ASCII_ONLY` and `PRINTABLE_ONLY`
+    pub fn character_policy(flags: u8) -> Option<CharacterPolicy> {
+        match (
+            flags & Self::ASCII_ONLY != 0,
+            flags & Self::PRINTABLE_ONLY != 0,
+        ) {
+            (false, false) => Some(CharacterPolicy::Any),
+            (true, false) => Some(CharacterPolicy::Ascii),
+            (false, true) => Some(CharacterPolicy::Printable),
+            (true, true) => None,
+        }
+    }
+
+    /// The policy `InitGreeting` checks greeting strings against
+    pub fn greeting_policy(&self) -> Result<GreetingPolicy, ProgramError> {
+        let characters =
+            Self::character_policy(self.flags).ok_or(ProgramError::InvalidAccountData)?;
+        Ok(GreetingPolicy {
+            characters,
+            ..GreetingPolicy::DEFAULT
+        })
+    }
 }
 
 impl Sealed for GreetingAccount {}
 impl IsInitialized for GreetingAccount {
     fn is_initialized(&self) -> bool {
-        self.counter != 0
+        self.authority != Pubkey::default()
     }
 }
 
-// Leaving this here for reference as an alternative method for matching
-// instructions. This method matches based on the first byte of the data
-// and then parses the remaining bytes based on explicit references.
-const GREETING_ACCOUNT_LEN: usize = 8; // 4 + 4
+/// Size of the Borsh encoding of a `GreetingAccount`
+const GREETING_ACCOUNT_LEN: usize = 4 + 4 + 32 + 1; // counter + counter_times_2 + authority + flags
 impl Pack for GreetingAccount {
     const LEN: usize = GREETING_ACCOUNT_LEN;
 
     fn pack_into_slice(&self, output: &mut [u8]) {
-        let output = array_mut_ref![output, 0, GREETING_ACCOUNT_LEN];
-        let (counter, counter_times_2) = mut_array_refs![output, 4, 4];
-
-        *counter = self.counter.to_le_bytes();
-        *counter_times_2 = self.counter_times_2.to_le_bytes();
+        let mut output = output;
+        self.serialize(&mut output)
+            .expect("GREETING_ACCOUNT_LEN matches the Borsh encoding");
     }
 
     /// Unpacks a byte buffer into a GreetingAccount
     fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
-        let input = array_ref![input, 0, GREETING_ACCOUNT_LEN];
-        let (counter, counter_times_2) = array_refs![input, 4, 4];
+        Self::try_from_slice(input).map_err(|_| ProgramError::InvalidAccountData)
+    }
+}
 
-        Ok(Self {
-            counter: u32::from_le_bytes(*counter),
-            counter_times_2: u32::from_le_bytes(*counter_times_2),
-        })
+/// Seed prefix of the per-greeter nonce accounts
+pub const GREETER_NONCE_SEED: &[u8] = b"nonce";
+
+/// Returns the address of the account tracking `greeter`'s nonces
+pub fn find_greeter_nonce_address(program_id: &Pubkey, greeter: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[GREETER_NONCE_SEED, greeter.as_ref()], program_id)
+}
+
+/// Replay protection for a greeter, stored at `find_greeter_nonce_address`
+///
+/// Only the high-water mark is kept: using nonce `n` invalidates every nonce
+/// up to `n`, including skipped ones, so a greeter's greetings must land in
+/// nonce order.
+#[repr(C)]
+#[derive(BorshSerialize, BorshDeserialize, BorshSchema, Debug, Clone, PartialEq)]
+pub struct GreeterNonce {
+    /// lowest nonce the greeter may use next
+    pub next_nonce: u64,
+}
+
+impl Sealed for GreeterNonce {}
+
+const GREETER_NONCE_LEN: usize = 8; // next_nonce
+impl Pack for GreeterNonce {
+    const LEN: usize = GREETER_NONCE_LEN;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let mut output = output;
+        self.serialize(&mut output)
+            .expect("GREETER_NONCE_LEN matches the Borsh encoding");
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        Self::try_from_slice(input).map_err(|_| ProgramError::InvalidAccountData)
     }
 }
 
@@ -72,34 +197,364 @@ impl Processor {
                     accounts,
                     args.num_greetings,
                     args.greeting_string,
+                    args.nonce,
                     program_id,
                 )
             }
-            GreetingInstruction::InitGreeting2(_) => Err(ProgramError::Custom(42 as u32)),
+            GreetingInstruction::InitGreeting2(_) => Err(ProgramError::Custom(42)),
+            GreetingInstruction::SignedGreeting(args) => {
+                msg!("Instruction: SignedGreeting");
+                Self::process_signed_greeting(accounts, args, program_id)
+            }
+            GreetingInstruction::Initialize(args) => {
+                msg!("Instruction: Initialize");
+                Self::process_initialize(accounts, args, program_id)
+            }
+            GreetingInstruction::SetFlags(flags) => {
+                msg!("Instruction: SetFlags");
+                Self::process_set_flags(accounts, flags, program_id)
+            }
+            GreetingInstruction::Close => {
+                msg!("Instruction: Close");
+                Self::process_close(accounts, program_id)
+            }
+            GreetingInstruction::Migrate(args) => {
+                msg!("Instruction: Migrate");
+                Self::process_migrate(accounts, args, program_id)
+            }
         }
     }
+
+    fn process_initialize(
+        accounts: &[AccountInfo],
+        args: InitializeArgs,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let account = next_account_info(account_info_iter)?;
+        let creator = next_account_info(account_info_iter)?;
+        if account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if account.data_len() != GreetingAccount::LEN {
+            msg!(
+                "Greeting account holds {} bytes, not {}; migrate legacy accounts instead",
+                account.data_len(),
+                GreetingAccount::LEN
+            );
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Self::check_creator(account, creator, &args.seed, program_id)?;
+
+        let mut greeting_account = GreetingAccount::unpack_unchecked(&account.data.borrow())?;
+        if greeting_account.is_initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        Self::check_initialize_args(&args)?;
+        greeting_account.authority = args.authority;
+        greeting_account.flags = args.flags;
+        GreetingAccount::pack(greeting_account, &mut account.data.borrow_mut())
+    }
+
+    fn process_migrate(
+        accounts: &[AccountInfo],
+        args: InitializeArgs,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let account = next_account_info(account_info_iter)?;
+        let creator = next_account_info(account_info_iter)?;
+        let payer = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        if account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if account.data_len() != GreetingAccount::LEGACY_LEN {
+            msg!(
+                "Greeting account holds {} bytes, only legacy {} byte accounts migrate",
+                account.data_len(),
+                GreetingAccount::LEGACY_LEN
+            );
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Self::check_creator(account, creator, &args.seed, program_id)?;
+        Self::check_initialize_args(&args)?;
+
+        let mut greeting_account = GreetingAccount::unpack_versioned(&account.data.borrow())?;
+        let required_lamports = Rent::get()?
+            .minimum_balance(GreetingAccount::LEN)
+            .saturating_sub(account.lamports());
+        if required_lamports > 0 {
+            invoke(
+                &system_instruction::transfer(payer.key, account.key, required_lamports),
+                &[payer.clone(), account.clone(), system_program.clone()],
+            )?;
+        }
+        account.realloc(GreetingAccount::LEN, true)?;
+        greeting_account.authority = args.authority;
+        greeting_account.flags = args.flags;
+        GreetingAccount::pack(greeting_account, &mut account.data.borrow_mut())
+    }
+
+    /// Fails unless `creator` signed and created `account`: it is either the
+    /// account itself, or the base its address was derived from with `seed`.
+    /// This keeps others from claiming an account before its creator does.
+    fn check_creator(
+        account: &AccountInfo,
+        creator: &AccountInfo,
+        seed: &str,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if !creator.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if creator.key != account.key
+            && Pubkey::create_with_seed(creator.key, seed, program_id)? != *account.key
+        {
+            msg!("{} did not create {}", creator.key, account.key);
+            return Err(InvalidCreator.into());
+        }
+        Ok(())
+    }
+
+    fn check_initialize_args(args: &InitializeArgs) -> ProgramResult {
+        if args.authority == Pubkey::default() {
+            return Err(InvalidAuthority.into());
+        }
+        if GreetingAccount::character_policy(args.flags).is_none() {
+            return Err(ProgramError::InvalidArgument);
+        }
+        Ok(())
+    }
+
+    fn process_set_flags(
+        accounts: &[AccountInfo],
+        flags: u8,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let account = next_account_info(account_info_iter)?;
+        let authority = next_account_info(account_info_iter)?;
+        if account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let mut greeting_account = GreetingAccount::unpack(&account.data.borrow())?;
+        if greeting_account.authority != *authority.key {
+            return Err(InvalidAuthority.into());
+        }
+        if !authority.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if GreetingAccount::character_policy(flags).is_none() {
+            return Err(ProgramError::InvalidArgument);
+        }
+        greeting_account.flags = flags;
+        GreetingAccount::pack(greeting_account, &mut account.data.borrow_mut())
+    }
+
+    fn process_close(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let account = next_account_info(account_info_iter)?;
+        let authority = next_account_info(account_info_iter)?;
+        let recipient = next_account_info(account_info_iter)?;
+        if account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if account.key == recipient.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let greeting_account = GreetingAccount::unpack(&account.data.borrow())?;
+        if greeting_account.authority != *authority.key {
+            return Err(InvalidAuthority.into());
+        }
+        if !authority.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let recipient_lamports = recipient
+            .lamports()
+            .checked_add(account.lamports())
+            .ok_or(ProgramError::InvalidArgument)?;
+        **recipient.lamports.borrow_mut() = recipient_lamports;
+        **account.lamports.borrow_mut() = 0;
+        account.data.borrow_mut().fill(0);
+        Ok(())
+    }
     fn process_greeting(
         accounts: &[AccountInfo],
         num_greetings: u32,
         greeting_string: String,
+        nonce: Option<u64>,
         program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let account = next_account_info(account_info_iter)?;
+        if account.owner != program_id {
+            msg!("Greeted account does not have the correct program id");
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        GreetingAccount::unpack_versioned(&account.data.borrow())?
+            .greeting_policy()?
+            .validate(&greeting_string)?;
         msg!(
             "Unpacked {} greetings and {} string!",
             num_greetings,
             greeting_string
         );
+        if let Some(nonce) = nonce {
+            let greeter = next_account_info(account_info_iter)?;
+            if !greeter.is_signer {
+                msg!("Greeter must sign greetings that carry a nonce");
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            Self::use_nonce(program_id, greeter.key, nonce, account_info_iter)?;
+        }
+        Self::increment_counter(account, accounts, num_greetings, program_id)
+    }
+
+    fn process_signed_greeting(
+        accounts: &[AccountInfo],
+        args: SignedGreetingArgs,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let account = next_account_info(account_info_iter)?;
+        let instructions_sysvar = next_account_info(account_info_iter)?;
+
+        // The signature check must be the instruction right before this one
+        let current_index = load_current_index_checked(instructions_sysvar)? as usize;
+        if current_index == 0 {
+            return Err(MissingSignatureInstruction.into());
+        }
+        let signature_instruction =
+            load_instruction_at_checked(current_index - 1, instructions_sysvar)?;
+        if signature_instruction.program_id != ed25519_program::id() {
+            return Err(MissingSignatureInstruction.into());
+        }
+        let (greeter, message) = parse_ed25519_instruction(&signature_instruction.data)?;
+
+        let payload = GreetingPayload {
+            program_id: *program_id,
+            greeting_account: *account.key,
+            num_greetings: args.num_greetings,
+            nonce: args.nonce,
+        };
+        if message != payload.try_to_vec()?.as_slice() {
+            return Err(SignedPayloadMismatch.into());
+        }
+        msg!(
+            "Crediting {} greetings signed by {} (nonce {})",
+            args.num_greetings,
+            greeter,
+            args.nonce
+        );
+        Self::use_nonce(program_id, &greeter, args.nonce, account_info_iter)?;
+        Self::increment_counter(account, accounts, args.num_greetings, program_id)
+    }
+
+    /// Fails unless the transaction's current top-level instruction is ours,
+    /// i.e. we were not reached through CPI. Looks for the Instructions sysvar
+    /// anywhere in `accounts`.
+    fn check_top_level(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let instructions_sysvar = accounts
+            .iter()
+            .find(|account| sysvar::instructions::check_id(account.key))
+            .ok_or_else(|| {
+                msg!("Top-level only account requires the Instructions sysvar");
+                ProgramError::NotEnoughAccountKeys
+            })?;
+        let current_index = load_current_index_checked(instructions_sysvar)? as usize;
+        let current_instruction = load_instruction_at_checked(current_index, instructions_sysvar)?;
+        if current_instruction.program_id != *program_id {
+            msg!(
+                "Invoked through CPI by {}, account only accepts top-level greetings",
+                current_instruction.program_id
+            );
+            return Err(CpiNotAllowed.into());
+        }
+        Ok(())
+    }
+
+    /// Records `nonce` as used by `greeter`, creating the greeter's nonce
+    /// account on first use. Expects the nonce account, payer and system
+    /// program next in `account_info_iter`.
+    fn use_nonce<'a, 'b: 'a>(
+        program_id: &Pubkey,
+        greeter: &Pubkey,
+        nonce: u64,
+        account_info_iter: &mut impl Iterator<Item = &'a AccountInfo<'b>>,
+    ) -> ProgramResult {
+        let nonce_account = next_account_info(account_info_iter)?;
+        let payer = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+
+        let (nonce_account_id, bump_seed) = find_greeter_nonce_address(program_id, greeter);
+        if *nonce_account.key != nonce_account_id {
+            return Err(InvalidNonceAccount.into());
+        }
+        if nonce_account.data_is_empty() {
+            // Transfer, allocate and assign rather than create_account, which
+            // fails if someone has already sent lamports to the address
+            let signer_seeds: &[&[u8]] = &[GREETER_NONCE_SEED, greeter.as_ref(), &[bump_seed]];
+            let required_lamports = Rent::get()?
+                .minimum_balance(GreeterNonce::LEN)
+                .saturating_sub(nonce_account.lamports());
+            if required_lamports > 0 {
+                invoke(
+                    &system_instruction::transfer(payer.key, nonce_account.key, required_lamports),
+                    &[payer.clone(), nonce_account.clone(), system_program.clone()],
+                )?;
+            }
+            invoke_signed(
+                &system_instruction::allocate(nonce_account.key, GreeterNonce::LEN as u64),
+                &[nonce_account.clone(), system_program.clone()],
+                &[signer_seeds],
+            )?;
+            invoke_signed(
+                &system_instruction::assign(nonce_account.key, program_id),
+                &[nonce_account.clone(), system_program.clone()],
+                &[signer_seeds],
+            )?;
+        }
+        if nonce_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let mut greeter_nonce = GreeterNonce::unpack_unchecked(&nonce_account.data.borrow())?;
+        if nonce < greeter_nonce.next_nonce {
+            msg!(
+                "Nonce {} already used, next nonce is {}",
+                nonce,
+                greeter_nonce.next_nonce
+            );
+            return Err(NonceReused.into());
+        }
+        greeter_nonce.next_nonce = nonce.checked_add(1).ok_or(NonceReused)?;
+        GreeterNonce::pack(greeter_nonce, &mut nonce_account.data.borrow_mut())
+    }
+
+    fn increment_counter(
+        account: &AccountInfo,
+        accounts: &[AccountInfo],
+        num_greetings: u32,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
         if account.owner != program_id {
             msg!("Greeted account does not have the correct program id");
             return Err(ProgramError::IncorrectProgramId);
         }
         // Increment and store the number of times the account has been greeted
-        let mut greeting_account = GreetingAccount::try_from_slice(&account.data.borrow())?;
+        let mut greeting_account = GreetingAccount::unpack_versioned(&account.data.borrow())?;
+        if greeting_account.top_level_only() {
+            Self::check_top_level(accounts, program_id)?;
+        }
         greeting_account.counter += &num_greetings;
         greeting_account.counter_times_2 = greeting_account.counter * 2;
-        greeting_account.serialize(&mut &mut account.data.borrow_mut()[..])?;
+        greeting_account
+            .clone()
+            .pack_versioned(&mut account.data.borrow_mut())?;
         msg!("Greeted {} time(s)!", greeting_account.counter);
         msg!(
             "Greetings times 2 equals {}!",
@@ -113,16 +568,18 @@ impl Processor {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::instruction::InitGreetingArgs;
+    use crate::{
+        error::GreetingError, instruction::InitGreetingArgs, validation::MAX_GREETING_LEN,
+    };
+    use proptest::prelude::*;
     use solana_program::clock::Epoch;
-    use std::mem;
 
     #[test]
     fn test_sanity() {
         let program_id = Pubkey::default();
         let key = Pubkey::default();
         let mut lamports = 0;
-        let mut data = vec![0; mem::size_of::<u32>() * 2];
+        let mut data = vec![0; GreetingAccount::LEN];
         let owner = Pubkey::default();
         let account = AccountInfo::new(
             &key,
@@ -138,6 +595,7 @@ mod test {
         let instruction_data = GreetingInstruction::InitGreeting(InitGreetingArgs {
             num_greetings: 1,
             greeting_string: String::from("hello"),
+            nonce: None,
         })
         .try_to_vec()
         .unwrap();
@@ -169,4 +627,343 @@ mod test {
             2
         );
     }
+
+    #[test]
+    fn test_invalid_greeting_string() {
+        let program_id = Pubkey::default();
+        let key = Pubkey::default();
+        let mut lamports = 0;
+        let mut data = vec![0; GreetingAccount::LEN];
+        let owner = Pubkey::default();
+        let account = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            &mut data,
+            &owner,
+            false,
+            Epoch::default(),
+        );
+        let accounts = vec![account];
+
+        for (greeting_string, error) in [
+            (
+                "x".repeat(MAX_GREETING_LEN + 1),
+                GreetingError::GreetingTooLong,
+            ),
+            (
+                String::from("hello\n"),
+                GreetingError::GreetingControlCharacter,
+            ),
+        ] {
+            let instruction_data = GreetingInstruction::InitGreeting(InitGreetingArgs {
+                num_greetings: 1,
+                greeting_string,
+                nonce: None,
+            })
+            .try_to_vec()
+            .unwrap();
+            assert_eq!(
+                Processor::process(&program_id, &accounts, &instruction_data),
+                Err(error.into())
+            );
+        }
+        assert_eq!(
+            GreetingAccount::try_from_slice(&accounts[0].data.borrow())
+                .unwrap()
+                .counter,
+            0
+        );
+    }
+
+    #[test]
+    fn test_account_greeting_policy() {
+        let program_id = Pubkey::new_unique();
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = vec![0; GreetingAccount::LEN];
+        let account = AccountInfo::new(
+            &key,
+            true,
+            true,
+            &mut lamports,
+            &mut data,
+            &program_id,
+            false,
+            Epoch::default(),
+        );
+        // The account is its own creator
+        let accounts = vec![account.clone(), account];
+        let greet = |greeting_string: &str| {
+            let instruction_data = GreetingInstruction::InitGreeting(InitGreetingArgs {
+                num_greetings: 1,
+                greeting_string: greeting_string.to_string(),
+                nonce: None,
+            })
+            .try_to_vec()
+            .unwrap();
+            Processor::process(&program_id, &accounts, &instruction_data)
+        };
+        let initialize = |flags| {
+            let instruction_data = GreetingInstruction::Initialize(InitializeArgs {
+                authority: Pubkey::new_unique(),
+                flags,
+                seed: String::new(),
+            })
+            .try_to_vec()
+            .unwrap();
+            Processor::process(&program_id, &accounts, &instruction_data)
+        };
+
+        assert_eq!(greet("hello\u{200b}"), Ok(()));
+        assert_eq!(
+            initialize(GreetingAccount::ASCII_ONLY | GreetingAccount::PRINTABLE_ONLY),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert_eq!(initialize(GreetingAccount::PRINTABLE_ONLY), Ok(()));
+        assert_eq!(
+            greet("hello\u{200b}"),
+            Err(GreetingError::GreetingNotPrintable.into())
+        );
+        assert_eq!(greet("¡hola!"), Ok(()));
+        assert_eq!(
+            GreetingAccount::unpack(&accounts[0].data.borrow())
+                .unwrap()
+                .counter,
+            2
+        );
+    }
+
+    fn initialize_with<'a>(
+        program_id: &Pubkey,
+        account: &AccountInfo<'a>,
+        creator: &AccountInfo<'a>,
+        seed: &str,
+        authority: Pubkey,
+    ) -> ProgramResult {
+        let instruction_data = GreetingInstruction::Initialize(InitializeArgs {
+            authority,
+            flags: 0,
+            seed: seed.to_string(),
+        })
+        .try_to_vec()
+        .unwrap();
+        Processor::process(
+            program_id,
+            &[account.clone(), creator.clone()],
+            &instruction_data,
+        )
+    }
+
+    #[test]
+    fn test_initialize_requires_creator() {
+        let program_id = Pubkey::new_unique();
+        let base = Pubkey::new_unique();
+        let key = Pubkey::create_with_seed(&base, "hello", &program_id).unwrap();
+        let other = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut base_lamports = 0;
+        let mut other_lamports = 0;
+        let mut data = vec![0; GreetingAccount::LEN];
+        let mut base_data = vec![];
+        let mut other_data = vec![];
+        let owner = solana_program::system_program::id();
+        let account = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            &mut data,
+            &program_id,
+            false,
+            Epoch::default(),
+        );
+        let base_account = AccountInfo::new(
+            &base,
+            true,
+            false,
+            &mut base_lamports,
+            &mut base_data,
+            &owner,
+            false,
+            Epoch::default(),
+        );
+        let other_account = AccountInfo::new(
+            &other,
+            true,
+            false,
+            &mut other_lamports,
+            &mut other_data,
+            &owner,
+            false,
+            Epoch::default(),
+        );
+        // Someone who saw the account being created can't claim it
+        assert_eq!(
+            initialize_with(&program_id, &account, &other_account, "hello", other),
+            Err(GreetingError::InvalidCreator.into())
+        );
+        assert_eq!(
+            initialize_with(&program_id, &account, &base_account, "bye", other),
+            Err(GreetingError::InvalidCreator.into())
+        );
+        let mut unsigned_base_account = base_account.clone();
+        unsigned_base_account.is_signer = false;
+        assert_eq!(
+            initialize_with(
+                &program_id,
+                &account,
+                &unsigned_base_account,
+                "hello",
+                other
+            ),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        assert_eq!(
+            initialize_with(&program_id, &account, &base_account, "hello", other),
+            Ok(())
+        );
+        assert_eq!(
+            GreetingAccount::unpack(&account.data.borrow())
+                .unwrap()
+                .authority,
+            other
+        );
+        assert_eq!(
+            initialize_with(&program_id, &account, &base_account, "hello", other),
+            Err(ProgramError::AccountAlreadyInitialized)
+        );
+    }
+
+    #[test]
+    fn test_legacy_account() {
+        let program_id = Pubkey::new_unique();
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = vec![0; GreetingAccount::LEGACY_LEN];
+        let account = AccountInfo::new(
+            &key,
+            true,
+            true,
+            &mut lamports,
+            &mut data,
+            &program_id,
+            false,
+            Epoch::default(),
+        );
+        let accounts = vec![account.clone(), account];
+
+        // Legacy accounts keep counting greetings in their own layout
+        let instruction_data = GreetingInstruction::InitGreeting(InitGreetingArgs {
+            num_greetings: 2,
+            greeting_string: String::from("hello"),
+            nonce: None,
+        })
+        .try_to_vec()
+        .unwrap();
+        Processor::process(&program_id, &accounts, &instruction_data).unwrap();
+        assert_eq!(*accounts[0].data.borrow(), [2, 0, 0, 0, 4, 0, 0, 0]);
+        assert_eq!(
+            GreetingAccount::unpack_versioned(&accounts[0].data.borrow()).unwrap(),
+            GreetingAccount {
+                counter: 2,
+                counter_times_2: 4,
+                authority: Pubkey::default(),
+                flags: 0,
+            }
+        );
+
+        // but need `Migrate` rather than `Initialize` to get an authority
+        let instruction_data = GreetingInstruction::Initialize(InitializeArgs {
+            authority: Pubkey::new_unique(),
+            flags: 0,
+            seed: String::new(),
+        })
+        .try_to_vec()
+        .unwrap();
+        assert_eq!(
+            Processor::process(&program_id, &accounts, &instruction_data),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    prop_compose! {
+        fn arb_greeting_account()(
+            counter in any::<u32>(),
+            counter_times_2 in any::<u32>(),
+            authority in any::<[u8; 32]>(),
+            flags in any::<u8>(),
+        ) -> GreetingAccount {
+            GreetingAccount {
+                counter,
+                counter_times_2,
+                authority: Pubkey::new_from_array(authority),
+                flags,
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_pack_matches_borsh(account in arb_greeting_account()) {
+            let borsh_bytes = account.try_to_vec().unwrap();
+            let mut packed = vec![0; GreetingAccount::LEN];
+            GreetingAccount::pack(account.clone(), &mut packed).unwrap();
+
+            prop_assert_eq!(borsh_bytes.len(), GreetingAccount::LEN);
+            prop_assert_eq!(&packed, &borsh_bytes);
+            prop_assert_eq!(
+                &packed[GreetingAccount::AUTHORITY_OFFSET..][..32],
+                account.authority.as_ref()
+            );
+            prop_assert_eq!(GreetingAccount::unpack_unchecked(&packed).unwrap(), account.clone());
+            prop_assert_eq!(GreetingAccount::try_from_slice(&borsh_bytes).unwrap(), account);
+        }
+
+        #[test]
+        fn test_unpack_matches_borsh(
+            data in prop::collection::vec(any::<u8>(), GreetingAccount::LEN)
+        ) {
+            let unpacked = GreetingAccount::unpack_unchecked(&data).unwrap();
+            prop_assert_eq!(&unpacked, &GreetingAccount::try_from_slice(&data).unwrap());
+
+            let mut repacked = vec![0; GreetingAccount::LEN];
+            GreetingAccount::pack(unpacked.clone(), &mut repacked).unwrap();
+            prop_assert_eq!(&repacked[..], &data[..]);
+            prop_assert_eq!(unpacked.try_to_vec().unwrap(), data);
+        }
+
+        #[test]
+        fn test_unpack_rejects_wrong_length(data in prop::collection::vec(any::<u8>(), 0..64)) {
+            prop_assume!(data.len() != GreetingAccount::LEN);
+            prop_assert_eq!(
+                GreetingAccount::unpack_unchecked(&data),
+                Err(ProgramError::InvalidAccountData)
+            );
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_schema() {
+        let account = GreetingAccount {
+            counter: 3,
+            counter_times_2: 6,
+            authority: Pubkey::new_from_array([1; 32]),
+            flags: GreetingAccount::TOP_LEVEL_ONLY,
+        };
+        let json = serde_json::to_string(&account).unwrap();
+        assert_eq!(
+            json,
+            format!(
+                r#"{{"counter":3,"counterTimes2":6,"authority":"{}","flags":1}}"#,
+                account.authority
+            )
+        );
+        assert_eq!(
+            serde_json::from_str::<GreetingAccount>(&json).unwrap(),
+            account
+        );
+    }
 }
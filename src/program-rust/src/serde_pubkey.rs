@@ -0,0 +1,15 @@
+//! Serializes a `Pubkey` as its base58 string, the form every Solana tool
+//! prints, instead of the default byte array.
+
+use serde::{de, Deserialize, Deserializer, Serializer};
+use solana_program::pubkey::Pubkey;
+use std::str::FromStr;
+
+pub fn serialize<S: Serializer>(pubkey: &Pubkey, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(pubkey)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Pubkey, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    Pubkey::from_str(&s).map_err(de::Error::custom)
+}
@@ -0,0 +1,197 @@
+use crate::error::GreetingError;
+
+/// Maximum size, in bytes, of a greeting string accepted by the program
+pub const MAX_GREETING_LEN: usize = 128;
+
+/// Which characters a greeting string may contain
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CharacterPolicy {
+    /// Any UTF-8 character except control characters
+    Any,
+    /// ASCII characters only
+    Ascii,
+    /// Printable characters only: no whitespace other than the ASCII space and
+    /// no invisible format characters such as U+200B ZERO WIDTH SPACE
+    Printable,
+}
+
+/// Code points of the Unicode `Cf` (format) general category
+const FORMAT_CHARACTERS: &[(char, char)] = &[
+    ('\u{ad}', '\u{ad}'),
+    ('\u{600}', '\u{605}'),
+    ('\u{61c}', '\u{61c}'),
+    ('\u{6dd}', '\u{6dd}'),
+    ('\u{70f}', '\u{70f}'),
+    ('\u{890}', '\u{891}'),
+    ('\u{8e2}', '\u{8e2}'),
+    ('\u{180e}', '\u{180e}'),
+    ('\u{200b}', '\u{200f}'),
+    ('\u{202a}', '\u{202e}'),
+    ('\u{2060}', '\u{2064}'),
+    ('\u{2066}', '\u{206f}'),
+    ('\u{feff}', '\u{feff}'),
+    ('\u{fff9}', '\u{fffb}'),
+    ('\u{110bd}', '\u{110bd}'),
+    ('\u{110cd}', '\u{110cd}'),
+    ('\u{13430}', '\u{1343f}'),
+    ('\u{1bca0}', '\u{1bca3}'),
+    ('\u{1d173}', '\u{1d17a}'),
+    ('\u{e0001}', '\u{e0001}'),
+    ('\u{e0020}', '\u{e007f}'),
+];
+
+fn is_format(c: char) -> bool {
+    FORMAT_CHARACTERS
+        .iter()
+        .any(|(first, last)| (*first..=*last).contains(&c))
+}
+
+/// Limits applied to the `greeting_string` of a greeting instruction
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GreetingPolicy {
+    /// Maximum length of the greeting string in bytes
+    pub max_len: usize,
+    /// Characters allowed in the greeting string
+    pub characters: CharacterPolicy,
+}
+
+impl GreetingPolicy {
+    /// The policy of greeting accounts that did not choose one, see
+    /// `GreetingAccount::greeting_policy`
+    pub const DEFAULT: Self = Self {
+        max_len: MAX_GREETING_LEN,
+        characters: CharacterPolicy::Any,
+    };
+
+    /// Checks `greeting` against this policy, reporting the first violation
+    pub fn validate(&self, greeting: &str) -> Result<(), GreetingError> {
+        if greeting.len() > self.max_len {
+            return Err(GreetingError::GreetingTooLong);
+        }
+        for c in greeting.chars() {
+            if c.is_control() {
+                return Err(GreetingError::GreetingControlCharacter);
+            }
+            match self.characters {
+                CharacterPolicy::Any => {}
+                CharacterPolicy::Ascii if !c.is_ascii() => {
+                    return Err(GreetingError::GreetingNotAscii)
+                }
+                CharacterPolicy::Printable if (c.is_whitespace() && c != ' ') || is_format(c) => {
+                    return Err(GreetingError::GreetingNotPrintable)
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for GreetingPolicy {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_max_len() {
+        let policy = GreetingPolicy {
+            max_len: 5,
+            characters: CharacterPolicy::Any,
+        };
+        assert_eq!(policy.validate(""), Ok(()));
+        assert_eq!(policy.validate("hello"), Ok(()));
+        assert_eq!(
+            policy.validate("hello!"),
+            Err(GreetingError::GreetingTooLong)
+        );
+        // the limit is in bytes, not characters
+        assert_eq!(
+            policy.validate("héllo"),
+            Err(GreetingError::GreetingTooLong)
+        );
+    }
+
+    #[test]
+    fn test_control_characters() {
+        for characters in [
+            CharacterPolicy::Any,
+            CharacterPolicy::Ascii,
+            CharacterPolicy::Printable,
+        ] {
+            let policy = GreetingPolicy {
+                characters,
+                ..GreetingPolicy::DEFAULT
+            };
+            for greeting in ["hello\n", "\0", "a\u{7f}b", "tab\t", "\u{85}"] {
+                assert_eq!(
+                    policy.validate(greeting),
+                    Err(GreetingError::GreetingControlCharacter)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_character_policies() {
+        let any = GreetingPolicy::DEFAULT;
+        let ascii = GreetingPolicy {
+            characters: CharacterPolicy::Ascii,
+            ..any
+        };
+        let printable = GreetingPolicy {
+            characters: CharacterPolicy::Printable,
+            ..any
+        };
+
+        assert_eq!(any.validate("hello, world!"), Ok(()));
+        assert_eq!(ascii.validate("hello, world!"), Ok(()));
+        assert_eq!(printable.validate("hello, world!"), Ok(()));
+
+        assert_eq!(any.validate("¡hola!"), Ok(()));
+        assert_eq!(
+            ascii.validate("¡hola!"),
+            Err(GreetingError::GreetingNotAscii)
+        );
+        assert_eq!(printable.validate("¡hola!"), Ok(()));
+
+        // no-break space
+        assert_eq!(any.validate("hello\u{a0}world"), Ok(()));
+        assert_eq!(
+            ascii.validate("hello\u{a0}world"),
+            Err(GreetingError::GreetingNotAscii)
+        );
+        assert_eq!(
+            printable.validate("hello\u{a0}world"),
+            Err(GreetingError::GreetingNotPrintable)
+        );
+    }
+
+    #[test]
+    fn test_format_characters() {
+        let printable = GreetingPolicy {
+            characters: CharacterPolicy::Printable,
+            ..GreetingPolicy::DEFAULT
+        };
+        // zero width space, byte order mark, soft hyphen, right-to-left override, tag
+        for greeting in [
+            "hello\u{200b}world",
+            "\u{feff}hello",
+            "hel\u{ad}lo",
+            "\u{202e}olleh",
+            "hello\u{e0041}",
+        ] {
+            assert_eq!(GreetingPolicy::DEFAULT.validate(greeting), Ok(()));
+            assert_eq!(
+                printable.validate(greeting),
+                Err(GreetingError::GreetingNotPrintable)
+            );
+        }
+        // neighbours of the format ranges are printable
+        assert_eq!(printable.validate("\u{ac}\u{2010}\u{fffc}"), Ok(()));
+    }
+}
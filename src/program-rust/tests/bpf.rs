@@ -0,0 +1,227 @@
+// Mark this test as BPF-only due to current `ProgramTest` limitations when
+// CPIing into the system program
+#![cfg(feature = "test-bpf")]
+
+use borsh::BorshDeserialize;
+use helloworld::{
+    error::GreetingError,
+    instruction::{init_greeting_with_nonce, migrate},
+    processor::{find_greeter_nonce_address, GreeterNonce, GreetingAccount},
+};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    instruction::InstructionError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::{Keypair, Signer},
+    system_program,
+    transaction::{Transaction, TransactionError},
+};
+
+#[tokio::test]
+async fn test_greeter_nonce_created_on_first_use() {
+    let program_id = Pubkey::new_unique();
+    let greeted_pubkey = Pubkey::new_unique();
+
+    let mut program_test = ProgramTest::default();
+    program_test.prefer_bpf(true);
+    program_test.add_program("helloworld", program_id, None);
+    program_test.add_account(
+        greeted_pubkey,
+        Account {
+            lamports: 5,
+            data: vec![0_u8; GreetingAccount::LEN],
+            owner: program_id,
+            ..Account::default()
+        },
+    );
+    let greeter = Keypair::new();
+    let (nonce_pubkey, _) = find_greeter_nonce_address(&program_id, &greeter.pubkey());
+    // Someone sent lamports to the prefunded greeter's nonce address already
+    let prefunded_greeter = Keypair::new();
+    let (prefunded_nonce_pubkey, _) =
+        find_greeter_nonce_address(&program_id, &prefunded_greeter.pubkey());
+    program_test.add_account(
+        prefunded_nonce_pubkey,
+        Account {
+            lamports: 1,
+            owner: system_program::id(),
+            ..Account::default()
+        },
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let rent = Rent::default().minimum_balance(GreeterNonce::LEN);
+
+    for (greeter, nonce_pubkey) in [
+        (&greeter, nonce_pubkey),
+        (&prefunded_greeter, prefunded_nonce_pubkey),
+    ] {
+        let mut transaction = Transaction::new_with_payer(
+            &[init_greeting_with_nonce(
+                program_id,
+                greeted_pubkey,
+                &greeter.pubkey(),
+                &payer.pubkey(),
+                1,
+                "hello".to_string(),
+                3,
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, greeter], recent_blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let nonce_account = banks_client
+            .get_account(nonce_pubkey)
+            .await
+            .expect("get_account")
+            .expect("nonce account not created");
+        assert_eq!(nonce_account.owner, program_id);
+        assert_eq!(nonce_account.lamports, rent);
+        assert_eq!(
+            GreeterNonce::unpack_unchecked(&nonce_account.data).unwrap(),
+            GreeterNonce { next_nonce: 4 }
+        );
+    }
+
+    // The nonce account exists now, a lower nonce is a replay
+    let mut transaction = Transaction::new_with_payer(
+        &[init_greeting_with_nonce(
+            program_id,
+            greeted_pubkey,
+            &greeter.pubkey(),
+            &payer.pubkey(),
+            1,
+            "hello".to_string(),
+            2,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &greeter], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(GreetingError::NonceReused as u32)
+        )
+    );
+
+    let greeted_account = banks_client
+        .get_account(greeted_pubkey)
+        .await
+        .expect("get_account")
+        .expect("greeted_account not found");
+    assert_eq!(
+        GreetingAccount::try_from_slice(&greeted_account.data)
+            .unwrap()
+            .counter,
+        2
+    );
+}
+
+#[tokio::test]
+async fn test_migrate_legacy_account() {
+    let program_id = Pubkey::new_unique();
+    let creator = Keypair::new();
+    let greeted_pubkey = Pubkey::create_with_seed(&creator.pubkey(), "hello", &program_id).unwrap();
+
+    let mut program_test = ProgramTest::default();
+    program_test.prefer_bpf(true);
+    program_test.add_program("helloworld", program_id, None);
+    // Counter 2, counter times 2 equals 4, from before accounts had an authority
+    program_test.add_account(
+        greeted_pubkey,
+        Account {
+            lamports: Rent::default().minimum_balance(GreetingAccount::LEGACY_LEN),
+            data: vec![2, 0, 0, 0, 4, 0, 0, 0],
+            owner: program_id,
+            ..Account::default()
+        },
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let authority = Pubkey::new_unique();
+
+    // Only the creator may migrate the account
+    let mut transaction = Transaction::new_with_payer(
+        &[migrate(
+            program_id,
+            greeted_pubkey,
+            payer.pubkey(),
+            payer.pubkey(),
+            "hello".to_string(),
+            authority,
+            0,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(GreetingError::InvalidCreator as u32)
+        )
+    );
+
+    let migrate_instruction = migrate(
+        program_id,
+        greeted_pubkey,
+        creator.pubkey(),
+        payer.pubkey(),
+        "hello".to_string(),
+        authority,
+        GreetingAccount::PRINTABLE_ONLY,
+    );
+    let mut transaction = Transaction::new_with_payer(
+        std::slice::from_ref(&migrate_instruction),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &creator], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let greeted_account = banks_client
+        .get_account(greeted_pubkey)
+        .await
+        .expect("get_account")
+        .expect("greeted_account not found");
+    assert_eq!(
+        greeted_account.lamports,
+        Rent::default().minimum_balance(GreetingAccount::LEN)
+    );
+    assert_eq!(
+        GreetingAccount::unpack(&greeted_account.data).unwrap(),
+        GreetingAccount {
+            counter: 2,
+            counter_times_2: 4,
+            authority,
+            flags: GreetingAccount::PRINTABLE_ONLY,
+        }
+    );
+
+    // Migrated accounts are no longer legacy ones
+    let recent_blockhash = banks_client
+        .get_new_latest_blockhash(&recent_blockhash)
+        .await
+        .unwrap();
+    let mut transaction =
+        Transaction::new_with_payer(&[migrate_instruction], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &creator], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(0, InstructionError::InvalidAccountData)
+    );
+}
@@ -1,14 +1,69 @@
 use borsh::{BorshDeserialize, BorshSerialize};
-use helloworld::{process_instruction, processor::GreetingAccount,instruction::{GreetingInstruction,InitGreetingArgs}};
+use helloworld::{
+    ed25519::new_ed25519_instruction,
+    error::GreetingError,
+    instruction::{
+        close, init_greeting, init_greeting_with_nonce, initialize, set_flags, signed_greeting,
+        GreetingInstruction, GreetingPayload, InitGreetingArgs,
+    },
+    process_instruction,
+    processor::{find_greeter_nonce_address, GreeterNonce, GreetingAccount},
+};
 use solana_program_test::*;
 use solana_sdk::{
     account::Account,
-    instruction::{AccountMeta, Instruction},
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    program::invoke,
+    program_pack::Pack,
     pubkey::Pubkey,
-    signature::Signer,
-    transaction::Transaction,
+    rent::Rent,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
 };
-use std::mem;
+
+/// Native `processor!` programs can't grow account data through CPI, so give
+/// the greeter a nonce account up front instead of letting the program create it.
+/// `tests/bpf.rs` covers the creation under `cargo test-bpf`.
+fn add_greeter_nonce_account(
+    program_test: &mut ProgramTest,
+    program_id: &Pubkey,
+    greeter: &Pubkey,
+) {
+    program_test.add_account(
+        find_greeter_nonce_address(program_id, greeter).0,
+        Account {
+            lamports: Rent::default().minimum_balance(GreeterNonce::LEN),
+            data: vec![0_u8; GreeterNonce::LEN],
+            owner: *program_id,
+            ..Account::default()
+        },
+    );
+}
+
+/// Forwards its instruction data to the program of its first account, passing
+/// along the remaining accounts
+fn proxy_process_instruction(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let (target_program, accounts) = accounts.split_first().unwrap();
+    let instruction = Instruction {
+        program_id: *target_program.key,
+        accounts: accounts
+            .iter()
+            .map(|account| AccountMeta {
+                pubkey: *account.key,
+                is_signer: account.is_signer,
+                is_writable: account.is_writable,
+            })
+            .collect(),
+        data: instruction_data.to_vec(),
+    };
+    invoke(&instruction, accounts)
+}
 
 #[tokio::test]
 async fn test_helloworld() {
@@ -24,11 +79,13 @@ async fn test_helloworld() {
         greeted_pubkey,
         Account {
             lamports: 5,
-            data: vec![0_u8; mem::size_of::<u32>() * 2],
+            data: vec![0_u8; GreetingAccount::LEN],
             owner: program_id,
             ..Account::default()
         },
     );
+    let greeter = Keypair::new();
+    add_greeter_nonce_account(&mut program_test, &program_id, &greeter.pubkey());
     let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
 
     // Verify account has zero greetings
@@ -47,10 +104,11 @@ async fn test_helloworld() {
     let instruction_data = GreetingInstruction::InitGreeting(InitGreetingArgs {
         num_greetings: 1,
         greeting_string: String::from("hello"),
+        nonce: None,
     })
     .try_to_vec()
     .unwrap();
-    
+
     // Greet once
     let mut transaction = Transaction::new_with_payer(
         &[Instruction::new_with_bytes(
@@ -76,26 +134,116 @@ async fn test_helloworld() {
         1
     );
 
-    // need new data to make sure instructions are unique in slot
-    let instruction_data = GreetingInstruction::InitGreeting(InitGreetingArgs {
-        num_greetings: 2,
-        greeting_string: String::from("hello"),
-    })
-    .try_to_vec()
-    .unwrap();
-    // Greet again
+    // Greet twice more with the same data, told apart by the greeter's nonce
+    for nonce in 1..=2 {
+        let mut transaction = Transaction::new_with_payer(
+            &[init_greeting_with_nonce(
+                program_id,
+                greeted_pubkey,
+                &greeter.pubkey(),
+                &payer.pubkey(),
+                1,
+                String::from("hello"),
+                nonce,
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &greeter], recent_blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+    }
+
+    // Verify account has three greetings
+    let greeted_account = banks_client
+        .get_account(greeted_pubkey)
+        .await
+        .expect("get_account")
+        .expect("greeted_account not found");
+    assert_eq!(
+        GreetingAccount::try_from_slice(&greeted_account.data)
+            .unwrap()
+            .counter,
+        3
+    );
+
+    // Reusing a nonce is rejected
     let mut transaction = Transaction::new_with_payer(
-        &[Instruction::new_with_bytes(
+        &[init_greeting_with_nonce(
             program_id,
-            &instruction_data,
-            vec![AccountMeta::new(greeted_pubkey, false)],
+            greeted_pubkey,
+            &greeter.pubkey(),
+            &payer.pubkey(),
+            1,
+            String::from("hello again"),
+            2,
         )],
         Some(&payer.pubkey()),
     );
+    transaction.sign(&[&payer, &greeter], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(GreetingError::NonceReused as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_signed_greeting() {
+    let program_id = Pubkey::new_unique();
+    let greeted_pubkey = Pubkey::new_unique();
+
+    let mut program_test =
+        ProgramTest::new("helloworld", program_id, processor!(process_instruction));
+    program_test.add_account(
+        greeted_pubkey,
+        Account {
+            lamports: 5,
+            data: vec![0_u8; GreetingAccount::LEN],
+            owner: program_id,
+            ..Account::default()
+        },
+    );
+    let relayer = Keypair::new();
+    program_test.add_account(
+        relayer.pubkey(),
+        Account {
+            lamports: 1_000_000_000,
+            ..Account::default()
+        },
+    );
+    let greeter = Keypair::new();
+    add_greeter_nonce_account(&mut program_test, &program_id, &greeter.pubkey());
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // The greeter signs off-chain, the payer relays
+    let payload = GreetingPayload {
+        program_id,
+        greeting_account: greeted_pubkey,
+        num_greetings: 3,
+        nonce: 1,
+    };
+    let signature = greeter.sign_message(&payload.try_to_vec().unwrap());
+
+    let mut transaction = Transaction::new_with_payer(
+        &signed_greeting(
+            program_id,
+            greeted_pubkey,
+            &greeter.pubkey(),
+            &payer.pubkey(),
+            signature.as_ref(),
+            3,
+            1,
+        ),
+        Some(&payer.pubkey()),
+    );
     transaction.sign(&[&payer], recent_blockhash);
     banks_client.process_transaction(transaction).await.unwrap();
 
-    // Verify account has two greetings
     let greeted_account = banks_client
         .get_account(greeted_pubkey)
         .await
@@ -107,4 +255,337 @@ async fn test_helloworld() {
             .counter,
         3
     );
+
+    // Relaying the same signed greeting again is a replay
+    let mut transaction = Transaction::new_with_payer(
+        &signed_greeting(
+            program_id,
+            greeted_pubkey,
+            &greeter.pubkey(),
+            &relayer.pubkey(),
+            signature.as_ref(),
+            3,
+            1,
+        ),
+        Some(&relayer.pubkey()),
+    );
+    transaction.sign(&[&relayer], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(GreetingError::NonceReused as u32)
+        )
+    );
+
+    // A valid signature over a different payload is rejected
+    let mut instructions = signed_greeting(
+        program_id,
+        greeted_pubkey,
+        &greeter.pubkey(),
+        &payer.pubkey(),
+        signature.as_ref(),
+        3,
+        1,
+    );
+    instructions[1] = signed_greeting(
+        program_id,
+        greeted_pubkey,
+        &greeter.pubkey(),
+        &payer.pubkey(),
+        signature.as_ref(),
+        3,
+        2,
+    )
+    .remove(1);
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(GreetingError::SignedPayloadMismatch as u32)
+        )
+    );
+
+    // A greeting signed for another program is rejected
+    let foreign_payload = GreetingPayload {
+        program_id: Pubkey::new_unique(),
+        nonce: 2,
+        ..payload
+    };
+    let foreign_payload_bytes = foreign_payload.try_to_vec().unwrap();
+    let foreign_signature = greeter.sign_message(&foreign_payload_bytes);
+    let mut instructions = signed_greeting(
+        program_id,
+        greeted_pubkey,
+        &greeter.pubkey(),
+        &payer.pubkey(),
+        foreign_signature.as_ref(),
+        3,
+        2,
+    );
+    instructions[0] = new_ed25519_instruction(
+        &greeter.pubkey(),
+        foreign_signature.as_ref(),
+        &foreign_payload_bytes,
+    );
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(GreetingError::SignedPayloadMismatch as u32)
+        )
+    );
+
+    // Without the Ed25519 instruction there is nothing to check
+    let instructions = signed_greeting(
+        program_id,
+        greeted_pubkey,
+        &greeter.pubkey(),
+        &payer.pubkey(),
+        signature.as_ref(),
+        3,
+        1,
+    );
+    let mut transaction = Transaction::new_with_payer(&instructions[1..], Some(&payer.pubkey()));
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(GreetingError::MissingSignatureInstruction as u32)
+        )
+    );
+
+    // The runtime rejects a forged signature before the program runs
+    let mut transaction = Transaction::new_with_payer(
+        &signed_greeting(
+            program_id,
+            greeted_pubkey,
+            &greeter.pubkey(),
+            &payer.pubkey(),
+            &[0; 64],
+            3,
+            1,
+        ),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_err());
+}
+
+#[tokio::test]
+async fn test_top_level_only() {
+    let program_id = Pubkey::new_unique();
+    let proxy_program_id = Pubkey::new_unique();
+    let creator = Keypair::new();
+    let greeted_pubkey = Pubkey::create_with_seed(&creator.pubkey(), "hello", &program_id).unwrap();
+
+    let mut program_test =
+        ProgramTest::new("helloworld", program_id, processor!(process_instruction));
+    program_test.add_program(
+        "proxy",
+        proxy_program_id,
+        processor!(proxy_process_instruction),
+    );
+    program_test.add_account(
+        greeted_pubkey,
+        Account {
+            lamports: 5,
+            data: vec![0_u8; GreetingAccount::LEN],
+            owner: program_id,
+            ..Account::default()
+        },
+    );
+    let authority = Keypair::new();
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let greet = |num_greetings| {
+        init_greeting(
+            program_id,
+            greeted_pubkey,
+            num_greetings,
+            String::from("hello"),
+        )
+    };
+    let proxy = |instruction: Instruction| {
+        let mut accounts = vec![AccountMeta::new_readonly(instruction.program_id, false)];
+        accounts.extend(instruction.accounts);
+        Instruction::new_with_bytes(proxy_program_id, &instruction.data, accounts)
+    };
+
+    // Greetings through CPI are counted until the flag is set
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            initialize(
+                program_id,
+                greeted_pubkey,
+                creator.pubkey(),
+                "hello".to_string(),
+                authority.pubkey(),
+                0,
+            ),
+            proxy(greet(1)),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &creator], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // Only the authority may set flags
+    let mut transaction = Transaction::new_with_payer(
+        &[set_flags(
+            program_id,
+            greeted_pubkey,
+            payer.pubkey(),
+            GreetingAccount::TOP_LEVEL_ONLY,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(GreetingError::InvalidAuthority as u32)
+        )
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[set_flags(
+            program_id,
+            greeted_pubkey,
+            authority.pubkey(),
+            GreetingAccount::TOP_LEVEL_ONLY,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &authority], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // CPI is now rejected
+    let mut transaction = Transaction::new_with_payer(&[proxy(greet(2))], Some(&payer.pubkey()));
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(GreetingError::CpiNotAllowed as u32)
+        )
+    );
+
+    // Top-level greetings still work
+    let mut transaction = Transaction::new_with_payer(&[greet(2)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let greeted_account = banks_client
+        .get_account(greeted_pubkey)
+        .await
+        .expect("get_account")
+        .expect("greeted_account not found");
+    let greeted_account = GreetingAccount::try_from_slice(&greeted_account.data).unwrap();
+    assert_eq!(greeted_account.counter, 3);
+    assert_eq!(greeted_account.authority, authority.pubkey());
+    assert!(greeted_account.top_level_only());
+}
+
+#[tokio::test]
+async fn test_close() {
+    let program_id = Pubkey::new_unique();
+    let creator = Keypair::new();
+    let greeted_pubkey = Pubkey::create_with_seed(&creator.pubkey(), "hello", &program_id).unwrap();
+    let recipient = Pubkey::new_unique();
+    let lamports = Rent::default().minimum_balance(GreetingAccount::LEN);
+
+    let mut program_test =
+        ProgramTest::new("helloworld", program_id, processor!(process_instruction));
+    program_test.add_account(
+        greeted_pubkey,
+        Account {
+            lamports,
+            data: vec![0_u8; GreetingAccount::LEN],
+            owner: program_id,
+            ..Account::default()
+        },
+    );
+    let authority = Keypair::new();
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[initialize(
+            program_id,
+            greeted_pubkey,
+            creator.pubkey(),
+            "hello".to_string(),
+            authority.pubkey(),
+            0,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &creator], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // Only the authority may close
+    let mut transaction = Transaction::new_with_payer(
+        &[close(program_id, greeted_pubkey, payer.pubkey(), recipient)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(GreetingError::InvalidAuthority as u32)
+        )
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[close(
+            program_id,
+            greeted_pubkey,
+            authority.pubkey(),
+            recipient,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &authority], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    assert_eq!(
+        banks_client.get_account(greeted_pubkey).await.unwrap(),
+        None
+    );
+    assert_eq!(banks_client.get_balance(recipient).await.unwrap(), lamports);
 }